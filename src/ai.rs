@@ -0,0 +1,894 @@
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_xpbd_2d::prelude::*;
+use std::collections::HashMap;
+
+use crate::boss::CurrentPhase;
+use crate::combat::{not_in_hitstop, not_in_super_freeze, GrabBox, Hitbox, SuperMeter};
+use crate::game_state::{AppState, BossType, Difficulty, GameConfig};
+use crate::habits::{HabitBias, HabitProfile};
+use crate::moves::{parse_attack_name, FrameCounter, MoveLibrary, FRAMES_PER_SECOND};
+use crate::player::{
+    AttackHeight, AttackPhase, AttackState, AttackType, BlockStance, BlockState, ControlType,
+    Grounded, Health, Player,
+};
+use crate::rng::{GameRng, RngStream};
+use crate::{AnimationState, GameAssets};
+
+// Attacks and hitboxes further away than this are not perceived as threats
+const THREAT_RANGE: f32 = 250.0;
+// Parrying bosses start blocking this many frames before the hit lands
+const PARRY_LEAD_FRAMES: u32 = 3;
+
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<BossTrees>()
+            .init_asset_loader::<BossTreesLoader>()
+            .add_systems(
+                FixedUpdate,
+                run_behavior_trees
+                    .run_if(in_state(AppState::InGame))
+                    .run_if(not_in_super_freeze)
+                    .run_if(not_in_hitstop),
+            );
+    }
+}
+
+// -- Behaviour Trees --
+
+// Every node succeeds or fails; actions write the boss's intents to its blackboard
+#[derive(Debug, Clone)]
+pub enum BehaviorNode {
+    Selector(Vec<BehaviorNode>), // Runs children until one succeeds
+    Sequence(Vec<BehaviorNode>), // Runs children until one fails
+    Parallel(Vec<BehaviorNode>), // Runs every child; succeeds if any did
+
+    // Conditions
+    InRange(f32),
+    OutOfRange(f32),
+    HealthBelow(f32),
+    MeterFull,
+    OpponentBlocking,
+    Chance(f32), // Per-tick probability
+    // Open for the first `window` seconds of every `period`
+    Every {
+        period: f32,
+        window: f32,
+    },
+
+    // Actions
+    Approach(f32),
+    Retreat(f32),
+    Wander(f32),    // Random direction every tick
+    Oscillate(f32), // Sways back and forth
+    Hold,
+    AttackIfInRange {
+        range: f32,
+        attacks: Vec<AttackType>,
+    },
+    BlockOnThreat(f32), // Blocks or parries a perceived threat within range
+    // Attacks an opponent caught in recovery within range
+    Punish {
+        range: f32,
+        attacks: Vec<AttackType>,
+    },
+    Jump,
+    JumpOver(f32), // Jumps toward an opponent within range to cross them up
+    // Attacks an airborne opponent within range, as often as they have been jumping
+    AntiAir {
+        range: f32,
+        attacks: Vec<AttackType>,
+    },
+    // Throws an opponent within range who tends to block after being hit
+    ThrowBlocker(f32),
+}
+
+impl BehaviorNode {
+    pub fn tick(&self, board: &mut Blackboard, rng: &mut GameRng) -> bool {
+        match self {
+            BehaviorNode::Selector(children) => children.iter().any(|child| child.tick(board, rng)),
+            BehaviorNode::Sequence(children) => children.iter().all(|child| child.tick(board, rng)),
+            BehaviorNode::Parallel(children) => {
+                let mut any = false;
+                for child in children {
+                    any |= child.tick(board, rng);
+                }
+                any
+            }
+
+            BehaviorNode::InRange(range) => board.distance() <= *range,
+            BehaviorNode::OutOfRange(range) => board.distance() > *range,
+            BehaviorNode::HealthBelow(ratio) => board.health_ratio < *ratio,
+            BehaviorNode::MeterFull => board.meter_full,
+            BehaviorNode::OpponentBlocking => board.opponent_blocking,
+            BehaviorNode::Chance(chance) => rng.chance(RngStream::Ai, chance * board.aggression),
+            BehaviorNode::Every { period, window } => {
+                board.elapsed % period < (window * board.aggression).min(*period)
+            }
+
+            BehaviorNode::Approach(speed) => {
+                board.move_direction = board.toward() * speed;
+                true
+            }
+            BehaviorNode::Retreat(speed) => {
+                board.move_direction = -board.toward() * speed;
+                true
+            }
+            BehaviorNode::Wander(speed) => {
+                board.move_direction = rng.signed(RngStream::Ai) * speed;
+                true
+            }
+            BehaviorNode::Oscillate(speed) => {
+                board.move_direction = board.elapsed.sin() * speed;
+                true
+            }
+            BehaviorNode::Hold => {
+                board.move_direction = 0.0;
+                true
+            }
+            BehaviorNode::AttackIfInRange { range, attacks } => {
+                if board.distance() > *range || attacks.is_empty() {
+                    return false;
+                }
+                board.attack = Some(attacks[rng.index(RngStream::Ai, attacks.len())]);
+                board.reacting = false;
+                true
+            }
+            BehaviorNode::BlockOnThreat(range) => {
+                let Some(threat) = board.threat.filter(|threat| threat.distance <= *range) else {
+                    return false;
+                };
+                match board.defense {
+                    Some(Defense::Block(stance)) => board.block = Some(stance),
+                    // Parries hold off until the hit is about to land, inside the parry window
+                    Some(Defense::Parry(stance))
+                        if threat.frames_until_hit <= PARRY_LEAD_FRAMES =>
+                    {
+                        board.block = Some(stance)
+                    }
+                    _ => return false,
+                }
+                true
+            }
+            BehaviorNode::Punish { range, attacks } => {
+                if board.punish != Some(true) || board.distance() > *range || attacks.is_empty() {
+                    return false;
+                }
+                board.attack = Some(attacks[rng.index(RngStream::Ai, attacks.len())]);
+                board.reacting = true;
+                true
+            }
+            BehaviorNode::Jump => {
+                board.jump = true;
+                true
+            }
+            BehaviorNode::JumpOver(range) => {
+                if board.distance() > *range {
+                    return false;
+                }
+                board.jump = true;
+                board.move_direction = board.toward();
+                true
+            }
+            BehaviorNode::AntiAir { range, attacks } => {
+                if !board.opponent_airborne
+                    || board.distance() > *range
+                    || attacks.is_empty()
+                    || !rng.chance(RngStream::Ai, board.habits.anti_air)
+                {
+                    return false;
+                }
+                board.attack = Some(attacks[rng.index(RngStream::Ai, attacks.len())]);
+                board.reacting = true;
+                true
+            }
+            BehaviorNode::ThrowBlocker(range) => {
+                if !(board.opponent_blocking || board.opponent_recovered_recently)
+                    || board.distance() > *range
+                    || !rng.chance(RngStream::Ai, board.habits.throw_blockers)
+                {
+                    return false;
+                }
+                board.attack = Some(AttackType::Throw);
+                board.reacting = true;
+                true
+            }
+        }
+    }
+
+    // Generic brawler used until the boss data is loaded
+    pub fn fallback() -> Self {
+        BehaviorNode::Parallel(vec![
+            BehaviorNode::Selector(vec![
+                BehaviorNode::Sequence(vec![
+                    BehaviorNode::HealthBelow(0.3),
+                    BehaviorNode::Retreat(1.0),
+                ]),
+                BehaviorNode::Approach(1.0),
+            ]),
+            BehaviorNode::Sequence(vec![
+                BehaviorNode::Every {
+                    period: 2.0,
+                    window: 0.2,
+                },
+                BehaviorNode::AttackIfInRange {
+                    range: 150.0,
+                    attacks: vec![AttackType::Light],
+                },
+            ]),
+        ])
+    }
+
+    // Nodes are single-key objects such as {"InRange": 150} or bare names such as "Hold".
+    // {"Subtree": "Name"} inlines a tree from the top-level "Subtrees" object. `expanding`
+    // holds the subtrees being inlined around this node, so a subtree can't include itself.
+    fn from_json(
+        value: &serde_json::Value,
+        subtrees: &serde_json::Map<String, serde_json::Value>,
+        expanding: &[&str],
+    ) -> Option<Self> {
+        let (name, args) = match value {
+            serde_json::Value::String(name) => (name.as_str(), &serde_json::Value::Null),
+            serde_json::Value::Object(obj) if obj.len() == 1 => {
+                let (name, args) = obj.iter().next()?;
+                (name.as_str(), args)
+            }
+            _ => {
+                tracing::warn!("Behaviour tree node must be a name or single-key object");
+                return None;
+            }
+        };
+        let children = || -> Vec<BehaviorNode> {
+            args.as_array()
+                .map(|nodes| {
+                    nodes
+                        .iter()
+                        .filter_map(|node| BehaviorNode::from_json(node, subtrees, expanding))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let number = || args.as_f64().map(|n| n as f32);
+
+        Some(match name {
+            "Selector" => BehaviorNode::Selector(children()),
+            "Sequence" => BehaviorNode::Sequence(children()),
+            "Parallel" => BehaviorNode::Parallel(children()),
+            "Subtree" => {
+                let Some((name, subtree)) = args
+                    .as_str()
+                    .and_then(|name| Some((name, subtrees.get(name)?)))
+                else {
+                    tracing::warn!("Unknown behaviour subtree {}", args);
+                    return None;
+                };
+                if expanding.contains(&name) {
+                    tracing::warn!("Behaviour subtree {} includes itself", name);
+                    return None;
+                }
+                let mut expanding = expanding.to_vec();
+                expanding.push(name);
+                return BehaviorNode::from_json(subtree, subtrees, &expanding);
+            }
+            "InRange" => BehaviorNode::InRange(number()?),
+            "OutOfRange" => BehaviorNode::OutOfRange(number()?),
+            "HealthBelow" => BehaviorNode::HealthBelow(number()?),
+            "MeterFull" => BehaviorNode::MeterFull,
+            "OpponentBlocking" => BehaviorNode::OpponentBlocking,
+            "Chance" => BehaviorNode::Chance(number()?),
+            "Every" => BehaviorNode::Every {
+                period: args.get("period")?.as_f64()? as f32,
+                window: args.get("window")?.as_f64()? as f32,
+            },
+            "Approach" => BehaviorNode::Approach(number().unwrap_or(1.0)),
+            "Retreat" => BehaviorNode::Retreat(number().unwrap_or(1.0)),
+            "Wander" => BehaviorNode::Wander(number().unwrap_or(1.0)),
+            "Oscillate" => BehaviorNode::Oscillate(number().unwrap_or(1.0)),
+            "Hold" => BehaviorNode::Hold,
+            "AttackIfInRange" => BehaviorNode::AttackIfInRange {
+                range: args.get("range")?.as_f64()? as f32,
+                attacks: args
+                    .get("attacks")?
+                    .as_array()?
+                    .iter()
+                    .filter_map(|attack| attack.as_str().and_then(parse_attack_name))
+                    .collect(),
+            },
+            "BlockOnThreat" => BehaviorNode::BlockOnThreat(number()?),
+            "Punish" => BehaviorNode::Punish {
+                range: args.get("range")?.as_f64()? as f32,
+                attacks: args
+                    .get("attacks")?
+                    .as_array()?
+                    .iter()
+                    .filter_map(|attack| attack.as_str().and_then(parse_attack_name))
+                    .collect(),
+            },
+            "Jump" => BehaviorNode::Jump,
+            "JumpOver" => BehaviorNode::JumpOver(number()?),
+            "AntiAir" => BehaviorNode::AntiAir {
+                range: args.get("range")?.as_f64()? as f32,
+                attacks: args
+                    .get("attacks")?
+                    .as_array()?
+                    .iter()
+                    .filter_map(|attack| attack.as_str().and_then(parse_attack_name))
+                    .collect(),
+            },
+            "ThrowBlocker" => BehaviorNode::ThrowBlocker(number()?),
+            _ => {
+                tracing::warn!("Unknown behaviour tree node '{}'", name);
+                return None;
+            }
+        })
+    }
+}
+
+// -- Perception --
+
+// An attack in startup or a hitbox already out, headed for the boss
+#[derive(Clone, Copy, Debug)]
+pub struct Threat {
+    pub height: AttackHeight,
+    pub attack_type: Option<AttackType>, // Known for attacks still in startup
+    pub distance: f32,
+    pub frames_until_hit: u32,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Defense {
+    Block(BlockStance),
+    Parry(BlockStance),
+    Take, // Doesn't defend this time
+}
+
+// Block, parry and punish tendencies of a boss, each a 0-1 weight, plus how strongly it
+// counters the player's habits (negative plays into them)
+#[derive(Clone, Copy, Debug)]
+pub struct Personality {
+    pub block: f32,
+    pub parry: f32,
+    pub punish: f32,
+    pub adapt: f32,
+}
+
+impl Default for Personality {
+    fn default() -> Self {
+        Personality {
+            block: 0.3,
+            parry: 0.1,
+            punish: 0.3,
+            adapt: 1.0,
+        }
+    }
+}
+
+impl Personality {
+    fn from_json(value: &serde_json::Value) -> Self {
+        let fallback = Personality::default();
+        let read = |key: &str, default: f32| {
+            value
+                .get(key)
+                .and_then(|v| v.as_f64())
+                .map(|v| v as f32)
+                .unwrap_or(default)
+        };
+        Personality {
+            block: read("block", fallback.block),
+            parry: read("parry", fallback.parry),
+            punish: read("punish", fallback.punish),
+            adapt: read("adapt", fallback.adapt),
+        }
+    }
+}
+
+// -- Blackboard --
+
+// Per-boss memory shared by the tree's nodes: what the boss perceives this tick and what it
+// intends to do. The fighter systems act on the intents.
+#[derive(Component, Default, Clone)]
+pub struct Blackboard {
+    // Perception
+    pub opponent_offset: Vec2, // Opponent position relative to this fighter
+    pub opponent_blocking: bool,
+    pub health_ratio: f32,
+    pub meter_full: bool,
+    pub elapsed: f32,           // Seconds since the fight started, for cadence nodes
+    pub aggression: f32,        // Difficulty scaling for chance and cadence nodes
+    pub threat: Option<Threat>, // The most urgent attack or hitbox headed this way
+    pub opponent_recovering: bool,
+    pub opponent_airborne: bool,
+    pub opponent_recovered_recently: bool, // Just left hitstun
+    pub habits: HabitBias,                 // Counters to the player's habits this fight
+
+    // Reactions, each decided once the reaction time has passed
+    threat_since: Option<f32>,
+    recovery_since: Option<f32>,
+    pub defense: Option<Defense>, // How the boss answers the current threat
+    pub punish: Option<bool>,     // Whether the boss punishes the current recovery
+
+    // Intents
+    pub move_direction: f32,
+    pub attack: Option<AttackType>,
+    pub reacting: bool, // The attack answers a read (punish, anti-air) rather than spacing
+    pub block: Option<BlockStance>,
+    pub jump: bool,
+}
+
+impl Blackboard {
+    pub fn distance(&self) -> f32 {
+        self.opponent_offset.length()
+    }
+
+    // -1.0 or 1.0 toward the opponent
+    pub fn toward(&self) -> f32 {
+        if self.opponent_offset.x > 0.0 {
+            1.0
+        } else {
+            -1.0
+        }
+    }
+
+    // Rolls a defense once the boss has had time to react to a threat, and a punish once it
+    // has had time to react to the opponent's recovery
+    fn react(&mut self, personality: Personality, difficulty: &Difficulty, rng: &mut GameRng) {
+        let reaction = difficulty.reaction_frames() as f32 / FRAMES_PER_SECOND;
+        let skill = difficulty.defense_skill();
+
+        match self.threat {
+            Some(threat) => {
+                let since = *self.threat_since.get_or_insert(self.elapsed);
+                // The player's favourite attack is read sooner (or later, when inverted)
+                let reaction = if threat.attack_type.is_some()
+                    && threat.attack_type == self.habits.favourite
+                {
+                    reaction * self.habits.read_factor
+                } else {
+                    reaction
+                };
+                if self.defense.is_none() && self.elapsed - since >= reaction {
+                    let correct = match threat.height {
+                        AttackHeight::High => BlockStance::High,
+                        AttackHeight::Low => BlockStance::Low,
+                    };
+                    let stance = if rng.chance(RngStream::Ai, difficulty.stance_accuracy()) {
+                        correct
+                    } else if correct == BlockStance::High {
+                        BlockStance::Low
+                    } else {
+                        BlockStance::High
+                    };
+                    let roll = rng.f32(RngStream::Ai);
+                    self.defense = Some(if roll < personality.parry * skill {
+                        Defense::Parry(stance)
+                    } else if roll < (personality.parry + personality.block) * skill {
+                        Defense::Block(stance)
+                    } else {
+                        Defense::Take
+                    });
+                }
+            }
+            None => {
+                self.threat_since = None;
+                self.defense = None;
+            }
+        }
+
+        if self.opponent_recovering {
+            let since = *self.recovery_since.get_or_insert(self.elapsed);
+            if self.punish.is_none() && self.elapsed - since >= reaction {
+                self.punish = Some(rng.chance(RngStream::Ai, personality.punish * skill));
+            }
+        } else {
+            self.recovery_since = None;
+            self.punish = None;
+        }
+    }
+
+    fn clear_intents(&mut self) {
+        self.move_direction = 0.0;
+        self.attack = None;
+        self.reacting = false;
+        self.block = None;
+        self.jump = false;
+    }
+}
+
+// -- Data --
+
+// Behaviour tree and personality for every boss, keyed by boss name
+#[derive(Asset, TypePath, Debug, Clone, Default)]
+pub struct BossTrees {
+    trees: HashMap<String, BehaviorNode>,
+    personalities: HashMap<String, Personality>,
+}
+
+impl BossTrees {
+    pub fn get(&self, boss: BossType) -> Option<&BehaviorNode> {
+        self.trees.get(boss_name(boss))
+    }
+
+    pub fn personality(&self, boss: BossType) -> Option<Personality> {
+        self.personalities.get(boss_name(boss)).copied()
+    }
+
+    fn from_json(json: &serde_json::Value) -> Result<Self, std::io::Error> {
+        let bosses_obj = json.as_object().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "boss AI data must be an object keyed by boss name",
+            )
+        })?;
+        let no_subtrees = serde_json::Map::new();
+        let subtrees = bosses_obj
+            .get("Subtrees")
+            .and_then(|subtrees| subtrees.as_object())
+            .unwrap_or(&no_subtrees);
+
+        let mut personalities = HashMap::new();
+        if let Some(personalities_obj) = bosses_obj
+            .get("Personalities")
+            .and_then(|personalities| personalities.as_object())
+        {
+            for (boss, personality_value) in personalities_obj {
+                personalities.insert(boss.clone(), Personality::from_json(personality_value));
+            }
+        }
+
+        let mut trees = HashMap::new();
+        for (boss, tree_value) in bosses_obj {
+            if boss == "Subtrees" || boss == "Personalities" {
+                continue;
+            }
+            if let Some(tree) = BehaviorNode::from_json(tree_value, subtrees, &[]) {
+                trees.insert(boss.clone(), tree);
+            }
+        }
+
+        Ok(BossTrees {
+            trees,
+            personalities,
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct BossTreesLoader;
+
+impl AssetLoader for BossTreesLoader {
+    type Asset = BossTrees;
+    type Settings = ();
+    type Error = std::io::Error;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<BossTrees, std::io::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let json = serde_json::from_slice::<serde_json::Value>(&bytes)?;
+        let boss_trees = BossTrees::from_json(&json)?;
+        tracing::info!(
+            "Behaviour trees loaded for {} bosses",
+            boss_trees.trees.len()
+        );
+        Ok(boss_trees)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ai.json"]
+    }
+}
+
+// Looks up a boss's behaviour tree, which is missing until the asset is loaded
+#[derive(SystemParam)]
+pub struct BehaviorLibrary<'w> {
+    boss_trees: Res<'w, Assets<BossTrees>>,
+    game_assets: Res<'w, GameAssets>,
+}
+
+impl BehaviorLibrary<'_> {
+    pub fn get(&self, boss: BossType) -> Option<&BehaviorNode> {
+        self.boss_trees
+            .get(&self.game_assets.boss_ai)
+            .and_then(|boss_trees| boss_trees.get(boss))
+    }
+
+    pub fn personality(&self, boss: BossType) -> Personality {
+        self.boss_trees
+            .get(&self.game_assets.boss_ai)
+            .and_then(|boss_trees| boss_trees.personality(boss))
+            .unwrap_or_default()
+    }
+}
+
+// -- Systems --
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+pub fn run_behavior_trees(
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    frames: Res<FrameCounter>,
+    mut rng: ResMut<GameRng>,
+    habit_profile: Res<HabitProfile>,
+    behavior_library: BehaviorLibrary,
+    move_library: MoveLibrary,
+    mut boss_query: Query<(
+        Entity,
+        &ControlType,
+        &Transform,
+        &Health,
+        &SuperMeter,
+        &mut Blackboard,
+        Option<&CurrentPhase>,
+    )>,
+    opponent_query: Query<
+        (
+            Entity,
+            &Transform,
+            &AttackState,
+            &BlockState,
+            &AnimationState,
+            &Grounded,
+        ),
+        With<Player>,
+    >,
+    hitbox_query: Query<
+        (
+            &Hitbox,
+            &Transform,
+            Option<&Parent>,
+            Option<&LinearVelocity>,
+        ),
+        Without<GrabBox>,
+    >,
+    transform_query: Query<&Transform>,
+) {
+    let fallback = BehaviorNode::fallback();
+
+    for (entity, control, transform, health, meter, mut board, phase) in boss_query.iter_mut() {
+        let ControlType::AI(boss) = control else {
+            continue;
+        };
+        let Some((
+            opponent,
+            opponent_transform,
+            opponent_attack,
+            opponent_block,
+            opponent_animation,
+            opponent_grounded,
+        )) = opponent_query
+            .iter()
+            .find(|(opponent, ..)| *opponent != entity)
+        else {
+            board.clear_intents();
+            continue;
+        };
+
+        board.opponent_offset = (opponent_transform.translation - transform.translation).truncate();
+        board.opponent_blocking = opponent_block.is_blocking;
+        board.health_ratio = health.current as f32 / health.max as f32;
+        board.meter_full = meter.is_full();
+        // Counted in ticks so cadences line up the same way when a fight is replayed
+        board.elapsed = frames.0 as f32 / FRAMES_PER_SECOND;
+        board.aggression = 1.0 / config.difficulty.attack_frequency_multiplier();
+        board.opponent_recovering = opponent_attack
+            .current
+            .as_ref()
+            .is_some_and(|attack| attack.phase == AttackPhase::Recovery);
+        board.opponent_airborne = !opponent_grounded.0;
+        board.opponent_recovered_recently =
+            habit_profile.recovered_recently(time.elapsed_seconds());
+        let personality = behavior_library.personality(*boss);
+        board.habits =
+            HabitBias::from_profile(&habit_profile, time.elapsed_seconds(), personality.adapt);
+
+        // Read the opponent's attack while it's still in startup
+        let mut threat = opponent_attack
+            .current
+            .as_ref()
+            .filter(|attack| {
+                attack.phase == AttackPhase::Startup
+                    && attack.attack_type != AttackType::Throw
+                    && board.distance() <= THREAT_RANGE
+            })
+            .map(|attack| Threat {
+                height: move_library
+                    .get(opponent_animation.character_type, attack.attack_type)
+                    .height,
+                attack_type: Some(attack.attack_type.ground_variant()),
+                distance: board.distance(),
+                frames_until_hit: attack.startup_frames.saturating_sub(attack.frame()),
+            });
+        // Hitboxes already out, including projectiles in flight
+        for (hitbox, hitbox_transform, parent, velocity) in hitbox_query.iter() {
+            if hitbox.owner != opponent {
+                continue;
+            }
+            // Attached hitboxes sit relative to the attacker. `GlobalTransform` only catches
+            // up after the tick and isn't rolled back, so positions are worked out here.
+            let offset = hitbox_transform.translation.truncate();
+            let attacker = parent.and_then(|parent| transform_query.get(parent.get()).ok());
+            let position =
+                attacker.map_or(offset, |attacker| attacker.translation.truncate() + offset);
+            let to_boss = transform.translation.truncate() - position;
+            let distance = to_boss.length();
+            if distance > THREAT_RANGE {
+                continue;
+            }
+            // Only hitboxes reaching or flying toward the boss are a threat
+            let toward_boss = (transform.translation.x - opponent_transform.translation.x).signum();
+            let reaching_away = attacker.is_some() && offset.x * toward_boss < 0.0;
+            let closing_speed = velocity.map_or(0.0, |velocity| velocity.x * to_boss.x.signum());
+            if reaching_away || closing_speed < 0.0 {
+                continue;
+            }
+            let frames_until_hit = if closing_speed > 0.0 {
+                (distance / closing_speed * FRAMES_PER_SECOND) as u32
+            } else {
+                0
+            };
+            if threat.map_or(true, |threat| frames_until_hit < threat.frames_until_hit) {
+                threat = Some(Threat {
+                    height: hitbox.height,
+                    attack_type: None,
+                    distance,
+                    frames_until_hit,
+                });
+            }
+        }
+        board.threat = threat;
+        board.react(personality, &config.difficulty, &mut rng);
+
+        board.clear_intents();
+        behavior_library
+            .get(*boss)
+            .unwrap_or(&fallback)
+            .tick(&mut board, &mut rng);
+
+        // Later boss phases swap in their own move sets for regular attacks; punishes and
+        // anti-airs keep the move their node picked
+        let phase_moves = phase.map_or(&[][..], |phase| phase.phase(*boss).moves);
+        if let Some(attack) = board.attack.filter(|_| !board.reacting) {
+            if !phase_moves.is_empty() && !matches!(attack, AttackType::Super | AttackType::Throw) {
+                board.attack = Some(phase_moves[rng.index(RngStream::Ai, phase_moves.len())]);
+            }
+        }
+    }
+}
+
+fn boss_name(boss: BossType) -> &'static str {
+    match boss {
+        BossType::NullPointer => "NullPointer",
+        BossType::UndefinedBehavior => "UndefinedBehavior",
+        BossType::DataRace => "DataRace",
+        BossType::UseAfterFree => "UseAfterFree",
+        BossType::BufferOverflow => "BufferOverflow",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: serde_json::Value) -> Option<BehaviorNode> {
+        BehaviorNode::from_json(&json, &serde_json::Map::new(), &[])
+    }
+
+    #[test]
+    fn parses_names_and_arguments() {
+        assert!(matches!(
+            parse(serde_json::json!("Hold")),
+            Some(BehaviorNode::Hold)
+        ));
+        assert!(matches!(
+            parse(serde_json::json!({ "InRange": 120.0 })),
+            Some(BehaviorNode::InRange(range)) if range == 120.0
+        ));
+        assert!(matches!(
+            parse(serde_json::json!({ "Every": { "period": 3.0, "window": 0.5 } })),
+            Some(BehaviorNode::Every { period, window }) if period == 3.0 && window == 0.5
+        ));
+        // Movement speed defaults to full
+        assert!(matches!(
+            parse(serde_json::json!("Approach")),
+            Some(BehaviorNode::Approach(speed)) if speed == 1.0
+        ));
+    }
+
+    #[test]
+    fn parses_attack_lists() {
+        let node = parse(serde_json::json!({
+            "AttackIfInRange": { "range": 90.0, "attacks": ["Light", "Kick", "Dropkick"] },
+        }));
+        let Some(BehaviorNode::AttackIfInRange { range, attacks }) = node else {
+            panic!("expected AttackIfInRange, got {:?}", node);
+        };
+        assert_eq!(range, 90.0);
+        assert_eq!(attacks, vec![AttackType::Light, AttackType::Kick]);
+    }
+
+    #[test]
+    fn rejects_bad_nodes() {
+        assert!(parse(serde_json::json!("Teleport")).is_none());
+        assert!(parse(serde_json::json!({ "InRange": "close" })).is_none());
+        assert!(parse(serde_json::json!({ "Hold": null, "Jump": null })).is_none());
+        assert!(parse(serde_json::json!(42)).is_none());
+    }
+
+    #[test]
+    fn composites_drop_bad_children() {
+        let node = parse(serde_json::json!({
+            "Selector": ["Jump", "Teleport", { "Sequence": ["MeterFull", "Hold"] }],
+        }));
+        let Some(BehaviorNode::Selector(children)) = node else {
+            panic!("expected Selector, got {:?}", node);
+        };
+        assert_eq!(children.len(), 2);
+        assert!(matches!(children[0], BehaviorNode::Jump));
+        assert!(matches!(&children[1], BehaviorNode::Sequence(nodes) if nodes.len() == 2));
+    }
+
+    #[test]
+    fn subtrees_are_inlined() {
+        let subtrees = serde_json::json!({ "keep_away": { "Retreat": 0.5 } });
+        let subtrees = subtrees.as_object().expect("subtrees are an object");
+        let node = BehaviorNode::from_json(
+            &serde_json::json!({ "Subtree": "keep_away" }),
+            subtrees,
+            &[],
+        );
+        assert!(matches!(node, Some(BehaviorNode::Retreat(speed)) if speed == 0.5));
+        assert!(BehaviorNode::from_json(
+            &serde_json::json!({ "Subtree": "missing" }),
+            subtrees,
+            &[]
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn subtree_cycles_are_dropped() {
+        let subtrees = serde_json::json!({
+            "a": { "Selector": [{ "Subtree": "b" }, "Hold"] },
+            "b": { "Subtree": "a" },
+        });
+        let subtrees = subtrees.as_object().expect("subtrees are an object");
+        let node = BehaviorNode::from_json(&serde_json::json!({ "Subtree": "a" }), subtrees, &[]);
+        assert!(
+            matches!(node, Some(BehaviorNode::Selector(children)) if matches!(children[..], [BehaviorNode::Hold]))
+        );
+    }
+
+    #[test]
+    fn reaction_picks_are_marked() {
+        let mut board = Blackboard {
+            opponent_offset: Vec2::new(50.0, 0.0),
+            punish: Some(true),
+            ..default()
+        };
+        let mut rng = GameRng::new(0);
+        let tree = parse(serde_json::json!({
+            "Selector": [
+                { "Punish": { "range": 100.0, "attacks": ["Heavy"] } },
+                { "AttackIfInRange": { "range": 100.0, "attacks": ["Light"] } },
+            ],
+        }))
+        .expect("tree parses");
+        tree.tick(&mut board, &mut rng);
+        assert_eq!(board.attack, Some(AttackType::Heavy));
+        assert!(board.reacting);
+
+        board.clear_intents();
+        board.punish = Some(false);
+        tree.tick(&mut board, &mut rng);
+        assert_eq!(board.attack, Some(AttackType::Light));
+        assert!(!board.reacting);
+    }
+}
@@ -0,0 +1,278 @@
+use bevy::prelude::*;
+use bevy_xpbd_2d::prelude::*;
+
+use crate::combat::{
+    apply_damage, check_for_game_over, Hitstun, HurtboxDisabled, Juggle, Knockdown, Thrown,
+};
+use crate::game_state::{AppState, ArenaType, BossType};
+use crate::moves::frames_to_duration;
+use crate::player::{AttackState, AttackType, ControlType, Health};
+use crate::{arena_texture, ArenaBackground, GameAssets};
+
+// Invulnerable power-up pose between phases
+const PHASE_TRANSITION_FRAMES: u32 = 90;
+
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PhaseChangeEvent>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    // Runs before the game over check so a final phase can catch a lethal hit
+                    update_boss_phases
+                        .after(apply_damage)
+                        .before(check_for_game_over),
+                    update_phase_transitions,
+                )
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(
+                Update,
+                change_arena_on_phase.run_if(in_state(AppState::InGame)),
+            );
+    }
+}
+
+// -- Phase Definitions --
+
+pub struct BossPhase {
+    pub name: &'static str,
+    pub health_threshold: f32, // Starts once health falls to this fraction of max
+    pub refill: f32,           // Fraction of max health restored when the phase starts
+    pub moves: &'static [AttackType], // Attacks picked from; empty keeps the boss's signature choice
+    pub speed_multiplier: f32,
+    pub arena: Option<ArenaType>, // Background swapped in when the phase starts
+    pub arena_tint: Color,
+}
+
+const OPENING_PHASE: BossPhase = BossPhase {
+    name: "",
+    health_threshold: 1.0,
+    refill: 0.0,
+    moves: &[],
+    speed_multiplier: 1.0,
+    arena: None,
+    arena_tint: Color::WHITE,
+};
+
+const NULL_POINTER_PHASES: &[BossPhase] = &[
+    OPENING_PHASE,
+    BossPhase {
+        name: "Segmentation Fault",
+        health_threshold: 0.5,
+        moves: &[AttackType::Light, AttackType::Kick],
+        speed_multiplier: 1.2,
+        arena_tint: Color::srgb(0.6, 0.6, 0.8),
+        ..OPENING_PHASE
+    },
+];
+
+const UNDEFINED_BEHAVIOR_PHASES: &[BossPhase] = &[
+    OPENING_PHASE,
+    BossPhase {
+        name: "Nasal Demons",
+        health_threshold: 0.5,
+        moves: &[
+            AttackType::Light,
+            AttackType::Heavy,
+            AttackType::Kick,
+            AttackType::Projectile,
+        ],
+        speed_multiplier: 1.3,
+        arena: Some(ArenaType::UndefinedBehavior),
+        arena_tint: Color::srgb(0.9, 0.6, 1.0),
+        ..OPENING_PHASE
+    },
+];
+
+const DATA_RACE_PHASES: &[BossPhase] = &[
+    OPENING_PHASE,
+    BossPhase {
+        name: "Deadlock",
+        health_threshold: 0.5,
+        moves: &[AttackType::Light, AttackType::Kick, AttackType::Projectile],
+        speed_multiplier: 1.3,
+        arena: Some(ArenaType::DataRace),
+        arena_tint: Color::srgb(1.0, 0.7, 0.7),
+        ..OPENING_PHASE
+    },
+];
+
+const USE_AFTER_FREE_PHASES: &[BossPhase] = &[
+    OPENING_PHASE,
+    BossPhase {
+        name: "Double Free",
+        health_threshold: 0.5,
+        moves: &[AttackType::Heavy, AttackType::Kick],
+        speed_multiplier: 1.2,
+        arena_tint: Color::srgb(0.7, 1.0, 0.7),
+        ..OPENING_PHASE
+    },
+];
+
+// The final boss overflows back to life instead of dying
+const BUFFER_OVERFLOW_PHASES: &[BossPhase] = &[
+    OPENING_PHASE,
+    BossPhase {
+        name: "Stack Smash",
+        health_threshold: 0.6,
+        moves: &[AttackType::Heavy, AttackType::Kick, AttackType::Projectile],
+        speed_multiplier: 1.1,
+        arena_tint: Color::srgb(1.0, 0.8, 0.6),
+        ..OPENING_PHASE
+    },
+    BossPhase {
+        name: "Heap Spray",
+        health_threshold: 0.0,
+        refill: 0.4,
+        moves: &[
+            AttackType::Light,
+            AttackType::Heavy,
+            AttackType::Kick,
+            AttackType::Projectile,
+        ],
+        speed_multiplier: 1.4,
+        arena: Some(ArenaType::UndefinedBehavior),
+        arena_tint: Color::srgb(1.0, 0.4, 0.4),
+    },
+];
+
+pub fn boss_phases(boss: BossType) -> &'static [BossPhase] {
+    match boss {
+        BossType::NullPointer => NULL_POINTER_PHASES,
+        BossType::UndefinedBehavior => UNDEFINED_BEHAVIOR_PHASES,
+        BossType::DataRace => DATA_RACE_PHASES,
+        BossType::UseAfterFree => USE_AFTER_FREE_PHASES,
+        BossType::BufferOverflow => BUFFER_OVERFLOW_PHASES,
+    }
+}
+
+// -- Components & Events --
+
+// Index into the boss's phase list
+#[derive(Component, Default, Clone)]
+pub struct CurrentPhase(pub usize);
+
+impl CurrentPhase {
+    pub fn phase(&self, boss: BossType) -> &'static BossPhase {
+        &boss_phases(boss)[self.0]
+    }
+}
+
+// The boss is powering up into a new phase and can't act or be hit
+#[derive(Component, Clone)]
+pub struct PhaseTransition {
+    pub timer: Timer,
+}
+
+#[derive(Event)]
+pub struct PhaseChangeEvent {
+    pub boss: Entity,
+    pub phase: usize,
+}
+
+// -- Systems --
+
+#[allow(clippy::type_complexity)]
+fn update_boss_phases(
+    mut commands: Commands,
+    mut query: Query<
+        (
+            Entity,
+            &ControlType,
+            &mut Health,
+            &mut CurrentPhase,
+            &mut AttackState,
+            &mut LinearVelocity,
+        ),
+        Without<PhaseTransition>,
+    >,
+    mut phase_writer: EventWriter<PhaseChangeEvent>,
+) {
+    for (entity, control, mut health, mut current, mut attack_state, mut velocity) in
+        query.iter_mut()
+    {
+        let ControlType::AI(boss) = control else {
+            continue;
+        };
+
+        // A big hit can skip past several thresholds at once
+        let phases = boss_phases(*boss);
+        let ratio = health.current as f32 / health.max as f32;
+        let mut next = current.0;
+        while next + 1 < phases.len() && ratio <= phases[next + 1].health_threshold {
+            next += 1;
+        }
+        if next == current.0 {
+            continue;
+        }
+
+        let phase = &phases[next];
+        if phase.refill > 0.0 {
+            health.current = health
+                .current
+                .max((health.max as f32 * phase.refill) as i32);
+        }
+        // A boss knocked out by the hit stays down
+        if health.current <= 0 {
+            continue;
+        }
+
+        current.0 = next;
+        attack_state.current = None;
+        velocity.x = 0.0;
+        let duration = frames_to_duration(PHASE_TRANSITION_FRAMES);
+        // The power-up pose replaces any hitstun, knockdown, juggle or throw in progress
+        commands
+            .entity(entity)
+            .remove::<(Hitstun, Knockdown, Juggle, Thrown)>()
+            .insert((
+                PhaseTransition {
+                    timer: Timer::new(duration, TimerMode::Once),
+                },
+                HurtboxDisabled {
+                    timer: Timer::new(duration, TimerMode::Once),
+                },
+            ));
+        phase_writer.send(PhaseChangeEvent {
+            boss: entity,
+            phase: next,
+        });
+        tracing::info!("{:?} entered phase {}: {}", boss, next + 1, phase.name);
+    }
+}
+
+fn update_phase_transitions(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut PhaseTransition)>,
+) {
+    for (entity, mut transition) in query.iter_mut() {
+        transition.timer.tick(time.delta());
+        if transition.timer.finished() {
+            commands.entity(entity).remove::<PhaseTransition>();
+        }
+    }
+}
+
+fn change_arena_on_phase(
+    assets: Res<GameAssets>,
+    mut phase_reader: EventReader<PhaseChangeEvent>,
+    boss_query: Query<&ControlType>,
+    mut background_query: Query<(&mut Handle<Image>, &mut Sprite), With<ArenaBackground>>,
+) {
+    for event in phase_reader.read() {
+        let Ok(ControlType::AI(boss)) = boss_query.get(event.boss) else {
+            continue;
+        };
+        let phase = &boss_phases(*boss)[event.phase];
+        for (mut texture, mut sprite) in background_query.iter_mut() {
+            if let Some(arena) = phase.arena {
+                *texture = arena_texture(&assets, arena);
+            }
+            sprite.color = phase.arena_tint;
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use bevy::prelude::*;
+use bevy_xpbd_2d::prelude::*;
+
+use crate::combat::{Hitbox, Hurtbox, Knockdown, LandingRecovery};
+use crate::game_state::AppState;
+use crate::moves::MoveLibrary;
+use crate::player::{
+    AttackState, AttackType, BlockStance, BlockState, FacingDirection, Grounded, Player,
+};
+use crate::status::{StatusEffects, StatusKind};
+use crate::AnimationState;
+
+const FIGHTER_HEIGHT: f32 = 100.0;
+
+pub struct BoxesPlugin;
+
+impl Plugin for BoxesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (update_fighter_boxes, update_hitbox_frames).run_if(in_state(AppState::InGame)),
+        );
+    }
+}
+
+// -- Collision Layers --
+
+// Pushboxes keep fighters apart and on the ground; hitboxes and hurtboxes only see each other
+#[derive(PhysicsLayer, Clone, Copy, Debug)]
+pub enum GameLayer {
+    Arena,
+    Pushbox,
+    Hurtbox,
+    Hitbox,
+}
+
+impl GameLayer {
+    pub fn arena() -> CollisionLayers {
+        CollisionLayers::new(GameLayer::Arena, GameLayer::Pushbox)
+    }
+
+    pub fn pushbox() -> CollisionLayers {
+        CollisionLayers::new(GameLayer::Pushbox, [GameLayer::Arena, GameLayer::Pushbox])
+    }
+
+    pub fn hurtbox() -> CollisionLayers {
+        CollisionLayers::new(GameLayer::Hurtbox, GameLayer::Hitbox)
+    }
+
+    pub fn hitbox() -> CollisionLayers {
+        CollisionLayers::new(GameLayer::Hitbox, GameLayer::Hurtbox)
+    }
+
+    // Whether a collider is a member of this layer
+    pub fn contains(self, layers: &CollisionLayers) -> bool {
+        layers.memberships.0 & self.to_bits() != 0
+    }
+}
+
+// -- Box Layouts --
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HurtboxRegion {
+    Head,
+    Torso,
+    Legs,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BoxShape {
+    pub offset: Vec2, // Relative to the fighter's center when facing right
+    pub size: Vec2,
+}
+
+pub const fn shape(x: f32, y: f32, width: f32, height: f32) -> BoxShape {
+    BoxShape {
+        offset: Vec2::new(x, y),
+        size: Vec2::new(width, height),
+    }
+}
+
+// Pushbox width and hurtboxes for a single animation frame
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FrameBoxes {
+    pub pushbox_width: f32,
+    pub head: BoxShape,
+    pub torso: BoxShape,
+    pub legs: BoxShape,
+}
+
+impl FrameBoxes {
+    pub fn region(&self, region: HurtboxRegion) -> BoxShape {
+        match region {
+            HurtboxRegion::Head => self.head,
+            HurtboxRegion::Torso => self.torso,
+            HurtboxRegion::Legs => self.legs,
+        }
+    }
+}
+
+// The parts of a layout that a pose or attack frame changes; the rest is kept
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct BoxOverrides {
+    pub pushbox_width: Option<f32>,
+    pub head: Option<BoxShape>,
+    pub torso: Option<BoxShape>,
+    pub legs: Option<BoxShape>,
+}
+
+impl BoxOverrides {
+    pub fn apply(&self, boxes: FrameBoxes) -> FrameBoxes {
+        FrameBoxes {
+            pushbox_width: self.pushbox_width.unwrap_or(boxes.pushbox_width),
+            head: self.head.unwrap_or(boxes.head),
+            torso: self.torso.unwrap_or(boxes.torso),
+            legs: self.legs.unwrap_or(boxes.legs),
+        }
+    }
+}
+
+// Used when the move data has no standing layout
+const STANDING: FrameBoxes = FrameBoxes {
+    pushbox_width: 50.0,
+    head: shape(0.0, 35.0, 30.0, 30.0),
+    torso: shape(0.0, 0.0, 44.0, 40.0),
+    legs: shape(0.0, -35.0, 40.0, 30.0),
+};
+
+// Poses outside of attacks; each is a change to the standing layout
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum BoxPose {
+    Standing,
+    Crouching, // Low blocks and landings duck under high attacks
+    Jumping,
+    Falling, // Knocked down
+}
+
+impl BoxPose {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "Standing" => Some(BoxPose::Standing),
+            "Crouching" => Some(BoxPose::Crouching),
+            "Jumping" => Some(BoxPose::Jumping),
+            "Falling" => Some(BoxPose::Falling),
+            _ => None,
+        }
+    }
+
+    // Built-in layouts used when the move data asset is missing or has no entry for the pose
+    pub fn fallback(self) -> BoxOverrides {
+        match self {
+            BoxPose::Standing => BoxOverrides::default(),
+            BoxPose::Crouching => BoxOverrides {
+                head: Some(shape(0.0, 5.0, 30.0, 30.0)),
+                torso: Some(shape(0.0, -20.0, 44.0, 30.0)),
+                legs: Some(shape(0.0, -40.0, 48.0, 20.0)),
+                ..default()
+            },
+            BoxPose::Jumping => BoxOverrides {
+                pushbox_width: Some(40.0),
+                legs: Some(shape(0.0, -28.0, 36.0, 24.0)), // Legs tucked in
+                ..default()
+            },
+            BoxPose::Falling => BoxOverrides {
+                head: Some(shape(-35.0, -38.0, 24.0, 24.0)),
+                torso: Some(shape(0.0, -40.0, 50.0, 20.0)),
+                legs: Some(shape(35.0, -40.0, 30.0, 18.0)),
+                ..default()
+            },
+        }
+    }
+}
+
+// Pose, attack and hurtbox frame entry, facing right and overflow stacks
+type LayoutKey = (BoxPose, Option<(AttackType, usize)>, bool, u32);
+
+// The layout last applied to a fighter, so colliders are only rebuilt when it changes
+#[derive(Component, Default)]
+pub struct AppliedBoxLayout(Option<LayoutKey>);
+
+// Hitbox whose shape follows the owner's active frame; holds the frame entry in use
+#[derive(Component, Clone)]
+pub struct AnimatedHitbox {
+    pub applied: Option<usize>,
+}
+
+// Head, torso and legs hurtboxes, in the standing layout until the first update
+pub fn spawn_hurtboxes(parent: &mut ChildBuilder) {
+    let owner = parent.parent_entity();
+    for region in [
+        HurtboxRegion::Head,
+        HurtboxRegion::Torso,
+        HurtboxRegion::Legs,
+    ] {
+        let box_shape = STANDING.region(region);
+        parent.spawn((
+            SpatialBundle::from_transform(Transform::from_translation(
+                box_shape.offset.extend(0.0),
+            )),
+            Collider::rectangle(box_shape.size.x, box_shape.size.y),
+            Sensor,
+            GameLayer::hurtbox(),
+            Hurtbox { owner, region },
+        ));
+    }
+}
+
+// -- Systems --
+
+// Lays out each fighter's boxes from the move data: the pose's layout, with the current
+// frame of an attack in progress on top
+#[allow(clippy::type_complexity)]
+fn update_fighter_boxes(
+    move_library: MoveLibrary,
+    mut fighters: Query<
+        (
+            &AnimationState,
+            &AttackState,
+            &BlockState,
+            &Grounded,
+            &FacingDirection,
+            &Children,
+            &mut Collider,
+            &mut AppliedBoxLayout,
+            Option<&StatusEffects>,
+            Has<Knockdown>,
+            Has<LandingRecovery>,
+        ),
+        With<Player>,
+    >,
+    mut hurtboxes: Query<(&Hurtbox, &mut Transform, &mut Collider), Without<Player>>,
+) {
+    for (
+        animation_state,
+        attack_state,
+        block_state,
+        grounded,
+        facing,
+        children,
+        mut pushbox,
+        mut applied,
+        status,
+        knocked_down,
+        landing,
+    ) in fighters.iter_mut()
+    {
+        let character = animation_state.character_type;
+        let pose = if knocked_down {
+            BoxPose::Falling
+        } else if landing || (block_state.is_blocking && block_state.stance == BlockStance::Low) {
+            BoxPose::Crouching
+        } else if !grounded.0 {
+            BoxPose::Jumping
+        } else {
+            BoxPose::Standing
+        };
+        let attack_frame = attack_state.current.as_ref().and_then(|attack| {
+            let move_data = move_library.get(character, attack.attack_type);
+            let index = move_data.hurtbox_frame_index(attack.frame())?;
+            Some((
+                attack.attack_type,
+                index,
+                move_data.hurtbox_frames[index].boxes,
+            ))
+        });
+        let facing_right = matches!(facing, FacingDirection::Right);
+        let layout = (
+            pose,
+            attack_frame.map(|(attack_type, index, _)| (attack_type, index)),
+            facing_right,
+            status
+                .map(|status| status.stacks(StatusKind::Overflow))
+                .unwrap_or(0),
+        );
+        if applied.0 == Some(layout) {
+            continue;
+        }
+        applied.0 = Some(layout);
+
+        let pose_boxes = |pose: BoxPose| {
+            move_library
+                .pose_boxes(character, pose)
+                .unwrap_or_else(|| pose.fallback())
+        };
+        let standing = pose_boxes(BoxPose::Standing).apply(STANDING);
+        let mut boxes = pose_boxes(pose).apply(standing);
+        if let Some((_, _, frame_boxes)) = attack_frame {
+            boxes = frame_boxes.apply(boxes);
+        }
+        *pushbox = Collider::rectangle(boxes.pushbox_width, FIGHTER_HEIGHT);
+
+        // Overflow grows the hurtboxes, not the pushbox
+        let scale = status.map(|status| status.hurtbox_scale()).unwrap_or(1.0);
+        let mirror = if facing_right { 1.0 } else { -1.0 };
+        for &child in children.iter() {
+            if let Ok((hurtbox, mut transform, mut collider)) = hurtboxes.get_mut(child) {
+                let box_shape = boxes.region(hurtbox.region);
+                transform.translation.x = box_shape.offset.x * mirror;
+                transform.translation.y = box_shape.offset.y;
+                *collider = Collider::rectangle(box_shape.size.x * scale, box_shape.size.y * scale);
+            }
+        }
+    }
+}
+
+// Reshapes attached hitboxes as the owner's attack moves through its active frames
+fn update_hitbox_frames(
+    move_library: MoveLibrary,
+    attackers: Query<(&AttackState, &AnimationState, &FacingDirection)>,
+    mut hitboxes: Query<(&Hitbox, &mut AnimatedHitbox, &mut Transform, &mut Collider)>,
+) {
+    for (hitbox, mut animated, mut transform, mut collider) in hitboxes.iter_mut() {
+        let Ok((attack_state, animation_state, facing)) = attackers.get(hitbox.owner) else {
+            continue;
+        };
+        let Some(attack) = &attack_state.current else {
+            continue;
+        };
+
+        let move_data = move_library.get(animation_state.character_type, attack.attack_type);
+        let active_frame = attack.frame().saturating_sub(attack.startup_frames);
+        let index = move_data.hitbox_frame_index(active_frame);
+        if index == animated.applied {
+            continue;
+        }
+        animated.applied = index;
+
+        let (offset, size) = move_data.hitbox_shape(active_frame);
+        let mirror = match facing {
+            FacingDirection::Right => 1.0,
+            FacingDirection::Left => -1.0,
+        };
+        transform.translation.x = offset.x * mirror;
+        transform.translation.y = offset.y;
+        *collider = Collider::rectangle(size.x, size.y);
+    }
+}
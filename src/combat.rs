@@ -1,15 +1,30 @@
 use bevy::prelude::*;
 use bevy_xpbd_2d::prelude::*;
+use std::collections::HashSet;
 use std::time::Duration;
 
+use crate::boss::PhaseTransition;
+use crate::boxes::{AnimatedHitbox, GameLayer, HurtboxRegion};
 use crate::game_state::{AppState, GameConfig, PlayerProgress, Winner};
-use crate::player::{BlockState, ControlType, FacingDirection, Health, Player};
-use crate::GameAssets;
+use crate::input::InputBuffer;
+use crate::moves::{frames_to_duration, MoveLibrary};
+use crate::netplay::frame_confirmed;
+use crate::player::{
+    update_attack_state, AttackHeight, AttackPhase, AttackState, AttackType, BlockState,
+    ControlType, Dash, FacingDirection, Grounded, Health, Player, GUARD_BREAK_STUN_FRAMES,
+    PARRY_STUN_FRAMES,
+};
+use crate::replay::Replays;
+use crate::rng::{seed_fight_rng, EffectsRng, GameRng, RngStream};
+use crate::{AnimationState, GameAssets};
 
-#[derive(Resource)]
+#[derive(Resource, Clone)]
 pub struct FightTracker {
     pub fight_start_time: Option<f32>,
-    pub current_combo: u32,
+    pub best_combo: u32,   // Longest combo landed by a human player this fight
+    pub counter_hits: u32, // Counter hits landed by a human player this fight
+    pub seed: u64,         // RNG seed the fight was played with
+    pub decided: bool,     // The outcome is recorded; ticks can still run before the state changes
     #[allow(dead_code)]
     pub boss: crate::game_state::BossType,
 }
@@ -20,20 +35,80 @@ impl Plugin for CombatPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<SpawnHitboxEvent>()
             .add_event::<DamageEvent>()
-            .add_systems(OnEnter(AppState::InGame), initialize_fight_tracker)
+            .add_event::<ParryEvent>()
+            .add_event::<SuperEvent>()
+            .add_event::<CounterHitEvent>()
+            .add_event::<HitstunEndedEvent>()
+            .init_resource::<SuperFreeze>()
+            .init_resource::<Hitstop>()
             .add_systems(
-                Update,
+                OnEnter(AppState::InGame),
+                initialize_fight_tracker.after(seed_fight_rng),
+            )
+            .add_systems(
+                OnExit(AppState::InGame),
+                (end_super_freeze, end_impact_feedback),
+            )
+            .add_systems(
+                FixedUpdate,
                 (
-                    spawn_hitbox,
-                    play_attack_sound.after(spawn_hitbox),
+                    spawn_hitbox.after(update_attack_state),
                     despawn_hitbox_after_duration,
-                    detect_collisions,
-                    play_hit_sound.after(detect_collisions),
-                    apply_damage.after(detect_collisions),
-                    spawn_particles_on_hit.after(apply_damage),
-                    despawn_particles_after_duration.after(spawn_particles_on_hit),
-                    check_for_game_over.after(apply_damage),
+                    cancel_clashing_projectiles
+                        .after(spawn_hitbox)
+                        .before(detect_collisions),
+                    update_stun_timers,
+                    update_juggles,
+                    land_aerial_attacks,
+                    update_knockdowns.after(update_juggles),
+                    update_hurtbox_disabled,
+                    update_throws,
+                    detect_collisions
+                        .after(update_stun_timers)
+                        .after(update_juggles)
+                        .after(update_knockdowns)
+                        .after(update_hurtbox_disabled),
+                    apply_damage.after(detect_collisions).after(update_throws),
+                    gain_super_meter
+                        .after(detect_collisions)
+                        .after(update_throws),
+                    check_for_game_over
+                        .after(apply_damage)
+                        .run_if(frame_confirmed),
                     update_combo_tracker.after(apply_damage),
+                    track_counter_hits.after(detect_collisions),
+                )
+                    .run_if(in_state(AppState::InGame)),
+            )
+            // Sound and particles only present what the simulation did
+            .add_systems(
+                Update,
+                (
+                    play_attack_sound,
+                    play_hit_sound,
+                    play_parry_sound,
+                    spawn_particles_on_hit,
+                    despawn_particles_after_duration.after(spawn_particles_on_hit),
+                )
+                    .run_if(in_state(AppState::InGame)),
+            )
+            // Freezes start on the tick that caused them, so they land on the same tick in a
+            // replay, then run on real time with the simulation clock paused
+            .add_systems(
+                FixedUpdate,
+                (
+                    start_super_freeze.after(spawn_hitbox),
+                    start_hitstop.after(detect_collisions).after(update_throws),
+                )
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(
+                Update,
+                (
+                    update_super_freeze,
+                    update_hitstop,
+                    shake_camera_on_hit,
+                    update_camera_shake.after(shake_camera_on_hit),
                 )
                     .run_if(in_state(AppState::InGame)),
             );
@@ -51,25 +126,254 @@ pub struct SpawnHitboxEvent {
 #[derive(Event)]
 pub struct DamageEvent {
     pub target: Entity,
+    pub attacker: Entity,
     pub damage: i32,
+    pub drain: bool, // Damage over time from a status effect; skips the hit feedback
+}
+
+// Sent when a fighter spends a full meter on a super
+#[derive(Event)]
+pub struct SuperEvent {
+    pub attacker: Entity,
+}
+
+#[derive(Event)]
+pub struct ParryEvent {
+    pub defender: Entity,
+}
+
+// Sent when a hit lands during the startup of the defender's own attack
+#[derive(Event)]
+pub struct CounterHitEvent {
+    pub attacker: Entity,
+    pub defender: Entity,
+}
+
+// Sent on the tick a fighter's hitstun runs out
+#[derive(Event)]
+pub struct HitstunEndedEvent {
+    pub defender: Entity,
 }
 
 // -- Components --
 
+// One of a fighter's head/torso/legs hurtboxes, spawned as a child of the fighter
 #[derive(Component)]
-pub struct Hurtbox;
+pub struct Hurtbox {
+    pub owner: Entity,
+    pub region: HurtboxRegion,
+}
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct Hitbox {
     pub damage: i32,
+    pub knockback: f32,
+    pub hitstun: Duration,
+    pub blockstun: Duration,
+    pub height: AttackHeight,
+    pub chip_damage: i32,
+    pub guard_damage: f32,
+    pub knockdown: bool,
+    pub juggle_points: u32,
     pub owner: Entity,
 }
 
+#[derive(Component, Clone)]
+pub struct Projectile {
+    pub size: Vec2, // Used for projectile-vs-projectile clashes
+}
+
+// Marks a hitbox as a grab box: it ignores blocking but only catches grounded, unstunned fighters
 #[derive(Component)]
+pub struct GrabBox;
+
+#[derive(Component, Clone)]
 pub struct HitboxDuration {
     pub timer: Timer,
 }
 
+// -- Stun Components --
+
+// A fighter in hitstun or blockstun ignores input and AI decisions until the timer ends
+#[derive(Component, Clone)]
+pub struct Hitstun {
+    pub timer: Timer,
+}
+
+#[derive(Component, Clone)]
+pub struct Blockstun {
+    pub timer: Timer,
+}
+
+// Hard knockdown: the fighter falls, stays down, then wakes up with invincibility
+#[derive(Component, Clone)]
+pub struct Knockdown {
+    pub timer: Timer, // Time spent on the ground, starts on landing
+    pub landed: bool,
+}
+
+// A fighter caught by a throw; pressing throw before the timer ends techs out of it
+#[derive(Component, Clone)]
+pub struct Thrown {
+    pub timer: Timer,
+    pub thrower: Entity,
+    pub damage: i32,
+    pub knockback: f32,
+}
+
+// Airborne after a hit or launch; further hits spend juggle points until the defender lands
+#[derive(Component, Clone)]
+pub struct Juggle {
+    pub points: u32,
+    pub airborne: bool, // Set once the defender has actually left the ground
+    pub takeoff: Timer, // A defender still grounded when this ends is treated as landed
+}
+
+impl Juggle {
+    fn new(points: u32, airborne: bool) -> Self {
+        Juggle {
+            points,
+            airborne,
+            takeoff: Timer::new(frames_to_duration(JUGGLE_TAKEOFF_FRAMES), TimerMode::Once),
+        }
+    }
+}
+
+// Short recovery after landing from an aerial attack or a juggle
+#[derive(Component, Clone)]
+pub struct LandingRecovery {
+    pub timer: Timer,
+}
+
+// Query filter for fighters that are free to act
+pub type NotStunned = (
+    Without<Hitstun>,
+    Without<Blockstun>,
+    Without<Knockdown>,
+    Without<Thrown>,
+    Without<Juggle>,
+    Without<LandingRecovery>,
+    Without<PhaseTransition>,
+);
+
+// Query filter for defenders that keep a combo going
+type InCombo = Or<(With<Hitstun>, With<Juggle>)>;
+
+// -- Invincibility --
+
+// Disables a fighter's hurtbox until the timer ends (wake-up and tech-roll invincibility)
+#[derive(Component, Clone)]
+pub struct HurtboxDisabled {
+    pub timer: Timer,
+}
+
+// Query filter for hurtboxes that can currently be hit; knocked-down fighters are only
+// vulnerable while they are being juggled
+pub type HurtboxEnabled = (Without<HurtboxDisabled>, Without<Thrown>);
+
+const KNOCKDOWN_FRAMES: u32 = 45;
+const KNOCKDOWN_LAUNCH: f32 = 300.0;
+const TECH_WINDOW_FRAMES: u32 = 10;
+const TECH_ROLL_FRAMES: u32 = 18;
+const WAKEUP_INVINCIBLE_FRAMES: u32 = 20;
+const THROW_TECH_FRAMES: u32 = 10;
+const THROW_TECH_PUSH: f32 = 300.0;
+
+// -- Juggles --
+
+const JUGGLE_POINT_LIMIT: u32 = 4;
+const JUGGLE_LAUNCH: f32 = 250.0;
+const AIRBORNE_RECOIL_SCALE: f32 = 0.5; // Airborne defenders are popped up rather than pushed away
+const JUGGLE_TAKEOFF_FRAMES: u32 = 10;
+const JUGGLE_LANDING_RECOVERY_FRAMES: u32 = 12;
+
+// Counter hits deal extra damage and leave the defender stunned for longer
+const COUNTER_HIT_DAMAGE_MULTIPLIER: f32 = 1.25;
+const COUNTER_HIT_HITSTUN_MULTIPLIER: f32 = 1.5;
+
+// -- Combo Components --
+
+// Each hit after the first in a combo deals 10% less damage, down to 30%
+const COMBO_DAMAGE_SCALING: f32 = 0.1;
+const MIN_COMBO_DAMAGE_SCALE: f32 = 0.3;
+
+// Ongoing combo for an attacker; it ends when the target leaves hitstun
+#[derive(Component, Clone, Default)]
+pub struct Combo {
+    pub hits: u32,
+    pub total_damage: i32,
+    pub target: Option<Entity>,
+}
+
+impl Combo {
+    pub fn scaled_damage(&self, base_damage: i32) -> i32 {
+        let scale = (1.0 - COMBO_DAMAGE_SCALING * self.hits as f32).max(MIN_COMBO_DAMAGE_SCALE);
+        ((base_damage as f32 * scale).round() as i32).max(1)
+    }
+
+    fn reset(&mut self) {
+        self.hits = 0;
+        self.total_damage = 0;
+        self.target = None;
+    }
+}
+
+// -- Super Meter --
+
+pub const MAX_SUPER_METER: f32 = 100.0;
+const METER_PER_DAMAGE_DEALT: f32 = 3.0;
+const METER_PER_DAMAGE_TAKEN: f32 = 2.0;
+const SUPER_FREEZE_DURATION: Duration = Duration::from_millis(600);
+
+#[derive(Component, Clone, Default)]
+pub struct SuperMeter {
+    pub current: f32,
+}
+
+impl SuperMeter {
+    pub fn is_full(&self) -> bool {
+        self.current >= MAX_SUPER_METER
+    }
+
+    fn gain(&mut self, amount: f32) {
+        self.current = (self.current + amount).min(MAX_SUPER_METER);
+    }
+}
+
+// Cinematic freeze while a super starts; gameplay time is paused until the timer ends
+#[derive(Resource, Default)]
+pub struct SuperFreeze {
+    timer: Option<Timer>,
+}
+
+pub fn not_in_super_freeze(freeze: Res<SuperFreeze>) -> bool {
+    freeze.timer.is_none()
+}
+
+// -- Impact Feedback --
+
+// Hitstop lasts a few frames, plus one frame for every 2 points of damage
+const HITSTOP_BASE_FRAMES: u32 = 3;
+const HITSTOP_MAX_FRAMES: u32 = 12;
+const MAX_SHAKE_OFFSET: f32 = 12.0;
+const SHAKE_DECAY_PER_SECOND: f32 = 2.5;
+
+// Freezes the fight on impact; like the super freeze it pauses gameplay time
+#[derive(Resource, Default)]
+pub struct Hitstop {
+    timer: Option<Timer>,
+}
+
+pub fn not_in_hitstop(hitstop: Res<Hitstop>) -> bool {
+    hitstop.timer.is_none()
+}
+
+// Camera shake strength; the offset scales with trauma squared and decays over time
+#[derive(Component, Default)]
+pub struct CameraShake {
+    pub trauma: f32,
+}
+
 // -- Particle Components --
 
 #[derive(Component)]
@@ -85,58 +389,85 @@ pub struct ParticleDuration {
 fn spawn_hitbox(
     mut commands: Commands,
     mut event_reader: EventReader<SpawnHitboxEvent>,
-    query: Query<&FacingDirection>,
+    query: Query<(&FacingDirection, &AnimationState, &Transform)>,
+    move_library: MoveLibrary,
 ) {
     for event in event_reader.read() {
         match query.get(event.attacker) {
-            Ok(facing) => {
-                // Different properties based on attack type
-                let (offset, size, damage, duration) = match event.attack_type {
-                    crate::player::AttackType::Light => (
-                        match facing {
-                            FacingDirection::Right => Vec2::new(55.0, 0.0),
-                            FacingDirection::Left => Vec2::new(-55.0, 0.0),
-                        },
-                        Vec2::new(60.0, 35.0),      // Smaller hitbox
-                        3,                          // Lower damage
-                        Duration::from_millis(120), // Shorter duration
-                    ),
-                    crate::player::AttackType::Heavy => (
-                        match facing {
-                            FacingDirection::Right => Vec2::new(70.0, 0.0),
-                            FacingDirection::Left => Vec2::new(-70.0, 0.0),
-                        },
-                        Vec2::new(80.0, 45.0),      // Larger hitbox
-                        8,                          // Higher damage
-                        Duration::from_millis(200), // Longer duration
-                    ),
-                    crate::player::AttackType::Kick => (
-                        match facing {
-                            FacingDirection::Right => Vec2::new(65.0, -10.0), // Slightly lower for kick
-                            FacingDirection::Left => Vec2::new(-65.0, -10.0),
-                        },
-                        Vec2::new(70.0, 40.0),      // Medium hitbox
-                        5,                          // Medium damage
-                        Duration::from_millis(160), // Medium duration
-                    ),
+            Ok((facing, animation_state, attacker_transform)) => {
+                // Hitbox properties come from the attacker's move data
+                let move_data = move_library.get(animation_state.character_type, event.attack_type);
+                let (hitbox_offset, hitbox_size) = move_data.hitbox_shape(0);
+                let (offset, direction) = match facing {
+                    FacingDirection::Right => (hitbox_offset, 1.0),
+                    FacingDirection::Left => (Vec2::new(-hitbox_offset.x, hitbox_offset.y), -1.0),
                 };
 
-                commands
-                    .spawn((
-                        SpatialBundle::from_transform(Transform::from_translation(
-                            offset.extend(0.0),
-                        )),
-                        Collider::rectangle(size.x, size.y),
-                        Sensor,
-                        Hitbox {
-                            damage,
-                            owner: event.attacker,
-                        },
-                        HitboxDuration {
-                            timer: Timer::new(duration, TimerMode::Once),
-                        },
-                    ))
-                    .set_parent(event.attacker);
+                let hitbox = Hitbox {
+                    damage: move_data.damage,
+                    knockback: move_data.knockback,
+                    hitstun: move_data.hitstun(),
+                    blockstun: move_data.blockstun(),
+                    height: move_data.height,
+                    chip_damage: move_data.chip_damage,
+                    guard_damage: move_data.guard_damage,
+                    knockdown: move_data.knockdown,
+                    juggle_points: move_data.juggle_points,
+                    owner: event.attacker,
+                };
+                let duration = HitboxDuration {
+                    timer: Timer::new(move_data.hitbox_lifetime(), TimerMode::Once),
+                };
+                let collider = Collider::rectangle(hitbox_size.x, hitbox_size.y);
+
+                match &move_data.projectile {
+                    Some(projectile) => {
+                        // Projectiles live in world space so they can travel across the arena
+                        let position = attacker_transform.translation.truncate() + offset;
+                        commands.spawn((
+                            SpriteBundle {
+                                sprite: Sprite {
+                                    color: Color::srgb(0.2, 1.0, 0.4), // Overflowing data green
+                                    custom_size: Some(move_data.hitbox_size),
+                                    ..default()
+                                },
+                                transform: Transform::from_xyz(position.x, position.y, 1.0),
+                                ..default()
+                            },
+                            RigidBody::Kinematic,
+                            LinearVelocity(Vec2::new(projectile.speed * direction, 0.0)),
+                            collider,
+                            Sensor,
+                            GameLayer::hitbox(),
+                            hitbox,
+                            duration,
+                            Projectile {
+                                size: move_data.hitbox_size,
+                            },
+                        ));
+                    }
+                    None => {
+                        let mut hitbox_commands = commands.spawn((
+                            SpatialBundle::from_transform(Transform::from_translation(
+                                offset.extend(0.0),
+                            )),
+                            collider,
+                            Sensor,
+                            GameLayer::hitbox(),
+                            hitbox,
+                            duration,
+                        ));
+                        if event.attack_type == AttackType::Throw {
+                            hitbox_commands.insert(GrabBox);
+                        }
+                        if !move_data.hitbox_frames.is_empty() {
+                            hitbox_commands.insert(AnimatedHitbox {
+                                applied: move_data.hitbox_frame_index(0),
+                            });
+                        }
+                        hitbox_commands.set_parent(event.attacker);
+                    }
+                }
             }
             Err(_) => {
                 tracing::warn!(
@@ -147,6 +478,31 @@ fn spawn_hitbox(
     }
 }
 
+// Projectiles that meet cancel each other out
+fn cancel_clashing_projectiles(
+    mut commands: Commands,
+    query: Query<(Entity, &Transform, &Projectile, &Hitbox)>,
+) {
+    let projectiles: Vec<_> = query.iter().collect();
+    for (i, (entity_a, transform_a, projectile_a, hitbox_a)) in projectiles.iter().enumerate() {
+        for (entity_b, transform_b, projectile_b, hitbox_b) in projectiles.iter().skip(i + 1) {
+            if hitbox_a.owner == hitbox_b.owner {
+                continue;
+            }
+
+            let delta = (transform_a.translation - transform_b.translation)
+                .truncate()
+                .abs();
+            let reach = (projectile_a.size + projectile_b.size) / 2.0;
+            if delta.x < reach.x && delta.y < reach.y {
+                tracing::info!("Projectiles cancelled out!");
+                commands.entity(*entity_a).despawn_recursive();
+                commands.entity(*entity_b).despawn_recursive();
+            }
+        }
+    }
+}
+
 fn despawn_hitbox_after_duration(
     mut commands: Commands,
     time: Res<Time>,
@@ -160,46 +516,172 @@ fn despawn_hitbox_after_duration(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn detect_collisions(
     mut commands: Commands, // Added: a way to issue commands like despawning
     mut collisions: EventReader<Collision>,
-    hitbox_query: Query<&Hitbox>,
+    hitbox_query: Query<(Entity, &Hitbox, Has<Projectile>, Has<GrabBox>)>,
     hurtbox_query: Query<&Hurtbox>,
-    block_query: Query<&BlockState>,
+    layers_query: Query<&CollisionLayers>,
+    vulnerable_query: Query<(Option<&Knockdown>, Option<&Juggle>), HurtboxEnabled>,
+    mut block_query: Query<&mut BlockState>,
+    mut attack_state_query: Query<&mut AttackState>,
+    mut combo_query: Query<&mut Combo>,
+    defender_query: Query<(&Grounded, Has<Hitstun>, Has<Blockstun>)>,
     mut damage_writer: EventWriter<DamageEvent>,
+    mut parry_writer: EventWriter<ParryEvent>,
+    mut counter_writer: EventWriter<CounterHitEvent>,
     transform_query: Query<&Transform>,
 ) {
+    let mut spent_hitboxes = HashSet::new();
     for Collision(contacts) in collisions.read() {
-        // Determine which entity is the hitbox and which is the hurtbox
-        let (hitbox_entity, hurtbox_entity) = if hitbox_query.contains(contacts.entity1)
-            && hurtbox_query.contains(contacts.entity2)
-        {
-            (contacts.entity1, contacts.entity2)
-        } else if hitbox_query.contains(contacts.entity2)
-            && hurtbox_query.contains(contacts.entity1)
-        {
-            (contacts.entity2, contacts.entity1)
-        } else {
-            continue; // Not a hitbox-hurtbox collision
+        // Determine which entity is the hitbox and which is the hurtbox by collision layer
+        let (Ok(layers1), Ok(layers2)) = (
+            layers_query.get(contacts.entity1),
+            layers_query.get(contacts.entity2),
+        ) else {
+            continue;
         };
+        let (hitbox_entity, hurtbox_entity) =
+            if GameLayer::Hitbox.contains(layers1) && GameLayer::Hurtbox.contains(layers2) {
+                (contacts.entity1, contacts.entity2)
+            } else if GameLayer::Hitbox.contains(layers2) && GameLayer::Hurtbox.contains(layers1) {
+                (contacts.entity2, contacts.entity1)
+            } else {
+                continue; // Not a hitbox-hurtbox collision
+            };
+
+        if let (Ok((_, hitbox, _, is_grab)), Ok(hurtbox)) = (
+            hitbox_query.get(hitbox_entity),
+            hurtbox_query.get(hurtbox_entity),
+        ) {
+            // Hurtboxes belong to a fighter, which may currently be invincible
+            let defender = hurtbox.owner;
+            let Ok((knockdown, juggle)) = vulnerable_query.get(defender) else {
+                continue;
+            };
+
+            // Juggled fighters can be hit until the juggle points run out
+            let juggle_points = juggle.map(|juggle| juggle.points);
+            match juggle_points {
+                Some(points) if points + hitbox.juggle_points > JUGGLE_POINT_LIMIT => continue,
+                None if knockdown.is_some() => continue,
+                _ => {}
+            }
+            let (airborne, in_hitstun, in_blockstun) = defender_query
+                .get(defender)
+                .map(|(grounded, in_hitstun, in_blockstun)| (!grounded.0, in_hitstun, in_blockstun))
+                .unwrap_or((false, false, false));
+
+            // Prevent hitting yourself, and let each hitbox connect only once per frame
+            if hitbox.owner != defender && spent_hitboxes.insert(hitbox_entity) {
+                // Check if target is blocking at the right height
+                let (is_parrying, is_blocking) = block_query
+                    .get(defender)
+                    .map(|block_state| {
+                        (
+                            block_state.parries(hitbox.height),
+                            block_state.blocks(hitbox.height),
+                        )
+                    })
+                    .unwrap_or((false, false));
+
+                if is_grab {
+                    // Throws ignore blocking, but whiff against airborne or stunned fighters
+                    if airborne || in_hitstun || in_blockstun {
+                        continue;
+                    }
+
+                    tracing::info!("Grabbed!");
+                    if let Ok(mut block_state) = block_query.get_mut(defender) {
+                        block_state.is_blocking = false;
+                    }
+                    if let Ok(mut attack_state) = attack_state_query.get_mut(defender) {
+                        attack_state.current = None;
+                    }
+                    commands.entity(defender).insert(Thrown {
+                        timer: Timer::new(frames_to_duration(THROW_TECH_FRAMES), TimerMode::Once),
+                        thrower: hitbox.owner,
+                        damage: hitbox.damage,
+                        knockback: hitbox.knockback,
+                    });
+                } else if is_parrying {
+                    // Parried! No damage, and the attacker eats the recoil and a short stun
+                    tracing::info!("Attack parried!");
+
+                    parry_writer.send(ParryEvent { defender });
+
+                    commands
+                        .entity(hitbox.owner)
+                        .remove::<Blockstun>()
+                        .insert(Hitstun {
+                            timer: Timer::new(
+                                frames_to_duration(PARRY_STUN_FRAMES),
+                                TimerMode::Once,
+                            ),
+                        });
+                    if let Ok(mut attack_state) = attack_state_query.get_mut(hitbox.owner) {
+                        attack_state.current = None;
+                    }
 
-        if let Ok(hitbox) = hitbox_query.get(hitbox_entity) {
-            // Prevent hitting yourself
-            if hitbox.owner != hurtbox_entity {
-                // Check if target is blocking
-                let is_blocking = block_query
-                    .get(hurtbox_entity)
-                    .map(|block_state| block_state.is_blocking)
-                    .unwrap_or(false);
-
-                if is_blocking {
-                    // Blocked! No damage, reduced recoil
+                    // Reflect the full recoil back onto the attacker
+                    if let (Ok(attacker_transform), Ok(defender_transform)) = (
+                        transform_query.get(hitbox.owner),
+                        transform_query.get(defender),
+                    ) {
+                        let direction_vec3 = (defender_transform.translation
+                            - attacker_transform.translation)
+                            .normalize();
+                        let direction = Vec2::new(direction_vec3.x, direction_vec3.y);
+
+                        commands
+                            .entity(hitbox.owner)
+                            .insert(ExternalImpulse::new(-direction * hitbox.knockback));
+                    }
+                } else if is_blocking {
+                    // Blocked! Only chip damage, reduced recoil
                     tracing::info!("Attack blocked!");
 
+                    if hitbox.chip_damage > 0 {
+                        damage_writer.send(DamageEvent {
+                            target: defender,
+                            attacker: hitbox.owner,
+                            damage: hitbox.chip_damage,
+                            drain: false,
+                        });
+                    }
+
+                    // Blocked hits drain the guard meter; emptying it breaks the guard
+                    let mut guard_broken = false;
+                    if let Ok(mut block_state) = block_query.get_mut(defender) {
+                        block_state.guard = (block_state.guard - hitbox.guard_damage).max(0.0);
+                        if block_state.guard <= 0.0 {
+                            block_state.is_blocking = false;
+                            guard_broken = true;
+                        }
+                    }
+
+                    if guard_broken {
+                        tracing::info!("Guard broken!");
+                        commands
+                            .entity(defender)
+                            .remove::<Blockstun>()
+                            .insert(Hitstun {
+                                timer: Timer::new(
+                                    frames_to_duration(GUARD_BREAK_STUN_FRAMES),
+                                    TimerMode::Once,
+                                ),
+                            });
+                    } else {
+                        commands.entity(defender).insert(Blockstun {
+                            timer: Timer::new(hitbox.blockstun, TimerMode::Once),
+                        });
+                    }
+
                     // Apply reduced recoil forces
                     if let (Ok(attacker_transform), Ok(defender_transform)) = (
                         transform_query.get(hitbox.owner),
-                        transform_query.get(hurtbox_entity),
+                        transform_query.get(defender),
                     ) {
                         let direction_vec3 = (defender_transform.translation
                             - attacker_transform.translation)
@@ -209,7 +691,7 @@ fn detect_collisions(
 
                         // Push defender slightly
                         commands
-                            .entity(hurtbox_entity)
+                            .entity(defender)
                             .insert(ExternalImpulse::new(direction * recoil_strength * 0.5));
 
                         // Push attacker slightly backward
@@ -218,27 +700,119 @@ fn detect_collisions(
                             .insert(ExternalImpulse::new(-direction * recoil_strength * 0.2));
                     }
                 } else {
-                    // Normal hit - send damage event
+                    // A hit at the wrong height goes through and drops the block
+                    if let Ok(mut block_state) = block_query.get_mut(defender) {
+                        block_state.is_blocking = false;
+                    }
+
+                    // Hitting a fighter in the startup of their own attack is a counter hit
+                    let counter_hit = attack_state_query
+                        .get(defender)
+                        .ok()
+                        .and_then(|attack_state| attack_state.current.as_ref())
+                        .is_some_and(|attack| attack.phase == AttackPhase::Startup);
+                    let (base_damage, hitstun) = if counter_hit {
+                        tracing::info!("Counter hit!");
+                        counter_writer.send(CounterHitEvent {
+                            attacker: hitbox.owner,
+                            defender,
+                        });
+                        (
+                            (hitbox.damage as f32 * COUNTER_HIT_DAMAGE_MULTIPLIER).round() as i32,
+                            hitbox.hitstun.mul_f32(COUNTER_HIT_HITSTUN_MULTIPLIER),
+                        )
+                    } else {
+                        (hitbox.damage, hitbox.hitstun)
+                    };
+
+                    // Normal hit - extend the attacker's combo if the defender is still in hitstun
+                    let mut damage = base_damage;
+                    if let Ok(mut combo) = combo_query.get_mut(hitbox.owner) {
+                        let continues_combo = combo.target == Some(defender)
+                            && (in_hitstun || juggle_points.is_some());
+                        if !continues_combo {
+                            combo.reset();
+                        }
+                        damage = combo.scaled_damage(base_damage);
+                        combo.hits += 1;
+                        combo.total_damage += damage;
+                        combo.target = Some(defender);
+                    }
+
                     damage_writer.send(DamageEvent {
-                        target: hurtbox_entity,
-                        damage: hitbox.damage,
+                        target: defender,
+                        attacker: hitbox.owner,
+                        damage,
+                        drain: false,
                     });
 
+                    // Hitstun (or a hard knockdown) interrupts whatever the defender was doing
+                    if hitbox.knockdown {
+                        tracing::info!("Knockdown!");
+                        commands
+                            .entity(defender)
+                            .remove::<(Blockstun, Hitstun)>()
+                            .insert(Knockdown {
+                                timer: Timer::new(
+                                    frames_to_duration(KNOCKDOWN_FRAMES),
+                                    TimerMode::Once,
+                                ),
+                                landed: false,
+                            });
+                    } else {
+                        commands
+                            .entity(defender)
+                            .remove::<Blockstun>()
+                            .insert(Hitstun {
+                                timer: Timer::new(hitstun, TimerMode::Once),
+                            });
+                    }
+                    if let Ok(mut attack_state) = attack_state_query.get_mut(defender) {
+                        attack_state.current = None;
+                    }
+
+                    // Airborne or launched defenders can be hit again until they land
+                    if airborne || hitbox.knockdown {
+                        let points = juggle_points.unwrap_or(0) + hitbox.juggle_points;
+                        commands
+                            .entity(defender)
+                            .remove::<LandingRecovery>()
+                            .insert(Juggle::new(points, airborne));
+                    }
+
+                    // Projectiles already in flight keep going
+                    for (entity, owned_hitbox, is_projectile, _) in hitbox_query.iter() {
+                        if owned_hitbox.owner == defender && !is_projectile {
+                            commands.entity(entity).despawn_recursive();
+                        }
+                    }
+
                     // Apply full recoil forces
                     if let (Ok(attacker_transform), Ok(defender_transform)) = (
                         transform_query.get(hitbox.owner),
-                        transform_query.get(hurtbox_entity),
+                        transform_query.get(defender),
                     ) {
                         let direction_vec3 = (defender_transform.translation
                             - attacker_transform.translation)
                             .normalize();
                         let direction = Vec2::new(direction_vec3.x, direction_vec3.y);
-                        let recoil_strength = 500.0; // Force to push players apart
+                        let (recoil_strength, launch) = if hitbox.knockdown {
+                            // Pop knocked-down fighters off the ground
+                            (hitbox.knockback, Vec2::Y * KNOCKDOWN_LAUNCH)
+                        } else if airborne {
+                            // Keep juggled fighters in the air
+                            (
+                                hitbox.knockback * AIRBORNE_RECOIL_SCALE,
+                                Vec2::Y * JUGGLE_LAUNCH,
+                            )
+                        } else {
+                            (hitbox.knockback, Vec2::ZERO) // Force to push players apart
+                        };
 
                         // Push defender away from attacker
                         commands
-                            .entity(hurtbox_entity)
-                            .insert(ExternalImpulse::new(direction * recoil_strength));
+                            .entity(defender)
+                            .insert(ExternalImpulse::new(direction * recoil_strength + launch));
 
                         // Push attacker slightly backward
                         commands
@@ -254,7 +828,262 @@ fn detect_collisions(
     }
 }
 
-fn apply_damage(
+pub fn update_stun_timers(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut hitstun_ended_writer: EventWriter<HitstunEndedEvent>,
+    mut hitstun_query: Query<(Entity, &mut Hitstun)>,
+    mut blockstun_query: Query<(Entity, &mut Blockstun)>,
+    mut landing_query: Query<(Entity, &mut LandingRecovery)>,
+) {
+    for (entity, mut hitstun) in hitstun_query.iter_mut() {
+        hitstun.timer.tick(time.delta());
+        if hitstun.timer.finished() {
+            commands.entity(entity).remove::<Hitstun>();
+            hitstun_ended_writer.send(HitstunEndedEvent { defender: entity });
+        }
+    }
+
+    for (entity, mut blockstun) in blockstun_query.iter_mut() {
+        blockstun.timer.tick(time.delta());
+        if blockstun.timer.finished() {
+            commands.entity(entity).remove::<Blockstun>();
+        }
+    }
+
+    for (entity, mut landing) in landing_query.iter_mut() {
+        landing.timer.tick(time.delta());
+        if landing.timer.finished() {
+            commands.entity(entity).remove::<LandingRecovery>();
+        }
+    }
+}
+
+// Juggles end on landing; without a knockdown the defender recovers on their feet
+pub fn update_juggles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut hitstun_ended_writer: EventWriter<HitstunEndedEvent>,
+    mut query: Query<(Entity, &Grounded, &mut Juggle, Has<Knockdown>)>,
+) {
+    for (entity, grounded, mut juggle, knocked_down) in query.iter_mut() {
+        juggle.takeoff.tick(time.delta());
+        if !grounded.0 {
+            juggle.airborne = true;
+            continue;
+        }
+        if !juggle.airborne && !juggle.takeoff.finished() {
+            continue;
+        }
+
+        commands.entity(entity).remove::<Juggle>();
+        if !knocked_down {
+            hitstun_ended_writer.send(HitstunEndedEvent { defender: entity });
+            commands
+                .entity(entity)
+                .remove::<Hitstun>()
+                .insert(LandingRecovery {
+                    timer: Timer::new(
+                        frames_to_duration(JUGGLE_LANDING_RECOVERY_FRAMES),
+                        TimerMode::Once,
+                    ),
+                });
+        }
+    }
+}
+
+// Landing during an aerial attack cancels it into the move's landing recovery
+fn land_aerial_attacks(
+    mut commands: Commands,
+    move_library: MoveLibrary,
+    mut query: Query<(Entity, &Grounded, &AnimationState, &mut AttackState)>,
+    hitbox_query: Query<(Entity, &Hitbox), Without<Projectile>>,
+) {
+    for (entity, grounded, animation_state, mut attack_state) in query.iter_mut() {
+        let Some(attack_type) = attack_state.current_attack() else {
+            continue;
+        };
+        if !attack_type.is_aerial() || !grounded.0 {
+            continue;
+        }
+
+        let move_data = move_library.get(animation_state.character_type, attack_type);
+        attack_state.current = None;
+        for (hitbox_entity, hitbox) in hitbox_query.iter() {
+            if hitbox.owner == entity {
+                commands.entity(hitbox_entity).despawn_recursive();
+            }
+        }
+        if move_data.landing_recovery_frames > 0 {
+            commands.entity(entity).insert(LandingRecovery {
+                timer: Timer::new(
+                    frames_to_duration(move_data.landing_recovery_frames),
+                    TimerMode::Once,
+                ),
+            });
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_knockdowns(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    mut rng: ResMut<GameRng>,
+    mut query: Query<(
+        Entity,
+        &ControlType,
+        &Grounded,
+        &FacingDirection,
+        &mut Knockdown,
+        &mut InputBuffer,
+        Has<Juggle>,
+    )>,
+) {
+    for (entity, control, grounded, facing, mut knockdown, mut input_buffer, is_juggled) in
+        query.iter_mut()
+    {
+        // Falling - the ground timer only starts once the fighter lands and the juggle ends
+        if !knockdown.landed {
+            if grounded.0 && !is_juggled {
+                knockdown.landed = true;
+
+                // AI fighters decide whether to tech once, on landing
+                if matches!(control, ControlType::AI(_))
+                    && rng.chance(RngStream::Combat, config.difficulty.tech_chance())
+                {
+                    tech_roll(&mut commands, entity, facing);
+                }
+            }
+            continue;
+        }
+
+        knockdown.timer.tick(time.delta());
+
+        // Tech roll: pressing a button just after landing rolls away with invincibility
+        let in_tech_window = knockdown.timer.elapsed() <= frames_to_duration(TECH_WINDOW_FRAMES);
+        if in_tech_window
+            && matches!(control, ControlType::Human)
+            && input_buffer.take_attack(time.elapsed_seconds()).is_some()
+        {
+            tech_roll(&mut commands, entity, facing);
+        } else if knockdown.timer.finished() {
+            // Wake-up with a few invincible frames
+            commands
+                .entity(entity)
+                .remove::<Knockdown>()
+                .insert(HurtboxDisabled {
+                    timer: Timer::new(
+                        frames_to_duration(WAKEUP_INVINCIBLE_FRAMES),
+                        TimerMode::Once,
+                    ),
+                });
+        }
+    }
+}
+
+fn tech_roll(commands: &mut Commands, entity: Entity, facing: &FacingDirection) {
+    // Roll away from the opponent
+    let back = match facing {
+        FacingDirection::Right => -1.0,
+        FacingDirection::Left => 1.0,
+    };
+    tracing::info!("Tech roll!");
+    commands.entity(entity).remove::<Knockdown>().insert((
+        Dash {
+            timer: Timer::new(frames_to_duration(TECH_ROLL_FRAMES), TimerMode::Once),
+            direction: back,
+        },
+        HurtboxDisabled {
+            timer: Timer::new(frames_to_duration(TECH_ROLL_FRAMES), TimerMode::Once),
+        },
+    ));
+}
+
+fn update_throws(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    mut rng: ResMut<GameRng>,
+    mut query: Query<(Entity, &ControlType, &mut Thrown, &mut InputBuffer)>,
+    transform_query: Query<&Transform>,
+    mut damage_writer: EventWriter<DamageEvent>,
+) {
+    for (entity, control, mut thrown, mut input_buffer) in query.iter_mut() {
+        thrown.timer.tick(time.delta());
+
+        // Humans tech by pressing throw inside the window; AI rolls once when it closes
+        let teched = match control {
+            ControlType::Human => {
+                input_buffer.take_attack(time.elapsed_seconds()) == Some(AttackType::Throw)
+            }
+            ControlType::AI(_) => {
+                thrown.timer.finished()
+                    && rng.chance(RngStream::Combat, config.difficulty.tech_chance())
+            }
+        };
+        if !teched && !thrown.timer.finished() {
+            continue;
+        }
+
+        let direction = match (
+            transform_query.get(thrown.thrower),
+            transform_query.get(entity),
+        ) {
+            (Ok(thrower_transform), Ok(transform)) => {
+                if transform.translation.x >= thrower_transform.translation.x {
+                    Vec2::X
+                } else {
+                    Vec2::NEG_X
+                }
+            }
+            _ => Vec2::ZERO,
+        };
+
+        if teched {
+            // Throw tech: both fighters are pushed apart with no damage
+            tracing::info!("Throw teched!");
+            commands
+                .entity(entity)
+                .remove::<Thrown>()
+                .insert(ExternalImpulse::new(direction * THROW_TECH_PUSH));
+            commands
+                .entity(thrown.thrower)
+                .insert(ExternalImpulse::new(-direction * THROW_TECH_PUSH));
+        } else {
+            // Throw lands: damage and a hard knockdown
+            damage_writer.send(DamageEvent {
+                target: entity,
+                attacker: thrown.thrower,
+                damage: thrown.damage,
+                drain: false,
+            });
+            commands.entity(entity).remove::<Thrown>().insert((
+                Knockdown {
+                    timer: Timer::new(frames_to_duration(KNOCKDOWN_FRAMES), TimerMode::Once),
+                    landed: false,
+                },
+                ExternalImpulse::new(direction * thrown.knockback + Vec2::Y * KNOCKDOWN_LAUNCH),
+            ));
+        }
+    }
+}
+
+fn update_hurtbox_disabled(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut HurtboxDisabled)>,
+) {
+    for (entity, mut disabled) in query.iter_mut() {
+        disabled.timer.tick(time.delta());
+        if disabled.timer.finished() {
+            commands.entity(entity).remove::<HurtboxDisabled>();
+        }
+    }
+}
+
+pub fn apply_damage(
     // Corrected: Removed `mut commands: Commands` as it wasn't used
     mut damage_reader: EventReader<DamageEvent>,
     mut query: Query<(&mut Health, &Player)>,
@@ -263,7 +1092,9 @@ fn apply_damage(
         match query.get_mut(event.target) {
             Ok((mut health, player)) => {
                 health.current = (health.current - event.damage).max(0);
-                tracing::info!("Player {} hit! {} HP left", player.id, health.current);
+                if !event.drain {
+                    tracing::info!("Player {} hit! {} HP left", player.id, health.current);
+                }
             }
             Err(_) => {
                 tracing::warn!(
@@ -274,18 +1105,176 @@ fn apply_damage(
     }
 }
 
+fn gain_super_meter(
+    mut damage_reader: EventReader<DamageEvent>,
+    mut query: Query<&mut SuperMeter>,
+) {
+    for event in damage_reader.read().filter(|event| !event.drain) {
+        if let Ok(mut meter) = query.get_mut(event.attacker) {
+            meter.gain(event.damage as f32 * METER_PER_DAMAGE_DEALT);
+        }
+        if let Ok(mut meter) = query.get_mut(event.target) {
+            meter.gain(event.damage as f32 * METER_PER_DAMAGE_TAKEN);
+        }
+    }
+}
+
+fn start_super_freeze(
+    mut super_reader: EventReader<SuperEvent>,
+    mut freeze: ResMut<SuperFreeze>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut fixed_time: ResMut<Time<Fixed>>,
+) {
+    for _event in super_reader.read() {
+        tracing::info!("Super activated!");
+        freeze.timer = Some(Timer::new(SUPER_FREEZE_DURATION, TimerMode::Once));
+        virtual_time.pause();
+        skip_remaining_ticks(&mut fixed_time);
+    }
+}
+
+// Pausing virtual time doesn't stop ticks already owed for this frame, so drop them
+fn skip_remaining_ticks(fixed_time: &mut Time<Fixed>) {
+    let overstep = fixed_time.overstep();
+    fixed_time.discard_overstep(overstep);
+}
+
+// Runs on real time, since virtual time is paused during the freeze
+fn update_super_freeze(
+    real_time: Res<Time<Real>>,
+    hitstop: Res<Hitstop>,
+    mut freeze: ResMut<SuperFreeze>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+) {
+    let Some(timer) = freeze.timer.as_mut() else {
+        return;
+    };
+
+    timer.tick(real_time.delta());
+    if timer.finished() {
+        freeze.timer = None;
+        if hitstop.timer.is_none() {
+            virtual_time.unpause();
+        }
+    }
+}
+
+fn end_super_freeze(mut freeze: ResMut<SuperFreeze>, mut virtual_time: ResMut<Time<Virtual>>) {
+    freeze.timer = None;
+    virtual_time.unpause();
+}
+
+// Bigger hits freeze the fight for longer
+fn start_hitstop(
+    mut damage_reader: EventReader<DamageEvent>,
+    config: Res<GameConfig>,
+    mut hitstop: ResMut<Hitstop>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut fixed_time: ResMut<Time<Fixed>>,
+) {
+    for event in damage_reader.read().filter(|event| !event.drain) {
+        if !config.hitstop {
+            continue;
+        }
+
+        let frames = (HITSTOP_BASE_FRAMES + event.damage.max(0) as u32 / 2).min(HITSTOP_MAX_FRAMES);
+        let duration = frames_to_duration(frames);
+        let remaining = hitstop
+            .timer
+            .as_ref()
+            .map(|timer| timer.remaining())
+            .unwrap_or_default();
+        if duration > remaining {
+            hitstop.timer = Some(Timer::new(duration, TimerMode::Once));
+        }
+        virtual_time.pause();
+        skip_remaining_ticks(&mut fixed_time);
+    }
+}
+
+// Runs on real time, since virtual time is paused during hitstop
+fn update_hitstop(
+    real_time: Res<Time<Real>>,
+    freeze: Res<SuperFreeze>,
+    mut hitstop: ResMut<Hitstop>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+) {
+    let Some(timer) = hitstop.timer.as_mut() else {
+        return;
+    };
+
+    timer.tick(real_time.delta());
+    if timer.finished() {
+        hitstop.timer = None;
+        // A super freeze that started during hitstop keeps the game paused
+        if freeze.timer.is_none() {
+            virtual_time.unpause();
+        }
+    }
+}
+
+fn shake_camera_on_hit(
+    mut damage_reader: EventReader<DamageEvent>,
+    config: Res<GameConfig>,
+    mut camera_query: Query<&mut CameraShake>,
+) {
+    for event in damage_reader.read().filter(|event| !event.drain) {
+        if !config.screen_shake {
+            continue;
+        }
+
+        for mut shake in camera_query.iter_mut() {
+            shake.trauma = (shake.trauma + event.damage as f32 / 20.0).min(1.0);
+        }
+    }
+}
+
+// Also runs on real time, so the camera keeps shaking through hitstop
+fn update_camera_shake(
+    real_time: Res<Time<Real>>,
+    mut rng: ResMut<EffectsRng>,
+    mut camera_query: Query<(&mut Transform, &mut CameraShake)>,
+) {
+    for (mut transform, mut shake) in camera_query.iter_mut() {
+        // A settled camera draws nothing
+        if shake.trauma <= 0.0 {
+            continue;
+        }
+        shake.trauma = (shake.trauma - SHAKE_DECAY_PER_SECOND * real_time.delta_seconds()).max(0.0);
+
+        let offset = MAX_SHAKE_OFFSET * shake.trauma * shake.trauma;
+        transform.translation.x = rng.signed() * offset;
+        transform.translation.y = rng.signed() * offset;
+    }
+}
+
+fn end_impact_feedback(
+    mut hitstop: ResMut<Hitstop>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut camera_query: Query<(&mut Transform, &mut CameraShake)>,
+) {
+    hitstop.timer = None;
+    virtual_time.unpause();
+    for (mut transform, mut shake) in camera_query.iter_mut() {
+        shake.trauma = 0.0;
+        transform.translation.x = 0.0;
+        transform.translation.y = 0.0;
+    }
+}
+
 fn spawn_particles_on_hit(
     mut commands: Commands,
     mut damage_reader: EventReader<DamageEvent>,
+    mut rng: ResMut<EffectsRng>,
     transform_query: Query<&Transform>,
 ) {
-    for event in damage_reader.read() {
+    for event in damage_reader.read().filter(|event| !event.drain) {
         if let Ok(transform) = transform_query.get(event.target) {
             let position = transform.translation.truncate();
             let particle_count = 5;
             for i in 0..particle_count {
                 let angle = std::f32::consts::PI * 2.0 * (i as f32 / particle_count as f32);
-                let speed = 100.0 + rand::random::<f32>() * 50.0;
+                let speed = 100.0 + rng.f32() * 50.0;
                 let velocity = Vec2::new(angle.cos(), angle.sin()) * speed;
 
                 commands.spawn((
@@ -323,15 +1312,25 @@ fn despawn_particles_after_duration(
     }
 }
 
-fn check_for_game_over(
+#[allow(clippy::too_many_arguments)]
+pub fn check_for_game_over(
     mut next_state: ResMut<NextState<AppState>>,
     query: Query<(&Health, &Player, &ControlType)>,
     mut winner: ResMut<Winner>,
     config: Res<GameConfig>,
     mut progress: ResMut<PlayerProgress>,
-    fight_tracker: Res<FightTracker>,
+    mut fight_tracker: ResMut<FightTracker>,
+    replays: Res<Replays>,
     time: Res<Time>,
 ) {
+    // A frame can run several ticks after the knockout, before the state change lands
+    if fight_tracker.decided {
+        return;
+    }
+
+    // Watching a replay doesn't count towards statistics or unlocks
+    let record = !replays.is_playing();
+
     let mut players_alive = Vec::new();
     let mut players_dead = Vec::new();
 
@@ -342,6 +1341,16 @@ fn check_for_game_over(
             players_alive.push((player.id, control));
         }
     }
+    if players_alive.len() <= 1 {
+        fight_tracker.decided = true;
+    }
+
+    if (players_alive.len() == 1 || players_alive.is_empty()) && !config.player2_is_human && record
+    {
+        progress.record_counter_hits(config.boss, fight_tracker.counter_hits);
+        progress.record_seed(config.boss, fight_tracker.seed);
+        tracing::info!("Fight over (seed {})", fight_tracker.seed);
+    }
 
     if players_alive.len() == 1 {
         let (winner_id, winner_control) = players_alive[0];
@@ -357,7 +1366,9 @@ fn check_for_game_over(
 
         if matches!(winner_control, ControlType::Human) && !config.player2_is_human {
             // Human victory
-            progress.record_victory(config.boss, fight_duration, fight_tracker.current_combo);
+            if record {
+                progress.record_victory(config.boss, fight_duration, fight_tracker.best_combo);
+            }
 
             // Check if this was the final boss
             if config.boss.is_final_boss() {
@@ -365,7 +1376,7 @@ fn check_for_game_over(
                 next_state.set(AppState::Credits);
             } else {
                 // Unlock next boss if human player won against AI
-                if let Some(next_boss) = progress.get_next_boss(config.boss) {
+                if let Some(next_boss) = progress.get_next_boss(config.boss).filter(|_| record) {
                     progress.unlock_boss(next_boss);
                     tracing::info!("New boss unlocked: {:?}", next_boss);
                 }
@@ -374,7 +1385,9 @@ fn check_for_game_over(
             }
         } else {
             // AI victory or human vs human
-            progress.record_defeat(config.boss);
+            if record {
+                progress.record_defeat(config.boss);
+            }
             tracing::info!("Player {} wins! Game Over.", winner_id);
             next_state.set(AppState::GameOver);
         }
@@ -384,7 +1397,9 @@ fn check_for_game_over(
         winner.is_human_winner = None;
 
         // Record defeat for both (since it's a draw)
-        progress.record_defeat(config.boss);
+        if record {
+            progress.record_defeat(config.boss);
+        }
 
         tracing::info!("Both players died! Draw.");
         next_state.set(AppState::GameOver);
@@ -411,7 +1426,7 @@ fn play_hit_sound(
     block_query: Query<&BlockState>,
     assets: Res<GameAssets>,
 ) {
-    for event in event_reader.read() {
+    for event in event_reader.read().filter(|event| !event.drain) {
         // Check if the target was blocking
         let is_blocking = block_query
             .get(event.target)
@@ -434,44 +1449,108 @@ fn play_hit_sound(
     }
 }
 
+fn play_parry_sound(
+    mut commands: Commands,
+    mut event_reader: EventReader<ParryEvent>,
+    assets: Res<GameAssets>,
+) {
+    for _event in event_reader.read() {
+        commands.spawn(AudioBundle {
+            source: assets.parry_sfx.clone(),
+            settings: PlaybackSettings::DESPAWN,
+        });
+    }
+}
+
 fn initialize_fight_tracker(
     mut commands: Commands,
     config: Res<GameConfig>,
     mut progress: ResMut<PlayerProgress>,
-    time: Res<Time>,
+    time: Res<Time<Fixed>>,
+    rng: Res<GameRng>,
+    replays: Res<Replays>,
 ) {
     // Record fight start
-    progress.record_fight_start(config.boss);
+    if !replays.is_playing() {
+        progress.record_fight_start(config.boss);
+    }
 
     commands.insert_resource(FightTracker {
         fight_start_time: Some(time.elapsed_seconds()),
-        current_combo: 0,
+        best_combo: 0,
+        counter_hits: 0,
+        seed: rng.seed(),
+        decided: false,
         boss: config.boss,
     });
 }
 
+fn track_counter_hits(
+    mut fight_tracker: ResMut<FightTracker>,
+    mut counter_reader: EventReader<CounterHitEvent>,
+    control_query: Query<&ControlType>,
+) {
+    for event in counter_reader.read() {
+        if matches!(control_query.get(event.attacker), Ok(ControlType::Human)) {
+            fight_tracker.counter_hits += 1;
+        }
+    }
+}
+
 fn update_combo_tracker(
     mut fight_tracker: ResMut<FightTracker>,
-    mut damage_events: EventReader<DamageEvent>,
-    block_query: Query<&BlockState>,
-    _time: Res<Time>,
+    mut combo_query: Query<(&mut Combo, &Player, &ControlType)>,
+    hitstun_query: Query<(), InCombo>,
 ) {
-    let mut hits_this_frame = 0;
+    for (mut combo, player, control) in combo_query.iter_mut() {
+        let Some(target) = combo.target else {
+            continue;
+        };
 
-    // Count successful hits (not blocked)
-    for event in damage_events.read() {
-        let is_blocking = block_query
-            .get(event.target)
-            .map(|block_state| block_state.is_blocking)
-            .unwrap_or(false);
+        // Record the longest human combo, including one still in progress
+        if matches!(control, ControlType::Human) && combo.hits > fight_tracker.best_combo {
+            fight_tracker.best_combo = combo.hits;
+        }
 
-        if !is_blocking {
-            hits_this_frame += 1;
+        // The combo ends as soon as the defender recovers from hitstun or lands from a juggle
+        if !hitstun_query.contains(target) {
+            if combo.hits > 1 {
+                tracing::info!(
+                    "Player {} combo ended: {}x for {} damage",
+                    player.id,
+                    combo.hits,
+                    combo.total_damage
+                );
+            }
+            combo.reset();
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn combo_of(hits: u32) -> Combo {
+        Combo { hits, ..default() }
+    }
+
+    #[test]
+    fn first_hit_deals_full_damage() {
+        assert_eq!(combo_of(0).scaled_damage(10), 10);
+    }
+
+    #[test]
+    fn each_extra_hit_deals_less() {
+        assert_eq!(combo_of(1).scaled_damage(10), 9);
+        assert_eq!(combo_of(3).scaled_damage(10), 7);
+    }
 
-    if hits_this_frame > 0 {
-        fight_tracker.current_combo += hits_this_frame;
-        tracing::info!("Combo: {}x", fight_tracker.current_combo);
+    #[test]
+    fn scaling_bottoms_out() {
+        assert_eq!(combo_of(7).scaled_damage(10), 3);
+        assert_eq!(combo_of(50).scaled_damage(10), 3);
+        // Every hit still does something
+        assert_eq!(combo_of(50).scaled_damage(1), 1);
     }
 }
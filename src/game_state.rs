@@ -1,5 +1,7 @@
 use bevy::prelude::*;
 
+use crate::CharacterType;
+
 // This enum represents the main states of our game
 #[derive(States, Debug, Clone, Eq, PartialEq, Hash, Default)]
 pub enum AppState {
@@ -11,6 +13,7 @@ pub enum AppState {
     Paused,
     Statistics,
     Credits,
+    Lobby, // Waiting for an online peer before the fight
 }
 
 // Resource to track the winner
@@ -53,12 +56,33 @@ impl BossType {
     }
 }
 
-#[derive(Resource, Default)]
+#[derive(Resource)]
 pub struct GameConfig {
     pub difficulty: Difficulty,
     pub boss: BossType,
     pub player2_is_human: bool,
     pub arena: ArenaType,
+    pub hitstop: bool, // Brief freeze on impact; can be turned off for accessibility
+    pub screen_shake: bool, // Camera shake on impact; can be turned off for accessibility
+    pub seed: Option<u64>, // Fixed RNG seed for every fight, to reproduce bugs; random if unset
+    pub player1_character: CharacterType, // Human fighters' characters; a boss fights as itself
+    pub player2_character: CharacterType,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            difficulty: Difficulty::default(),
+            boss: BossType::default(),
+            player2_is_human: false,
+            arena: ArenaType::default(),
+            hitstop: true,
+            screen_shake: true,
+            seed: None,
+            player1_character: CharacterType::Player,
+            player2_character: CharacterType::Player,
+        }
+    }
 }
 
 #[derive(Resource, Debug, Clone)]
@@ -85,6 +109,8 @@ pub struct BossStatistics {
     pub losses: u32,
     pub best_combo: u32,
     pub fastest_victory_seconds: Option<f32>,
+    pub counter_hits: u32,      // Total counter hits landed against this boss
+    pub last_seed: Option<u64>, // RNG seed of the latest fight, to reproduce it
 }
 
 impl Default for PlayerProgress {
@@ -171,6 +197,20 @@ impl PlayerProgress {
         Self::save_progress(self);
     }
 
+    // Saved along with the fight's victory or defeat
+    pub fn record_counter_hits(&mut self, boss: BossType, counter_hits: u32) {
+        if let Some(boss_stat) = self.statistics.boss_stats.get_mut(&boss) {
+            boss_stat.counter_hits += counter_hits;
+        }
+    }
+
+    // Saved along with the fight's victory or defeat
+    pub fn record_seed(&mut self, boss: BossType, seed: u64) {
+        if let Some(boss_stat) = self.statistics.boss_stats.get_mut(&boss) {
+            boss_stat.last_seed = Some(seed);
+        }
+    }
+
     pub fn record_defeat(&mut self, boss: BossType) {
         self.statistics.current_win_streak = 0;
 
@@ -239,7 +279,9 @@ impl PlayerProgress {
                         "wins": stats.wins,
                         "losses": stats.losses,
                         "best_combo": stats.best_combo,
-                        "fastest_victory_seconds": stats.fastest_victory_seconds
+                        "fastest_victory_seconds": stats.fastest_victory_seconds,
+                        "counter_hits": stats.counter_hits,
+                        "last_seed": stats.last_seed
                     }),
                 )
             })
@@ -359,6 +401,13 @@ impl PlayerProgress {
                                                 boss_stat.fastest_victory_seconds = Some(f as f32);
                                             }
                                         }
+                                        if let Some(counters) = boss_data.get("counter_hits") {
+                                            if let Some(c) = counters.as_u64() {
+                                                boss_stat.counter_hits = c as u32;
+                                            }
+                                        }
+                                        boss_stat.last_seed =
+                                            boss_data.get("last_seed").and_then(|s| s.as_u64());
                                     }
                                 }
                             }
@@ -377,34 +426,112 @@ impl PlayerProgress {
 }
 
 impl GameConfig {
-    pub fn save_config(config: &GameConfig) {
-        use std::fs;
-
-        let config_path = "game_config.json";
-
-        let save_data = serde_json::json!({
-            "difficulty": match config.difficulty {
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "difficulty": match self.difficulty {
                 Difficulty::Easy => "Easy",
                 Difficulty::Normal => "Normal",
                 Difficulty::Hard => "Hard",
             },
-            "boss": match config.boss {
+            "boss": match self.boss {
                 BossType::NullPointer => "NullPointer",
                 BossType::UndefinedBehavior => "UndefinedBehavior",
                 BossType::DataRace => "DataRace",
                 BossType::UseAfterFree => "UseAfterFree",
                 BossType::BufferOverflow => "BufferOverflow",
             },
-            "arena": match config.arena {
+            "arena": match self.arena {
                 ArenaType::Default => "Default",
                 ArenaType::DataRace => "DataRace",
                 ArenaType::UndefinedBehavior => "UndefinedBehavior",
                 ArenaType::BufferOverflow => "BufferOverflow",
             },
-            "player2_is_human": config.player2_is_human
-        });
+            "player2_is_human": self.player2_is_human,
+            "hitstop": self.hitstop,
+            "screen_shake": self.screen_shake,
+            "seed": self.seed,
+            "player1_character": self.player1_character.name(),
+            "player2_character": self.player2_character.name()
+        })
+    }
 
-        if let Ok(json_string) = serde_json::to_string_pretty(&save_data) {
+    // Missing or unknown fields keep their defaults
+    pub fn from_json(config_data: &serde_json::Value) -> GameConfig {
+        let mut config = GameConfig::default();
+
+        if let Some(difficulty) = config_data.get("difficulty") {
+            if let Some(diff_str) = difficulty.as_str() {
+                config.difficulty = match diff_str {
+                    "Easy" => Difficulty::Easy,
+                    "Hard" => Difficulty::Hard,
+                    _ => Difficulty::Normal,
+                };
+            }
+        }
+
+        if let Some(boss) = config_data.get("boss") {
+            if let Some(boss_str) = boss.as_str() {
+                config.boss = match boss_str {
+                    "UndefinedBehavior" => BossType::UndefinedBehavior,
+                    "DataRace" => BossType::DataRace,
+                    "UseAfterFree" => BossType::UseAfterFree,
+                    "BufferOverflow" => BossType::BufferOverflow,
+                    _ => BossType::NullPointer,
+                };
+            }
+        }
+
+        if let Some(arena) = config_data.get("arena") {
+            if let Some(arena_str) = arena.as_str() {
+                config.arena = match arena_str {
+                    "DataRace" => ArenaType::DataRace,
+                    "UndefinedBehavior" => ArenaType::UndefinedBehavior,
+                    "BufferOverflow" => ArenaType::BufferOverflow,
+                    _ => ArenaType::Default,
+                };
+            }
+        }
+
+        if let Some(player2_human) = config_data.get("player2_is_human") {
+            if let Some(is_human) = player2_human.as_bool() {
+                config.player2_is_human = is_human;
+            }
+        }
+
+        if let Some(hitstop) = config_data.get("hitstop").and_then(|v| v.as_bool()) {
+            config.hitstop = hitstop;
+        }
+
+        if let Some(screen_shake) = config_data.get("screen_shake").and_then(|v| v.as_bool()) {
+            config.screen_shake = screen_shake;
+        }
+
+        config.seed = config_data.get("seed").and_then(|v| v.as_u64());
+
+        if let Some(character) = config_data
+            .get("player1_character")
+            .and_then(|v| v.as_str())
+            .and_then(CharacterType::from_name)
+        {
+            config.player1_character = character;
+        }
+        if let Some(character) = config_data
+            .get("player2_character")
+            .and_then(|v| v.as_str())
+            .and_then(CharacterType::from_name)
+        {
+            config.player2_character = character;
+        }
+
+        config
+    }
+
+    pub fn save_config(config: &GameConfig) {
+        use std::fs;
+
+        let config_path = "game_config.json";
+
+        if let Ok(json_string) = serde_json::to_string_pretty(&config.to_json()) {
             if fs::write(config_path, json_string).is_ok() {
                 tracing::info!("Game config saved successfully");
             } else {
@@ -424,47 +551,7 @@ impl GameConfig {
         if Path::new(config_path).exists() {
             if let Ok(json_string) = fs::read_to_string(config_path) {
                 if let Ok(config_data) = serde_json::from_str::<serde_json::Value>(&json_string) {
-                    let mut config = GameConfig::default();
-
-                    if let Some(difficulty) = config_data.get("difficulty") {
-                        if let Some(diff_str) = difficulty.as_str() {
-                            config.difficulty = match diff_str {
-                                "Easy" => Difficulty::Easy,
-                                "Hard" => Difficulty::Hard,
-                                _ => Difficulty::Normal,
-                            };
-                        }
-                    }
-
-                    if let Some(boss) = config_data.get("boss") {
-                        if let Some(boss_str) = boss.as_str() {
-                            config.boss = match boss_str {
-                                "UndefinedBehavior" => BossType::UndefinedBehavior,
-                                "DataRace" => BossType::DataRace,
-                                "UseAfterFree" => BossType::UseAfterFree,
-                                "BufferOverflow" => BossType::BufferOverflow,
-                                _ => BossType::NullPointer,
-                            };
-                        }
-                    }
-
-                    if let Some(arena) = config_data.get("arena") {
-                        if let Some(arena_str) = arena.as_str() {
-                            config.arena = match arena_str {
-                                "DataRace" => ArenaType::DataRace,
-                                "UndefinedBehavior" => ArenaType::UndefinedBehavior,
-                                "BufferOverflow" => ArenaType::BufferOverflow,
-                                _ => ArenaType::Default,
-                            };
-                        }
-                    }
-
-                    if let Some(player2_human) = config_data.get("player2_is_human") {
-                        if let Some(is_human) = player2_human.as_bool() {
-                            config.player2_is_human = is_human;
-                        }
-                    }
-
+                    let config = GameConfig::from_json(&config_data);
                     tracing::info!("Game config loaded successfully");
                     return config;
                 }
@@ -0,0 +1,229 @@
+use bevy::prelude::*;
+use bevy_xpbd_2d::prelude::*;
+use std::collections::HashMap;
+
+use crate::combat::{
+    update_juggles, update_stun_timers, HitstunEndedEvent, NotStunned, SpawnHitboxEvent,
+};
+use crate::game_state::AppState;
+use crate::moves::FRAMES_PER_SECOND;
+use crate::player::{AttackType, BlockState, ControlType, Grounded};
+
+// A block this soon after leaving hitstun counts as blocking on wake-up from a hit
+const BLOCK_AFTER_HIT_WINDOW: f32 = 0.5;
+// Habits need this much evidence before the boss acts on them
+const MIN_ATTACKS_FOR_FAVOURITE: u32 = 5;
+const FAVOURITE_SHARE: f32 = 0.4;
+const MIN_RECOVERIES_FOR_BLOCK_HABIT: u32 = 3;
+const JUMPS_PER_MINUTE_FOR_FULL_ANTI_AIR: f32 = 20.0;
+// Reaction time multiplier against the player's favourite attack
+const FAVOURITE_READ_FACTOR: f32 = 0.5;
+
+pub struct HabitsPlugin;
+
+impl Plugin for HabitsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HabitProfile>()
+            .add_systems(OnEnter(AppState::InGame), reset_habit_profile)
+            .add_systems(
+                FixedUpdate,
+                (
+                    track_attacks,
+                    track_jumps,
+                    track_blocks_after_hits
+                        .after(update_stun_timers)
+                        .after(update_juggles),
+                )
+                    .run_if(in_state(AppState::InGame)),
+            );
+    }
+}
+
+// -- Profile --
+
+// What the human player has done this fight; reset at the start of every fight
+#[derive(Resource, Default, Clone)]
+pub struct HabitProfile {
+    pub fight_started_at: f32,
+    pub attacks: Vec<(AttackType, u32)>, // Times each attack was used
+    pub jumps: u32,
+    pub hit_recoveries: u32,   // Times the player came out of hitstun
+    pub blocks_after_hit: u32, // ...and blocked straight away
+    block_delay_total: f32,
+    pub last_recovered_at: Option<f32>,
+    awaiting_block: bool,
+    was_grounded: HashMap<Entity, bool>, // Per fighter, to spot take-offs
+}
+
+impl HabitProfile {
+    pub fn reset(&mut self, now: f32) {
+        *self = HabitProfile {
+            fight_started_at: now,
+            ..default()
+        };
+    }
+
+    pub fn total_attacks(&self) -> u32 {
+        self.attacks.iter().map(|(_, count)| count).sum()
+    }
+
+    // Most used attack and its share of all attacks
+    pub fn favourite_attack(&self) -> Option<(AttackType, f32)> {
+        let total = self.total_attacks();
+        self.attacks
+            .iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(attack_type, count)| (*attack_type, *count as f32 / total.max(1) as f32))
+    }
+
+    pub fn jumps_per_minute(&self, now: f32) -> f32 {
+        // Ignore the first seconds so a single early jump doesn't look like a habit
+        let minutes = (now - self.fight_started_at).max(10.0) / 60.0;
+        self.jumps as f32 / minutes
+    }
+
+    pub fn block_after_hit_rate(&self) -> f32 {
+        self.blocks_after_hit as f32 / self.hit_recoveries.max(1) as f32
+    }
+
+    // Whether the player has just left hitstun, around when they usually block
+    pub fn recovered_recently(&self, now: f32) -> bool {
+        self.last_recovered_at.is_some_and(|recovered_at| {
+            now - recovered_at
+                <= self.block_delay_frames() / FRAMES_PER_SECOND + BLOCK_AFTER_HIT_WINDOW / 2.0
+        })
+    }
+
+    // Average frames from leaving hitstun to blocking
+    pub fn block_delay_frames(&self) -> f32 {
+        self.block_delay_total / self.blocks_after_hit.max(1) as f32 * FRAMES_PER_SECOND
+    }
+}
+
+// -- Bias --
+
+// How the boss leans against the player's habits, each 0-1
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HabitBias {
+    pub anti_air: f32,       // Chance to anti-air a jumping player
+    pub throw_blockers: f32, // Chance to throw a player who blocks after being hit
+    pub favourite: Option<AttackType>,
+    pub read_factor: f32, // Reaction time multiplier against the favourite attack
+}
+
+impl HabitBias {
+    // `adapt` weights the counters: 1.0 follows the habits, negative values deliberately
+    // play into them instead
+    pub fn from_profile(profile: &HabitProfile, now: f32, adapt: f32) -> Self {
+        let anti_air =
+            (profile.jumps_per_minute(now) / JUMPS_PER_MINUTE_FOR_FULL_ANTI_AIR).min(1.0);
+        let throw_blockers = if profile.hit_recoveries >= MIN_RECOVERIES_FOR_BLOCK_HABIT {
+            profile.block_after_hit_rate()
+        } else {
+            0.0
+        };
+        let favourite = profile
+            .favourite_attack()
+            .filter(|(_, share)| {
+                profile.total_attacks() >= MIN_ATTACKS_FOR_FAVOURITE && *share >= FAVOURITE_SHARE
+            })
+            .map(|(attack_type, _)| attack_type);
+
+        if adapt >= 0.0 {
+            HabitBias {
+                anti_air: anti_air * adapt,
+                throw_blockers: throw_blockers * adapt,
+                favourite,
+                read_factor: 1.0 - (1.0 - FAVOURITE_READ_FACTOR) * adapt.min(1.0),
+            }
+        } else {
+            // Inverted: anti-air players who never jump, throw those who never block, and
+            // react slower to the attack the player leans on
+            let weight = -adapt;
+            HabitBias {
+                anti_air: (1.0 - anti_air) * weight,
+                throw_blockers: (1.0 - throw_blockers) * weight,
+                favourite,
+                read_factor: 1.0 + (1.0 - FAVOURITE_READ_FACTOR) * weight.min(1.0),
+            }
+        }
+    }
+}
+
+// -- Systems --
+
+fn reset_habit_profile(time: Res<Time<Fixed>>, mut profile: ResMut<HabitProfile>) {
+    profile.reset(time.elapsed_seconds());
+}
+
+fn track_attacks(
+    mut spawn_reader: EventReader<SpawnHitboxEvent>,
+    control_query: Query<&ControlType>,
+    mut profile: ResMut<HabitProfile>,
+) {
+    for event in spawn_reader.read() {
+        if !matches!(control_query.get(event.attacker), Ok(ControlType::Human)) {
+            continue;
+        }
+        let attack_type = event.attack_type.ground_variant();
+        match profile
+            .attacks
+            .iter_mut()
+            .find(|(used, _)| *used == attack_type)
+        {
+            Some((_, count)) => *count += 1,
+            None => profile.attacks.push((attack_type, 1)),
+        }
+    }
+}
+
+fn track_jumps(
+    mut profile: ResMut<HabitProfile>,
+    query: Query<(Entity, &ControlType, &Grounded, &LinearVelocity), NotStunned>,
+) {
+    for (entity, control, grounded, velocity) in query.iter() {
+        if !matches!(control, ControlType::Human) {
+            continue;
+        }
+        let previous = profile
+            .was_grounded
+            .insert(entity, grounded.0)
+            .unwrap_or(true);
+        if previous && !grounded.0 && velocity.y > 0.0 {
+            profile.jumps += 1;
+        }
+    }
+}
+
+fn track_blocks_after_hits(
+    time: Res<Time>,
+    mut profile: ResMut<HabitProfile>,
+    mut hitstun_ended_reader: EventReader<HitstunEndedEvent>,
+    query: Query<(&ControlType, &BlockState)>,
+) {
+    let now = time.elapsed_seconds();
+    for event in hitstun_ended_reader.read() {
+        if matches!(query.get(event.defender), Ok((ControlType::Human, _))) {
+            profile.hit_recoveries += 1;
+            profile.last_recovered_at = Some(now);
+            profile.awaiting_block = true;
+        }
+    }
+
+    let Some(recovered_at) = profile.last_recovered_at else {
+        return;
+    };
+    if !profile.awaiting_block {
+        return;
+    }
+    let delay = now - recovered_at;
+    if delay > BLOCK_AFTER_HIT_WINDOW {
+        profile.awaiting_block = false;
+    } else if query.iter().any(|(control, block_state)| {
+        matches!(control, ControlType::Human) && block_state.is_blocking
+    }) {
+        profile.blocks_after_hit += 1;
+        profile.block_delay_total += delay;
+        profile.awaiting_block = false;
+    }
+}
@@ -0,0 +1,495 @@
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+use crate::game_state::AppState;
+use crate::moves::{FrameCounter, FRAMES_PER_SECOND};
+use crate::netplay::NetSession;
+use crate::player::{AttackType, ControlType, FacingDirection, Player};
+use crate::replay::Replays;
+use crate::status::StatusEffects;
+
+// Buffered attacks come out on the first legal frame within this window
+const ATTACK_BUFFER_FRAMES: u32 = 8;
+// A motion must be completed within this many frames of the button press
+const MOTION_WINDOW_FRAMES: u32 = 20;
+// Both taps of a dash must land within this window
+const DASH_TAP_WINDOW_FRAMES: u32 = 12;
+const MAX_HISTORY: usize = 16;
+
+pub struct InputPlugin;
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, sample_keyboard.run_if(in_state(AppState::InGame)))
+            .add_systems(
+                FixedPreUpdate,
+                record_inputs.run_if(in_state(AppState::InGame)),
+            );
+    }
+}
+
+// -- Data --
+
+// Stick direction relative to the way the fighter is facing
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StickDirection {
+    Neutral,
+    Up,
+    Down,
+    Forward,
+    Back,
+    DownForward,
+    DownBack,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DashDirection {
+    Forward,
+    Back,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum InputButton {
+    Left,
+    Right,
+    Up,
+    Down,
+    Light,
+    Heavy,
+    Kick,
+    Super,
+    Throw,
+    BlockHigh,
+    BlockLow,
+}
+
+impl InputButton {
+    pub const ALL: [InputButton; 11] = [
+        InputButton::Left,
+        InputButton::Right,
+        InputButton::Up,
+        InputButton::Down,
+        InputButton::Light,
+        InputButton::Heavy,
+        InputButton::Kick,
+        InputButton::Super,
+        InputButton::Throw,
+        InputButton::BlockHigh,
+        InputButton::BlockLow,
+    ];
+
+    pub fn bit(self) -> u16 {
+        1 << self as u16
+    }
+
+    pub fn key(self, player_id: u8) -> KeyCode {
+        if player_id == 1 {
+            match self {
+                InputButton::Left => KeyCode::KeyA,
+                InputButton::Right => KeyCode::KeyD,
+                InputButton::Up => KeyCode::KeyW,
+                InputButton::Down => KeyCode::KeyS,
+                InputButton::Light => KeyCode::KeyF,
+                InputButton::Heavy => KeyCode::KeyR,
+                InputButton::Kick => KeyCode::KeyT,
+                InputButton::Super => KeyCode::KeyQ,
+                InputButton::Throw => KeyCode::KeyG,
+                InputButton::BlockHigh => KeyCode::KeyE,
+                InputButton::BlockLow => KeyCode::KeyX,
+            }
+        } else {
+            match self {
+                InputButton::Left => KeyCode::ArrowLeft,
+                InputButton::Right => KeyCode::ArrowRight,
+                InputButton::Up => KeyCode::ArrowUp,
+                InputButton::Down => KeyCode::ArrowDown,
+                InputButton::Light => KeyCode::KeyL,
+                InputButton::Heavy => KeyCode::KeyO,
+                InputButton::Kick => KeyCode::KeyP,
+                InputButton::Super => KeyCode::KeyU,
+                InputButton::Throw => KeyCode::KeyI,
+                InputButton::BlockHigh => KeyCode::KeyJ,
+                InputButton::BlockLow => KeyCode::KeyK,
+            }
+        }
+    }
+}
+
+// One fighter's buttons for one simulation tick, as bitmasks of `InputButton`s
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct FrameInput {
+    pub held: u16,
+    pub pressed: u16, // Went down since the previous tick
+}
+
+impl FrameInput {
+    pub fn held(&self, button: InputButton) -> bool {
+        self.held & button.bit() != 0
+    }
+
+    pub fn pressed(&self, button: InputButton) -> bool {
+        self.pressed & button.bit() != 0
+    }
+}
+
+// Per-player input history used for motion inputs and buffering
+#[derive(Component, Clone, Default)]
+pub struct InputBuffer {
+    directions: VecDeque<(StickDirection, f32)>, // Direction changes with the time they happened
+    last_tap: Option<(DashDirection, f32)>,
+    pending_attack: Option<(AttackType, f32)>,
+    pending_dash: Option<(DashDirection, f32)>,
+    pending_jump: Option<f32>,
+    delayed: VecDeque<FrameInput>, // Ticks held back by an input delay, oldest first
+    pub current: FrameInput,       // Buttons for the current tick
+    held: u16,                     // Sampled keyboard state waiting for the next tick
+    latched: u16,
+}
+
+impl InputBuffer {
+    fn push_direction(&mut self, direction: StickDirection, now: f32) {
+        if self.directions.back().map(|(last, _)| *last) == Some(direction) {
+            return;
+        }
+        self.directions.push_back((direction, now));
+        if self.directions.len() > MAX_HISTORY {
+            self.directions.pop_front();
+        }
+    }
+
+    // Quarter-circle forward: down, down-forward, forward in order within the motion window
+    fn quarter_circle_forward(&self, now: f32) -> bool {
+        let window = MOTION_WINDOW_FRAMES as f32 / FRAMES_PER_SECOND;
+        let motion = [
+            StickDirection::Down,
+            StickDirection::DownForward,
+            StickDirection::Forward,
+        ];
+        let mut next = 0;
+        for (direction, time) in self.directions.iter() {
+            if now - time > window {
+                continue;
+            }
+            if *direction == motion[next] {
+                next += 1;
+                if next == motion.len() {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    // A second tap in the same direction within the tap window queues a dash
+    fn push_tap(&mut self, tap: DashDirection, now: f32) {
+        let window = DASH_TAP_WINDOW_FRAMES as f32 / FRAMES_PER_SECOND;
+        match self.last_tap {
+            Some((last, tapped_at)) if last == tap && now - tapped_at <= window => {
+                self.pending_dash = Some((tap, now));
+                self.last_tap = None;
+            }
+            _ => self.last_tap = Some((tap, now)),
+        }
+    }
+
+    // Takes the buffered attack if it is still inside the buffer window
+    pub fn take_attack(&mut self, now: f32) -> Option<AttackType> {
+        let window = ATTACK_BUFFER_FRAMES as f32 / FRAMES_PER_SECOND;
+        self.pending_attack
+            .take()
+            .filter(|(_, pressed_at)| now - pressed_at <= window)
+            .map(|(attack_type, _)| attack_type)
+    }
+
+    pub fn take_dash(&mut self, now: f32) -> Option<DashDirection> {
+        let window = ATTACK_BUFFER_FRAMES as f32 / FRAMES_PER_SECOND;
+        self.pending_dash
+            .take()
+            .filter(|(_, tapped_at)| now - tapped_at <= window)
+            .map(|(direction, _)| direction)
+    }
+
+    pub fn take_jump(&mut self, now: f32) -> bool {
+        let window = ATTACK_BUFFER_FRAMES as f32 / FRAMES_PER_SECOND;
+        self.pending_jump
+            .take()
+            .is_some_and(|pressed_at| now - pressed_at <= window)
+    }
+
+    // Passes a tick's buttons through a delay line of `delay` ticks. Presses held back while
+    // the delay shrinks are merged into the next tick rather than lost.
+    fn delay_input(&mut self, input: FrameInput, delay: usize) -> FrameInput {
+        self.delayed.push_back(input);
+        let mut output = FrameInput {
+            held: self.current.held,
+            pressed: 0,
+        };
+        while self.delayed.len() > delay {
+            let Some(next) = self.delayed.pop_front() else {
+                break;
+            };
+            output.held = next.held;
+            output.pressed |= next.pressed;
+        }
+        output
+    }
+
+    // Buttons sampled from the keyboard since the last tick
+    pub fn take_keyboard_input(&mut self) -> FrameInput {
+        FrameInput {
+            held: self.held,
+            pressed: std::mem::take(&mut self.latched),
+        }
+    }
+
+    // Rewinds the history to a saved copy, keeping keys sampled since that haven't been used
+    pub fn rollback_to(&mut self, saved: &InputBuffer) {
+        *self = InputBuffer {
+            held: self.held,
+            latched: self.latched,
+            ..saved.clone()
+        };
+    }
+}
+
+// -- Systems --
+
+// Samples the keyboard every rendered frame. Presses are latched until the next simulation
+// tick picks them up, so none are missed or seen twice however many ticks run per frame.
+fn sample_keyboard(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    replays: Res<Replays>,
+    session: Res<NetSession>,
+    mut query: Query<(&Player, &ControlType, &mut InputBuffer)>,
+) {
+    if replays.is_playing() {
+        return;
+    }
+    for (player, control, mut buffer) in query.iter_mut() {
+        if !matches!(control, ControlType::Human) {
+            continue;
+        }
+        // Online, only the local fighter reads this keyboard
+        let Some(keys) = session.keyboard_layout(player.id) else {
+            continue;
+        };
+        let mut held = 0;
+        let mut pressed = 0;
+        for button in InputButton::ALL {
+            let key = button.key(keys);
+            if keyboard_input.pressed(key) {
+                held |= button.bit();
+            }
+            if keyboard_input.just_pressed(key) {
+                pressed |= button.bit();
+            }
+        }
+        buffer.held = held;
+        buffer.latched |= pressed;
+    }
+}
+
+// Turns each human fighter's buttons for this tick into buffered actions. The buttons come
+// from the keyboard, the replay during playback or the netplay session, and are recorded for
+// the replay file.
+pub fn record_inputs(
+    time: Res<Time>,
+    frames: Res<FrameCounter>,
+    mut replays: ResMut<Replays>,
+    mut session: ResMut<NetSession>,
+    mut query: Query<(
+        &Player,
+        &ControlType,
+        &FacingDirection,
+        &mut InputBuffer,
+        Option<&StatusEffects>,
+    )>,
+) {
+    let now = time.elapsed_seconds();
+
+    for (player, control, facing, mut buffer, status) in query.iter_mut() {
+        if !matches!(control, ControlType::Human) {
+            continue;
+        }
+
+        let input = replays
+            .playback_input(frames.0, player.id)
+            .or_else(|| session.input(frames.0, player.id))
+            .unwrap_or_else(|| buffer.take_keyboard_input());
+        replays.record_input(frames.0, player.id, input);
+
+        // Status effects can disable a button or make every input arrive late
+        let delay = status.map_or(0, |status| status.input_delay_frames());
+        let input = buffer.delay_input(input, delay);
+        buffer.current = input;
+        let disabled_attack = status.and_then(|status| status.disabled_attack());
+
+        let (forward_button, back_button) = match facing {
+            FacingDirection::Right => (InputButton::Right, InputButton::Left),
+            FacingDirection::Left => (InputButton::Left, InputButton::Right),
+        };
+
+        // Directional history
+        let forward = input.held(forward_button);
+        let back = input.held(back_button);
+        let down = input.held(InputButton::Down);
+        let direction = match (down, forward, back) {
+            (true, true, false) => StickDirection::DownForward,
+            (true, false, true) => StickDirection::DownBack,
+            (true, _, _) => StickDirection::Down,
+            (false, true, false) => StickDirection::Forward,
+            (false, false, true) => StickDirection::Back,
+            _ if input.held(InputButton::Up) => StickDirection::Up,
+            _ => StickDirection::Neutral,
+        };
+        buffer.push_direction(direction, now);
+        if input.pressed(InputButton::Up) {
+            buffer.pending_jump = Some(now);
+        }
+
+        // Dash: double-tap forward or back
+        let tap = if input.pressed(forward_button) {
+            Some(DashDirection::Forward)
+        } else if input.pressed(back_button) {
+            Some(DashDirection::Back)
+        } else {
+            None
+        };
+        if let Some(tap) = tap {
+            buffer.push_tap(tap, now);
+        }
+
+        // Attack buttons, upgraded to a special when they finish a motion
+        let button = if input.pressed(InputButton::Light) {
+            Some(AttackType::Light)
+        } else if input.pressed(InputButton::Heavy) {
+            Some(AttackType::Heavy)
+        } else if input.pressed(InputButton::Kick) {
+            Some(AttackType::Kick)
+        } else {
+            None
+        }
+        .filter(|attack_type| Some(*attack_type) != disabled_attack);
+        if input.pressed(InputButton::Super) {
+            buffer.pending_attack = Some((AttackType::Super, now));
+        } else if input.pressed(InputButton::Throw) {
+            buffer.pending_attack = Some((AttackType::Throw, now));
+        } else if let Some(attack_type) = button {
+            let attack_type = if buffer.quarter_circle_forward(now) {
+                AttackType::Projectile
+            } else {
+                attack_type
+            };
+            buffer.pending_attack = Some((attack_type, now));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(n: u32) -> f32 {
+        n as f32 / FRAMES_PER_SECOND
+    }
+
+    fn buffer_with(directions: &[(StickDirection, u32)]) -> InputBuffer {
+        let mut buffer = InputBuffer::default();
+        for (direction, at) in directions {
+            buffer.push_direction(*direction, frame(*at));
+        }
+        buffer
+    }
+
+    #[test]
+    fn quarter_circle_forward_in_order() {
+        let buffer = buffer_with(&[
+            (StickDirection::Neutral, 0),
+            (StickDirection::Down, 1),
+            (StickDirection::DownForward, 3),
+            (StickDirection::Forward, 5),
+        ]);
+        assert!(buffer.quarter_circle_forward(frame(6)));
+    }
+
+    #[test]
+    fn quarter_circle_forward_rejects_wrong_order() {
+        let buffer = buffer_with(&[
+            (StickDirection::Forward, 1),
+            (StickDirection::DownForward, 3),
+            (StickDirection::Down, 5),
+        ]);
+        assert!(!buffer.quarter_circle_forward(frame(6)));
+    }
+
+    #[test]
+    fn quarter_circle_forward_must_fit_the_motion_window() {
+        let buffer = buffer_with(&[
+            (StickDirection::Down, 0),
+            (StickDirection::DownForward, 10),
+            (StickDirection::Forward, 20),
+        ]);
+        assert!(buffer.quarter_circle_forward(frame(MOTION_WINDOW_FRAMES)));
+        assert!(!buffer.quarter_circle_forward(frame(MOTION_WINDOW_FRAMES + 1)));
+    }
+
+    #[test]
+    fn double_tap_dashes_within_the_tap_window() {
+        let mut buffer = InputBuffer::default();
+        let second_tap = frame(DASH_TAP_WINDOW_FRAMES);
+        buffer.push_tap(DashDirection::Forward, frame(0));
+        buffer.push_tap(DashDirection::Forward, second_tap);
+        assert_eq!(buffer.take_dash(second_tap), Some(DashDirection::Forward));
+        assert_eq!(buffer.take_dash(second_tap), None);
+    }
+
+    #[test]
+    fn slow_or_mixed_taps_dont_dash() {
+        let mut buffer = InputBuffer::default();
+        let late_tap = frame(DASH_TAP_WINDOW_FRAMES + 1);
+        buffer.push_tap(DashDirection::Forward, frame(0));
+        buffer.push_tap(DashDirection::Forward, late_tap);
+        assert_eq!(buffer.take_dash(late_tap), None);
+
+        let mut buffer = InputBuffer::default();
+        buffer.push_tap(DashDirection::Forward, frame(0));
+        buffer.push_tap(DashDirection::Back, frame(4));
+        assert_eq!(buffer.take_dash(frame(4)), None);
+    }
+
+    #[test]
+    fn delayed_inputs_arrive_late_and_are_never_lost() {
+        let jump = FrameInput {
+            held: InputButton::Up.bit(),
+            pressed: InputButton::Up.bit(),
+        };
+        let mut buffer = InputBuffer::default();
+        assert_eq!(buffer.delay_input(jump, 2), FrameInput::default());
+        assert_eq!(
+            buffer.delay_input(FrameInput::default(), 2),
+            FrameInput::default()
+        );
+        assert_eq!(buffer.delay_input(FrameInput::default(), 2), jump);
+
+        // Dropping the delay flushes held-back presses into the next tick
+        buffer.delay_input(jump, 2);
+        let flushed = buffer.delay_input(FrameInput::default(), 0);
+        assert!(flushed.pressed(InputButton::Up));
+        assert!(!flushed.held(InputButton::Up));
+    }
+
+    #[test]
+    fn buffered_attacks_expire_after_the_buffer_window() {
+        let mut buffer = InputBuffer {
+            pending_attack: Some((AttackType::Heavy, frame(0))),
+            ..default()
+        };
+        assert_eq!(
+            buffer.take_attack(frame(ATTACK_BUFFER_FRAMES)),
+            Some(AttackType::Heavy)
+        );
+
+        buffer.pending_attack = Some((AttackType::Heavy, frame(0)));
+        assert_eq!(buffer.take_attack(frame(ATTACK_BUFFER_FRAMES + 1)), None);
+    }
+}
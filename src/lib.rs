@@ -1,10 +1,20 @@
 use wasm_bindgen::prelude::*;
 
 // Include shared modules
+mod ai;
+mod boss;
+mod boxes;
 mod combat;
 mod game_state;
+mod habits;
+mod input;
 mod menu;
+mod moves;
+mod netplay;
 mod player;
+mod replay;
+mod rng;
+mod status;
 mod ui;
 
 // Include the shared game logic
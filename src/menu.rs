@@ -1,5 +1,11 @@
 use crate::game_state::{AppState, ArenaType, BossType, Difficulty, GameConfig, PlayerProgress};
+use crate::netplay::{DisconnectNotice, NetMode, NetSession, DEFAULT_PORT};
+use crate::replay::{Replay, Replays, LAST_FIGHT_REPLAY};
+use crate::CharacterType;
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::ButtonState;
 use bevy::prelude::*;
+use std::net::SocketAddr;
 
 fn next_boss(current: BossType) -> BossType {
     match current {
@@ -82,6 +88,42 @@ fn arena_name(a: ArenaType) -> &'static str {
     }
 }
 
+fn next_character(current: CharacterType) -> CharacterType {
+    match current {
+        CharacterType::Player => CharacterType::Zombie,
+        CharacterType::Zombie => CharacterType::Adventurer,
+        CharacterType::Adventurer => CharacterType::Female,
+        CharacterType::Female => CharacterType::Soldier,
+        CharacterType::Soldier => CharacterType::Player,
+    }
+}
+
+fn prev_character(current: CharacterType) -> CharacterType {
+    match current {
+        CharacterType::Player => CharacterType::Soldier,
+        CharacterType::Zombie => CharacterType::Player,
+        CharacterType::Adventurer => CharacterType::Zombie,
+        CharacterType::Female => CharacterType::Adventurer,
+        CharacterType::Soldier => CharacterType::Female,
+    }
+}
+
+fn yes_no(enabled: bool) -> &'static str {
+    if enabled {
+        "Yes"
+    } else {
+        "No"
+    }
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled {
+        "On"
+    } else {
+        "Off"
+    }
+}
+
 pub struct MenuPlugin;
 
 impl Plugin for MenuPlugin {
@@ -114,7 +156,17 @@ impl Plugin for MenuPlugin {
                 Update,
                 statistics_screen_interaction.run_if(in_state(AppState::Statistics)),
             )
-            .add_systems(OnExit(AppState::Statistics), cleanup_statistics_screen);
+            .add_systems(OnExit(AppState::Statistics), cleanup_statistics_screen)
+            .init_resource::<JoinAddress>()
+            .add_systems(OnEnter(AppState::Lobby), setup_lobby_screen)
+            .add_systems(Update, lobby_interaction.run_if(in_state(AppState::Lobby)))
+            .add_systems(Update, type_join_address.run_if(in_state(AppState::Lobby)))
+            .add_systems(
+                Update,
+                update_lobby_display.run_if(in_state(AppState::Lobby)),
+            )
+            .add_systems(Update, menu_button_color.run_if(in_state(AppState::Lobby)))
+            .add_systems(OnExit(AppState::Lobby), cleanup_lobby_screen);
     }
 }
 
@@ -146,6 +198,12 @@ struct ArenaDisplay;
 #[derive(Component)]
 struct ArenaPreview;
 
+#[derive(Component)]
+struct HitstopDisplay;
+
+#[derive(Component)]
+struct ScreenShakeDisplay;
+
 #[derive(Debug, Clone, Copy)]
 enum MenuAction {
     StartGame,
@@ -159,6 +217,10 @@ enum MenuAction {
     ShowCredits,
     NextArena,
     PrevArena,
+    ToggleHitstop,
+    ToggleScreenShake,
+    HostGame,
+    JoinGame,
 }
 
 // A type alias for the filter used in button interaction queries.
@@ -167,7 +229,8 @@ type InteractingButtonFilter = (Changed<Interaction>, With<Button>);
 
 // -- Systems --
 
-fn setup_main_menu(mut commands: Commands) {
+fn setup_main_menu(mut commands: Commands, mut notice: ResMut<DisconnectNotice>) {
+    let notice = notice.0.take();
     commands
         .spawn((
             NodeBundle {
@@ -208,6 +271,18 @@ fn setup_main_menu(mut commands: Commands) {
                 },
             ));
 
+            // Why an online fight ended early
+            if let Some(notice) = notice {
+                parent.spawn(TextBundle::from_section(
+                    notice,
+                    TextStyle {
+                        font_size: 22.0,
+                        color: Color::srgb(1.0, 0.4, 0.4),
+                        ..default()
+                    },
+                ));
+            }
+
             // Boss Selection
             parent
                 .spawn(NodeBundle {
@@ -344,6 +419,44 @@ fn setup_main_menu(mut commands: Commands) {
                     ));
                 });
 
+            // Impact effects (accessibility)
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(10.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "HITSTOP: On",
+                            TextStyle {
+                                font_size: 24.0,
+                                color: Color::WHITE,
+                                ..default()
+                            },
+                        ),
+                        HitstopDisplay,
+                    ));
+                    spawn_menu_button(parent, "TOGGLE", MenuAction::ToggleHitstop);
+                    parent.spawn((
+                        TextBundle::from_section(
+                            "SCREEN SHAKE: On",
+                            TextStyle {
+                                font_size: 24.0,
+                                color: Color::WHITE,
+                                ..default()
+                            },
+                        ),
+                        ScreenShakeDisplay,
+                    ));
+                    spawn_menu_button(parent, "TOGGLE", MenuAction::ToggleScreenShake);
+                });
+
             parent
                 .spawn(NodeBundle {
                     style: Style {
@@ -370,9 +483,26 @@ fn setup_main_menu(mut commands: Commands) {
                     spawn_menu_button(parent, "QUIT", MenuAction::Quit);
                 });
 
+            // Online play, through the lobby
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::Center,
+                        justify_content: JustifyContent::Center,
+                        column_gap: Val::Px(20.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    spawn_menu_button(parent, "HOST ONLINE", MenuAction::HostGame);
+                    spawn_menu_button(parent, "JOIN ONLINE", MenuAction::JoinGame);
+                });
+
             // Controls info
             parent.spawn(TextBundle::from_section(
-                "Controls: Player 1 - A/D/W/S to move/jump/block, F to attack | Player 2 - Arrows to move/jump, L to attack, Down to block",
+                "Controls: Player 1 - A/D/W to move/jump, F to attack, E/X to block high/low | Player 2 - Arrows to move/jump, L to attack, J/K to block high/low",
                 TextStyle {
                     font_size: 14.0, // Increased by 2 points
                     color: Color::WHITE, // Changed to white
@@ -383,6 +513,11 @@ fn setup_main_menu(mut commands: Commands) {
 }
 
 fn spawn_menu_button(parent: &mut ChildBuilder, text: &str, action: MenuAction) {
+    spawn_button(parent, text, MenuButtonAction { action });
+}
+
+// A menu-styled button carrying whatever marks what it does
+fn spawn_button(parent: &mut ChildBuilder, text: &str, marker: impl Bundle) {
     parent
         .spawn((
             ButtonBundle {
@@ -396,7 +531,7 @@ fn spawn_menu_button(parent: &mut ChildBuilder, text: &str, action: MenuAction)
                 background_color: Color::srgb(0.15, 0.15, 0.2).into(),
                 ..default()
             },
-            MenuButtonAction { action },
+            marker,
         ))
         .with_children(|parent| {
             parent.spawn(TextBundle::from_section(
@@ -415,6 +550,7 @@ fn main_menu_interaction(
     mut config: ResMut<GameConfig>,
     mut app_state: ResMut<NextState<AppState>>,
     progress: Res<PlayerProgress>,
+    mut session: ResMut<NetSession>,
 ) {
     for (interaction, button_action) in &mut interaction_query {
         if *interaction == Interaction::Pressed {
@@ -461,6 +597,23 @@ fn main_menu_interaction(
                     config.arena = prev_arena(config.arena);
                     tracing::info!("Arena changed to: {:?}", config.arena);
                 }
+                MenuAction::ToggleHitstop => {
+                    config.hitstop = !config.hitstop;
+                }
+                MenuAction::ToggleScreenShake => {
+                    config.screen_shake = !config.screen_shake;
+                }
+                MenuAction::HostGame => {
+                    if session.host(DEFAULT_PORT) {
+                        app_state.set(AppState::Lobby);
+                    } else {
+                        tracing::warn!("Cannot host: UDP port {} is unavailable", DEFAULT_PORT);
+                    }
+                }
+                MenuAction::JoinGame => {
+                    // The lobby asks for the host's address first
+                    app_state.set(AppState::Lobby);
+                }
             }
         }
     }
@@ -526,6 +679,8 @@ fn update_menu_display(
         Query<&mut Text, With<DifficultyDisplay>>,
         Query<&mut Text, With<ModeDisplay>>,
         Query<&mut Text, With<ArenaDisplay>>,
+        Query<&mut Text, With<HitstopDisplay>>,
+        Query<&mut Text, With<ScreenShakeDisplay>>,
     )>,
     config: Res<GameConfig>,
     progress: Res<PlayerProgress>,
@@ -573,6 +728,14 @@ fn update_menu_display(
         text.sections[0].style.color = Color::WHITE;
     }
 
+    // Update impact effect toggles
+    for mut text in set.p4().iter_mut() {
+        text.sections[0].value = format!("HITSTOP: {}", on_off(config.hitstop));
+    }
+    for mut text in set.p5().iter_mut() {
+        text.sections[0].value = format!("SCREEN SHAKE: {}", on_off(config.screen_shake));
+    }
+
     // Update arena preview
     for mut preview_image in preview_query.iter_mut() {
         let arena_texture = match config.arena {
@@ -608,6 +771,10 @@ struct StatisticsScreen;
 #[derive(Component)]
 struct BackToMenuButton;
 
+// Plays back the named replay file
+#[derive(Component)]
+struct WatchReplayButton(String);
+
 fn setup_statistics_screen(mut commands: Commands, progress: Res<PlayerProgress>) {
     commands
         .spawn((
@@ -703,11 +870,12 @@ fn setup_statistics_screen(mut commands: Commands, progress: Res<PlayerProgress>
                 if let Some(stats) = progress.statistics.boss_stats.get(boss) {
                     parent.spawn(TextBundle::from_section(
                         format!(
-                            "{}: {}W/{}L, Best Combo: {}, Fastest: {:.1}s",
+                            "{}: {}W/{}L, Best Combo: {}, Counters: {}, Fastest: {:.1}s",
                             boss_name(*boss),
                             stats.wins,
                             stats.losses,
                             stats.best_combo,
+                            stats.counter_hits,
                             stats.fastest_victory_seconds.unwrap_or(0.0)
                         ),
                         TextStyle {
@@ -716,9 +884,21 @@ fn setup_statistics_screen(mut commands: Commands, progress: Res<PlayerProgress>
                             ..default()
                         },
                     ));
+                    let replay_name = format!("{:?}", boss);
+                    if Replay::exists(&replay_name) {
+                        spawn_watch_button(
+                            parent,
+                            &format!("WATCH {}", boss_name(*boss)),
+                            replay_name,
+                        );
+                    }
                 }
             }
 
+            if Replay::exists(LAST_FIGHT_REPLAY) {
+                spawn_watch_button(parent, "WATCH LAST FIGHT", LAST_FIGHT_REPLAY.to_string());
+            }
+
             // Back Button
             parent
                 .spawn((
@@ -749,8 +929,38 @@ fn setup_statistics_screen(mut commands: Commands, progress: Res<PlayerProgress>
         });
 }
 
+fn spawn_watch_button(parent: &mut ChildBuilder, text: &str, replay_name: String) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    padding: UiRect::axes(Val::Px(16.0), Val::Px(6.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::srgb(0.2, 0.3, 0.2).into(),
+                ..default()
+            },
+            WatchReplayButton(replay_name),
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                text,
+                TextStyle {
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+        });
+}
+
 fn statistics_screen_interaction(
     mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<BackToMenuButton>)>,
+    watch_query: Query<(&Interaction, &WatchReplayButton), Changed<Interaction>>,
+    mut replays: ResMut<Replays>,
+    mut config: ResMut<GameConfig>,
     mut app_state: ResMut<NextState<AppState>>,
 ) {
     for interaction in &mut interaction_query {
@@ -758,6 +968,20 @@ fn statistics_screen_interaction(
             app_state.set(AppState::MainMenu);
         }
     }
+
+    for (interaction, button) in &watch_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match Replay::load(&button.0) {
+            Some(replay) => {
+                tracing::info!("Watching replay {}", Replay::path(&button.0));
+                replays.start_playback(replay, &mut config);
+                app_state.set(AppState::InGame);
+            }
+            None => tracing::warn!("Could not load replay {}", Replay::path(&button.0)),
+        }
+    }
 }
 
 fn cleanup_statistics_screen(mut commands: Commands, query: Query<Entity, With<StatisticsScreen>>) {
@@ -765,3 +989,332 @@ fn cleanup_statistics_screen(mut commands: Commands, query: Query<Entity, With<S
         commands.entity(entity).despawn_recursive();
     }
 }
+
+// -- Lobby Screen --
+
+#[derive(Component)]
+struct LobbyScreen;
+
+#[derive(Component)]
+struct LobbyStatusDisplay;
+
+// Only shown while the joiner types in the host's address
+#[derive(Component)]
+struct LobbyAddressRow;
+
+#[derive(Component)]
+struct LobbyAddressDisplay;
+
+#[derive(Component)]
+struct LobbyCharacterDisplay;
+
+#[derive(Component)]
+struct LobbyReadyDisplay;
+
+#[derive(Component)]
+struct LobbyPeerDisplay;
+
+#[derive(Component)]
+struct LobbyPingDisplay;
+
+#[derive(Debug, Clone, Copy)]
+enum LobbyAction {
+    Connect,
+    PrevCharacter,
+    NextCharacter,
+    ToggleReady,
+    Leave,
+}
+
+#[derive(Component)]
+struct LobbyButtonAction {
+    action: LobbyAction,
+}
+
+// The host address typed in on the lobby screen, kept for the next join
+#[derive(Resource)]
+struct JoinAddress {
+    text: String,
+    error: Option<String>,
+}
+
+impl Default for JoinAddress {
+    fn default() -> Self {
+        JoinAddress {
+            text: format!("127.0.0.1:{DEFAULT_PORT}"),
+            error: None,
+        }
+    }
+}
+
+fn setup_lobby_screen(mut commands: Commands) {
+    let text_style = TextStyle {
+        font_size: 30.0,
+        color: Color::WHITE,
+        ..default()
+    };
+    let row_style = Style {
+        flex_direction: FlexDirection::Row,
+        align_items: AlignItems::Center,
+        column_gap: Val::Px(20.0),
+        ..default()
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    position_type: PositionType::Absolute,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(20.0),
+                    padding: UiRect::all(Val::Px(20.0)),
+                    ..default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.9).into(),
+                ..default()
+            },
+            LobbyScreen,
+        ))
+        .with_children(|parent| {
+            // Title
+            parent.spawn(TextBundle::from_section(
+                "ONLINE LOBBY",
+                TextStyle {
+                    font_size: 50.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+
+            parent.spawn((
+                TextBundle::from_section("", text_style.clone()),
+                LobbyStatusDisplay,
+            ));
+
+            // Host address, typed in
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: row_style.clone(),
+                        ..default()
+                    },
+                    LobbyAddressRow,
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        TextBundle::from_section("", text_style.clone()),
+                        LobbyAddressDisplay,
+                    ));
+                    spawn_lobby_button(parent, "CONNECT", LobbyAction::Connect);
+                });
+
+            // Own character and ready state
+            parent
+                .spawn(NodeBundle {
+                    style: row_style.clone(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    spawn_lobby_button(parent, "<", LobbyAction::PrevCharacter);
+                    parent.spawn((
+                        TextBundle::from_section("", text_style.clone()),
+                        LobbyCharacterDisplay,
+                    ));
+                    spawn_lobby_button(parent, ">", LobbyAction::NextCharacter);
+                });
+            parent
+                .spawn(NodeBundle {
+                    style: row_style.clone(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        TextBundle::from_section("", text_style.clone()),
+                        LobbyReadyDisplay,
+                    ));
+                    spawn_lobby_button(parent, "TOGGLE", LobbyAction::ToggleReady);
+                });
+
+            // What the peer has picked
+            parent.spawn((
+                TextBundle::from_section("", text_style.clone()),
+                LobbyPeerDisplay,
+            ));
+            parent.spawn((
+                TextBundle::from_section("", text_style.clone()),
+                LobbyPingDisplay,
+            ));
+
+            spawn_lobby_button(parent, "BACK", LobbyAction::Leave);
+        });
+}
+
+fn spawn_lobby_button(parent: &mut ChildBuilder, text: &str, action: LobbyAction) {
+    spawn_button(parent, text, LobbyButtonAction { action });
+}
+
+// Parses the typed address and starts knocking on the host
+fn connect(session: &mut NetSession, address: &mut JoinAddress) {
+    match address.text.trim().parse::<SocketAddr>() {
+        Ok(peer) if session.join(peer) => address.error = None,
+        Ok(_) => address.error = Some("Could not open a UDP socket".to_string()),
+        Err(_) => address.error = Some("Enter the host as IP:port".to_string()),
+    }
+}
+
+fn lobby_interaction(
+    interaction_query: Query<(&Interaction, &LobbyButtonAction), InteractingButtonFilter>,
+    mut session: ResMut<NetSession>,
+    mut address: ResMut<JoinAddress>,
+    mut app_state: ResMut<NextState<AppState>>,
+) {
+    for (interaction, button_action) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button_action.action {
+            LobbyAction::Connect => {
+                if session.mode() == NetMode::Offline {
+                    connect(&mut session, &mut address);
+                }
+            }
+            LobbyAction::PrevCharacter => {
+                let character = prev_character(session.local_choice().character);
+                session.set_character(character);
+            }
+            LobbyAction::NextCharacter => {
+                let character = next_character(session.local_choice().character);
+                session.set_character(character);
+            }
+            LobbyAction::ToggleReady => session.toggle_ready(),
+            // Leaving for the main menu closes the session
+            LobbyAction::Leave => app_state.set(AppState::MainMenu),
+        }
+    }
+}
+
+// Typing for the address field, until a join is under way
+fn type_join_address(
+    mut key_events: EventReader<KeyboardInput>,
+    mut session: ResMut<NetSession>,
+    mut address: ResMut<JoinAddress>,
+) {
+    if session.mode() != NetMode::Offline {
+        key_events.clear();
+        return;
+    }
+    for event in key_events.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+        match &event.logical_key {
+            Key::Character(text) => {
+                address.text.extend(
+                    text.chars().filter(|c| {
+                        c.is_ascii_alphanumeric() || matches!(c, '.' | ':' | '[' | ']')
+                    }),
+                );
+            }
+            Key::Backspace => {
+                address.text.pop();
+            }
+            Key::Enter => connect(&mut session, &mut address),
+            _ => {}
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_lobby_display(
+    mut set: ParamSet<(
+        Query<&mut Text, With<LobbyStatusDisplay>>,
+        Query<&mut Text, With<LobbyAddressDisplay>>,
+        Query<&mut Text, With<LobbyCharacterDisplay>>,
+        Query<&mut Text, With<LobbyReadyDisplay>>,
+        Query<&mut Text, With<LobbyPeerDisplay>>,
+        Query<&mut Text, With<LobbyPingDisplay>>,
+    )>,
+    mut address_row: Query<&mut Style, With<LobbyAddressRow>>,
+    session: Res<NetSession>,
+    address: Res<JoinAddress>,
+) {
+    let entering_address = session.mode() == NetMode::Offline;
+    let peer_address = session
+        .peer_address()
+        .map(|peer| peer.to_string())
+        .unwrap_or_default();
+    let status = match (&address.error, session.mode(), session.is_connected()) {
+        (Some(error), NetMode::Offline, _) => error.clone(),
+        (None, NetMode::Offline, _) => "Type the host's address and connect".to_string(),
+        (_, NetMode::Host, false) => format!(
+            "Hosting on UDP port {}, waiting for a peer",
+            session.local_port().unwrap_or(DEFAULT_PORT)
+        ),
+        (_, _, false) => format!("Connecting to {peer_address}..."),
+        (_, _, true) => format!("Connected to {peer_address}"),
+    };
+    for mut text in set.p0().iter_mut() {
+        text.sections[0].value = status.clone();
+    }
+
+    for mut style in address_row.iter_mut() {
+        style.display = if entering_address {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+    for mut text in set.p1().iter_mut() {
+        text.sections[0].value = format!("ADDRESS: {}_", address.text);
+    }
+
+    let local = session.local_choice();
+    let (local_side, peer_side) = if session.local_player() == 2 {
+        ("P2", "P1")
+    } else {
+        ("P1", "P2")
+    };
+    for mut text in set.p2().iter_mut() {
+        text.sections[0].value = format!(
+            "YOU ({local_side}): {}",
+            local.character.name().to_uppercase()
+        );
+    }
+    for mut text in set.p3().iter_mut() {
+        text.sections[0].value = format!("READY: {}", yes_no(local.ready));
+        text.sections[0].style.color = if local.ready {
+            Color::srgb(0.4, 1.0, 0.4)
+        } else {
+            Color::WHITE
+        };
+    }
+
+    let peer_text = match session.peer_choice() {
+        Some(peer) => format!(
+            "PEER ({peer_side}): {} - READY: {}",
+            peer.character.name().to_uppercase(),
+            yes_no(peer.ready)
+        ),
+        None => format!("PEER ({peer_side}): -"),
+    };
+    for mut text in set.p4().iter_mut() {
+        text.sections[0].value = peer_text.clone();
+    }
+    let ping_text = match session.ping_ms() {
+        Some(ping) => format!("PING: {ping:.0} ms"),
+        None => "PING: -".to_string(),
+    };
+    for mut text in set.p5().iter_mut() {
+        text.sections[0].value = ping_text.clone();
+    }
+}
+
+fn cleanup_lobby_screen(mut commands: Commands, query: Query<Entity, With<LobbyScreen>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
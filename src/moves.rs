@@ -0,0 +1,806 @@
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::boxes::{shape, BoxOverrides, BoxPose, BoxShape};
+use crate::game_state::AppState;
+use crate::player::{AttackHeight, AttackType};
+use crate::{CharacterType, GameAssets};
+
+// Move data is authored in frames at this rate
+pub const FRAMES_PER_SECOND: f32 = 60.0;
+
+pub fn frames_to_duration(frames: u32) -> Duration {
+    Duration::from_secs_f32(frames as f32 / FRAMES_PER_SECOND)
+}
+
+pub struct MovesPlugin;
+
+impl Plugin for MovesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<MoveSet>()
+            .init_asset_loader::<MoveSetLoader>()
+            // Gameplay steps at exactly one frame of frame data per tick
+            .insert_resource(Time::<Fixed>::from_hz(FRAMES_PER_SECOND as f64))
+            .init_resource::<FrameCounter>()
+            .add_systems(OnEnter(AppState::InGame), reset_frame_counter)
+            .add_systems(
+                FixedFirst,
+                advance_frame_counter.run_if(in_state(AppState::InGame)),
+            );
+    }
+}
+
+// Simulation ticks since the fight started
+#[derive(Resource, Default)]
+pub struct FrameCounter(pub u32);
+
+fn reset_frame_counter(mut frames: ResMut<FrameCounter>) {
+    frames.0 = 0;
+}
+
+pub fn advance_frame_counter(mut frames: ResMut<FrameCounter>) {
+    frames.0 += 1;
+}
+
+// -- Data --
+
+#[derive(Debug, Clone)]
+pub struct MoveData {
+    pub startup_frames: u32,
+    pub active_frames: u32,
+    pub recovery_frames: u32,
+    pub cooldown_frames: u32,
+    pub hitbox_offset: Vec2, // Relative to the attacker when facing right
+    pub hitbox_size: Vec2,
+    pub damage: i32,
+    pub knockback: f32,
+    pub hitstun_frames: u32,
+    pub blockstun_frames: u32,
+    pub height: AttackHeight,
+    pub chip_damage: i32,                   // Damage dealt through a block
+    pub guard_damage: f32,                  // Guard meter drained when blocked
+    pub projectile: Option<ProjectileData>, // Set for attacks that travel instead of staying attached
+    pub knockdown: bool,                    // Hard knockdown instead of hitstun
+    pub hitbox_frames: Vec<HitboxFrame>, // Optional per-frame hitbox shapes during the active window
+    pub juggle_points: u32,              // Juggle points spent when hitting an airborne defender
+    pub landing_recovery_frames: u32,    // Recovery when landing during an aerial attack
+    pub hurtbox_frames: Vec<HurtboxFrame>, // Per-frame hurtbox changes over the whole move
+}
+
+// Hitbox shape used from `frame` (counted from the first active frame) until the next entry
+#[derive(Debug, Clone)]
+pub struct HitboxFrame {
+    pub frame: u32,
+    pub offset: Vec2,
+    pub size: Vec2,
+}
+
+// Hurtbox changes used from `frame` (counted from the first startup frame) until the next entry
+#[derive(Debug, Clone)]
+pub struct HurtboxFrame {
+    pub frame: u32,
+    pub boxes: BoxOverrides,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProjectileData {
+    pub speed: f32,
+    pub lifetime_frames: u32,
+}
+
+impl MoveData {
+    // Built-in values used when the move data asset is missing or incomplete
+    pub fn fallback(attack_type: AttackType) -> Self {
+        match attack_type {
+            AttackType::Light => MoveData {
+                startup_frames: 3,
+                active_frames: 7,
+                recovery_frames: 8,
+                cooldown_frames: 15,
+                hitbox_offset: Vec2::new(55.0, 0.0),
+                hitbox_size: Vec2::new(60.0, 35.0),
+                damage: 3,
+                knockback: 500.0,
+                hitstun_frames: 20,
+                blockstun_frames: 12,
+                height: AttackHeight::High,
+                chip_damage: 0,
+                guard_damage: 10.0,
+                projectile: None,
+                knockdown: false,
+                hitbox_frames: Vec::new(),
+                juggle_points: 1,
+                landing_recovery_frames: 0,
+                hurtbox_frames: reach_hurtbox_frames(),
+            },
+            AttackType::Heavy => MoveData {
+                startup_frames: 8,
+                active_frames: 12,
+                recovery_frames: 18,
+                cooldown_frames: 24,
+                hitbox_offset: Vec2::new(70.0, 0.0),
+                hitbox_size: Vec2::new(80.0, 45.0),
+                damage: 8,
+                knockback: 500.0,
+                hitstun_frames: 30,
+                blockstun_frames: 18,
+                height: AttackHeight::High,
+                chip_damage: 2,
+                guard_damage: 25.0,
+                projectile: None,
+                knockdown: true,
+                hitbox_frames: vec![
+                    HitboxFrame {
+                        frame: 0,
+                        offset: Vec2::new(55.0, 10.0),
+                        size: Vec2::new(50.0, 40.0),
+                    },
+                    HitboxFrame {
+                        frame: 4,
+                        offset: Vec2::new(70.0, 0.0),
+                        size: Vec2::new(80.0, 45.0),
+                    },
+                ],
+                juggle_points: 2,
+                landing_recovery_frames: 0,
+                hurtbox_frames: lunge_hurtbox_frames(8),
+            },
+            AttackType::Kick => MoveData {
+                startup_frames: 5,
+                active_frames: 10,
+                recovery_frames: 12,
+                cooldown_frames: 18,
+                hitbox_offset: Vec2::new(65.0, -10.0),
+                hitbox_size: Vec2::new(70.0, 40.0),
+                damage: 5,
+                knockback: 500.0,
+                hitstun_frames: 24,
+                blockstun_frames: 14,
+                height: AttackHeight::Low,
+                chip_damage: 0,
+                guard_damage: 15.0,
+                projectile: None,
+                knockdown: false,
+                hitbox_frames: vec![
+                    HitboxFrame {
+                        frame: 0,
+                        offset: Vec2::new(50.0, -20.0),
+                        size: Vec2::new(45.0, 30.0),
+                    },
+                    HitboxFrame {
+                        frame: 3,
+                        offset: Vec2::new(65.0, -10.0),
+                        size: Vec2::new(70.0, 40.0),
+                    },
+                ],
+                juggle_points: 1,
+                landing_recovery_frames: 0,
+                hurtbox_frames: kick_hurtbox_frames(5),
+            },
+            AttackType::Projectile => MoveData {
+                startup_frames: 12,
+                active_frames: 2,
+                recovery_frames: 20,
+                cooldown_frames: 60,
+                hitbox_offset: Vec2::new(50.0, 10.0),
+                hitbox_size: Vec2::new(30.0, 20.0),
+                damage: 6,
+                knockback: 300.0,
+                hitstun_frames: 24,
+                blockstun_frames: 16,
+                height: AttackHeight::High,
+                chip_damage: 1,
+                guard_damage: 15.0,
+                projectile: Some(ProjectileData {
+                    speed: 450.0,
+                    lifetime_frames: 90,
+                }),
+                knockdown: false,
+                hitbox_frames: Vec::new(),
+                juggle_points: 1,
+                landing_recovery_frames: 0,
+                hurtbox_frames: lunge_hurtbox_frames(12),
+            },
+            AttackType::Super => MoveData {
+                startup_frames: 10,
+                active_frames: 8,
+                recovery_frames: 30,
+                cooldown_frames: 45,
+                hitbox_offset: Vec2::new(75.0, 0.0),
+                hitbox_size: Vec2::new(120.0, 60.0),
+                damage: 20,
+                knockback: 800.0,
+                hitstun_frames: 45,
+                blockstun_frames: 24,
+                height: AttackHeight::High,
+                chip_damage: 5,
+                guard_damage: 40.0,
+                projectile: None,
+                knockdown: true,
+                hitbox_frames: Vec::new(),
+                juggle_points: 3,
+                landing_recovery_frames: 0,
+                hurtbox_frames: lunge_hurtbox_frames(10),
+            },
+            AttackType::Throw => MoveData {
+                startup_frames: 4,
+                active_frames: 3,
+                recovery_frames: 20,
+                cooldown_frames: 30,
+                hitbox_offset: Vec2::new(40.0, 0.0),
+                hitbox_size: Vec2::new(40.0, 60.0),
+                damage: 10,
+                knockback: 600.0,
+                hitstun_frames: 0,
+                blockstun_frames: 0,
+                height: AttackHeight::High,
+                chip_damage: 0,
+                guard_damage: 0.0,
+                projectile: None,
+                knockdown: true,
+                hitbox_frames: Vec::new(),
+                juggle_points: 0,
+                landing_recovery_frames: 0,
+                hurtbox_frames: Vec::new(),
+            },
+            AttackType::AirLight => MoveData {
+                startup_frames: 4,
+                active_frames: 6,
+                recovery_frames: 8,
+                cooldown_frames: 15,
+                hitbox_offset: Vec2::new(45.0, -10.0),
+                hitbox_size: Vec2::new(55.0, 35.0),
+                damage: 3,
+                knockback: 300.0,
+                hitstun_frames: 20,
+                blockstun_frames: 12,
+                height: AttackHeight::High,
+                chip_damage: 0,
+                guard_damage: 10.0,
+                projectile: None,
+                knockdown: false,
+                hitbox_frames: Vec::new(),
+                juggle_points: 1,
+                landing_recovery_frames: 4,
+                hurtbox_frames: reach_hurtbox_frames(),
+            },
+            AttackType::AirHeavy => MoveData {
+                startup_frames: 9,
+                active_frames: 8,
+                recovery_frames: 16,
+                cooldown_frames: 24,
+                hitbox_offset: Vec2::new(55.0, -25.0),
+                hitbox_size: Vec2::new(70.0, 50.0),
+                damage: 7,
+                knockback: 450.0,
+                hitstun_frames: 28,
+                blockstun_frames: 16,
+                height: AttackHeight::High,
+                chip_damage: 2,
+                guard_damage: 20.0,
+                projectile: None,
+                knockdown: false,
+                hitbox_frames: Vec::new(),
+                juggle_points: 2,
+                landing_recovery_frames: 10,
+                hurtbox_frames: lunge_hurtbox_frames(9),
+            },
+            AttackType::AirKick => MoveData {
+                startup_frames: 5,
+                active_frames: 12,
+                recovery_frames: 10,
+                cooldown_frames: 18,
+                hitbox_offset: Vec2::new(40.0, -40.0),
+                hitbox_size: Vec2::new(50.0, 40.0),
+                damage: 5,
+                knockback: 350.0,
+                hitstun_frames: 22,
+                blockstun_frames: 14,
+                height: AttackHeight::High,
+                chip_damage: 0,
+                guard_damage: 15.0,
+                projectile: None,
+                knockdown: false,
+                hitbox_frames: Vec::new(),
+                juggle_points: 1,
+                landing_recovery_frames: 6,
+                hurtbox_frames: kick_hurtbox_frames(5),
+            },
+        }
+    }
+
+    pub fn hurtbox_frame_index(&self, frame: u32) -> Option<usize> {
+        self.hurtbox_frames
+            .iter()
+            .rposition(|hurtbox_frame| hurtbox_frame.frame <= frame)
+    }
+
+    pub fn cooldown(&self) -> Duration {
+        frames_to_duration(self.cooldown_frames)
+    }
+
+    pub fn active_duration(&self) -> Duration {
+        frames_to_duration(self.active_frames)
+    }
+
+    pub fn hitstun(&self) -> Duration {
+        frames_to_duration(self.hitstun_frames)
+    }
+
+    pub fn blockstun(&self) -> Duration {
+        frames_to_duration(self.blockstun_frames)
+    }
+
+    // How long the hitbox stays alive - projectiles outlive the attacker's active window
+    pub fn hitbox_lifetime(&self) -> Duration {
+        match &self.projectile {
+            Some(projectile) => frames_to_duration(projectile.lifetime_frames),
+            None => self.active_duration(),
+        }
+    }
+
+    // Hitbox offset and size on the given active frame
+    pub fn hitbox_shape(&self, active_frame: u32) -> (Vec2, Vec2) {
+        match self.hitbox_frame_index(active_frame) {
+            Some(index) => (
+                self.hitbox_frames[index].offset,
+                self.hitbox_frames[index].size,
+            ),
+            None => (self.hitbox_offset, self.hitbox_size),
+        }
+    }
+
+    pub fn hitbox_frame_index(&self, active_frame: u32) -> Option<usize> {
+        self.hitbox_frames
+            .iter()
+            .rposition(|hitbox_frame| hitbox_frame.frame <= active_frame)
+    }
+
+    fn from_json(value: &serde_json::Value, attack_type: AttackType) -> Self {
+        let mut data = MoveData::fallback(attack_type);
+
+        if let Some(frames) = read_u32(value, "startup_frames") {
+            data.startup_frames = frames;
+        }
+        if let Some(frames) = read_u32(value, "active_frames") {
+            data.active_frames = frames;
+        }
+        if let Some(frames) = read_u32(value, "recovery_frames") {
+            data.recovery_frames = frames;
+        }
+        if let Some(frames) = read_u32(value, "cooldown_frames") {
+            data.cooldown_frames = frames;
+        }
+        if let Some(offset) = read_vec2(value, "hitbox_offset") {
+            data.hitbox_offset = offset;
+        }
+        if let Some(size) = read_vec2(value, "hitbox_size") {
+            data.hitbox_size = size;
+        }
+        if let Some(damage) = value.get("damage").and_then(|v| v.as_i64()) {
+            data.damage = damage as i32;
+        }
+        if let Some(knockback) = value.get("knockback").and_then(|v| v.as_f64()) {
+            data.knockback = knockback as f32;
+        }
+        if let Some(frames) = read_u32(value, "hitstun_frames") {
+            data.hitstun_frames = frames;
+        }
+        if let Some(frames) = read_u32(value, "blockstun_frames") {
+            data.blockstun_frames = frames;
+        }
+        match value.get("height").and_then(|v| v.as_str()) {
+            Some("High") => data.height = AttackHeight::High,
+            Some("Low") => data.height = AttackHeight::Low,
+            Some(other) => tracing::warn!("Unknown attack height '{}'", other),
+            None => {}
+        }
+        if let Some(chip) = value.get("chip_damage").and_then(|v| v.as_i64()) {
+            data.chip_damage = chip as i32;
+        }
+        if let Some(guard) = value.get("guard_damage").and_then(|v| v.as_f64()) {
+            data.guard_damage = guard as f32;
+        }
+        if let Some(knockdown) = value.get("knockdown").and_then(|v| v.as_bool()) {
+            data.knockdown = knockdown;
+        }
+        if let Some(points) = read_u32(value, "juggle_points") {
+            data.juggle_points = points;
+        }
+        if let Some(frames) = read_u32(value, "landing_recovery_frames") {
+            data.landing_recovery_frames = frames;
+        }
+        if let Some(frames) = value.get("hitbox_frames").and_then(|v| v.as_array()) {
+            data.hitbox_frames = frames
+                .iter()
+                .map(|frame| HitboxFrame {
+                    frame: read_u32(frame, "frame").unwrap_or(0),
+                    offset: read_vec2(frame, "offset").unwrap_or(data.hitbox_offset),
+                    size: read_vec2(frame, "size").unwrap_or(data.hitbox_size),
+                })
+                .collect();
+        }
+        if let Some(frames) = value.get("hurtbox_frames").and_then(|v| v.as_array()) {
+            data.hurtbox_frames = frames
+                .iter()
+                .map(|frame| HurtboxFrame {
+                    frame: read_u32(frame, "frame").unwrap_or(0),
+                    boxes: read_box_overrides(frame),
+                })
+                .collect();
+        }
+        if let Some(projectile) = data.projectile.as_mut() {
+            if let Some(speed) = value.get("projectile_speed").and_then(|v| v.as_f64()) {
+                projectile.speed = speed as f32;
+            }
+            if let Some(frames) = read_u32(value, "projectile_lifetime_frames") {
+                projectile.lifetime_frames = frames;
+            }
+        }
+
+        data
+    }
+}
+
+// Built-in hurtbox frames: quick attacks reach out with the torso
+fn reach_hurtbox_frames() -> Vec<HurtboxFrame> {
+    vec![HurtboxFrame {
+        frame: 0,
+        boxes: BoxOverrides {
+            torso: Some(shape(6.0, 0.0, 48.0, 40.0)),
+            ..default()
+        },
+    }]
+}
+
+// Wind up during startup, then lean into the swing
+fn lunge_hurtbox_frames(startup_frames: u32) -> Vec<HurtboxFrame> {
+    vec![
+        HurtboxFrame {
+            frame: 0,
+            boxes: BoxOverrides {
+                torso: Some(shape(-4.0, 0.0, 44.0, 40.0)),
+                ..default()
+            },
+        },
+        HurtboxFrame {
+            frame: startup_frames,
+            boxes: BoxOverrides {
+                head: Some(shape(8.0, 33.0, 30.0, 30.0)),
+                torso: Some(shape(10.0, 0.0, 52.0, 40.0)),
+                ..default()
+            },
+        },
+    ]
+}
+
+// The extended leg is vulnerable, so whiffed kicks can be stuffed low
+fn kick_hurtbox_frames(startup_frames: u32) -> Vec<HurtboxFrame> {
+    vec![
+        HurtboxFrame {
+            frame: 0,
+            boxes: BoxOverrides {
+                legs: Some(shape(10.0, -35.0, 44.0, 30.0)),
+                ..default()
+            },
+        },
+        HurtboxFrame {
+            frame: startup_frames,
+            boxes: BoxOverrides {
+                legs: Some(shape(25.0, -30.0, 60.0, 26.0)),
+                ..default()
+            },
+        },
+    ]
+}
+
+// Move data for every character, keyed by character name and then attack name.
+// The "Default" entry is used for any character or attack without its own data, and a
+// "Poses" entry holds a character's hurtbox layouts outside of attacks.
+#[derive(Asset, TypePath, Debug, Clone, Default)]
+pub struct MoveSet {
+    characters: HashMap<String, HashMap<String, MoveData>>,
+    poses: HashMap<String, HashMap<BoxPose, BoxOverrides>>,
+}
+
+impl MoveSet {
+    pub fn get(&self, character: CharacterType, attack_type: AttackType) -> Option<&MoveData> {
+        let attack = attack_name(attack_type);
+        self.characters
+            .get(character.name())
+            .and_then(|moves| moves.get(attack))
+            .or_else(|| {
+                self.characters
+                    .get("Default")
+                    .and_then(|moves| moves.get(attack))
+            })
+    }
+
+    pub fn pose(&self, character: CharacterType, pose: BoxPose) -> Option<&BoxOverrides> {
+        self.poses
+            .get(character.name())
+            .and_then(|poses| poses.get(&pose))
+            .or_else(|| self.poses.get("Default").and_then(|poses| poses.get(&pose)))
+    }
+
+    fn from_json(json: &serde_json::Value) -> Result<Self, std::io::Error> {
+        let characters_obj = json.as_object().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "move data must be an object keyed by character name",
+            )
+        })?;
+
+        let mut characters = HashMap::new();
+        let mut poses = HashMap::new();
+        for (character, moves_value) in characters_obj {
+            let mut moves = HashMap::new();
+            if let Some(moves_obj) = moves_value.as_object() {
+                for (attack, move_value) in moves_obj {
+                    if attack == "Poses" {
+                        poses.insert(character.clone(), read_poses(move_value, character));
+                        continue;
+                    }
+                    let Some(attack_type) = parse_attack_name(attack) else {
+                        tracing::warn!("Unknown attack '{}' for {}", attack, character);
+                        continue;
+                    };
+                    moves.insert(attack.clone(), MoveData::from_json(move_value, attack_type));
+                }
+            }
+            characters.insert(character.clone(), moves);
+        }
+
+        Ok(MoveSet { characters, poses })
+    }
+}
+
+#[derive(Default)]
+pub struct MoveSetLoader;
+
+impl AssetLoader for MoveSetLoader {
+    type Asset = MoveSet;
+    type Settings = ();
+    type Error = std::io::Error;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<MoveSet, std::io::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let json = serde_json::from_slice::<serde_json::Value>(&bytes)?;
+        let move_set = MoveSet::from_json(&json)?;
+        tracing::info!(
+            "Move data loaded for {} characters",
+            move_set.characters.len()
+        );
+        Ok(move_set)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["moves.json"]
+    }
+}
+
+// Looks up move data for a fighter, falling back to built-in values until the asset is loaded
+#[derive(SystemParam)]
+pub struct MoveLibrary<'w> {
+    move_sets: Res<'w, Assets<MoveSet>>,
+    game_assets: Res<'w, GameAssets>,
+}
+
+impl MoveLibrary<'_> {
+    pub fn get(&self, character: CharacterType, attack_type: AttackType) -> MoveData {
+        self.move_sets
+            .get(&self.game_assets.move_data)
+            .and_then(|move_set| move_set.get(character, attack_type))
+            .cloned()
+            .unwrap_or_else(|| MoveData::fallback(attack_type))
+    }
+
+    // Hurtbox layout for a pose outside of attacks, if the move data has one
+    pub fn pose_boxes(&self, character: CharacterType, pose: BoxPose) -> Option<BoxOverrides> {
+        self.move_sets
+            .get(&self.game_assets.move_data)
+            .and_then(|move_set| move_set.pose(character, pose))
+            .copied()
+    }
+}
+
+// -- Helper Functions --
+
+pub fn parse_attack_name(name: &str) -> Option<AttackType> {
+    match name {
+        "Light" => Some(AttackType::Light),
+        "Heavy" => Some(AttackType::Heavy),
+        "Kick" => Some(AttackType::Kick),
+        "Projectile" => Some(AttackType::Projectile),
+        "Super" => Some(AttackType::Super),
+        "Throw" => Some(AttackType::Throw),
+        "AirLight" => Some(AttackType::AirLight),
+        "AirHeavy" => Some(AttackType::AirHeavy),
+        "AirKick" => Some(AttackType::AirKick),
+        _ => None,
+    }
+}
+
+fn attack_name(attack_type: AttackType) -> &'static str {
+    match attack_type {
+        AttackType::Light => "Light",
+        AttackType::Heavy => "Heavy",
+        AttackType::Kick => "Kick",
+        AttackType::Projectile => "Projectile",
+        AttackType::Super => "Super",
+        AttackType::Throw => "Throw",
+        AttackType::AirLight => "AirLight",
+        AttackType::AirHeavy => "AirHeavy",
+        AttackType::AirKick => "AirKick",
+    }
+}
+
+fn read_u32(value: &serde_json::Value, key: &str) -> Option<u32> {
+    value.get(key).and_then(|v| v.as_u64()).map(|v| v as u32)
+}
+
+fn read_vec2(value: &serde_json::Value, key: &str) -> Option<Vec2> {
+    let array = value.get(key)?.as_array()?;
+    match array.as_slice() {
+        [x, y] => Some(Vec2::new(x.as_f64()? as f32, y.as_f64()? as f32)),
+        _ => None,
+    }
+}
+
+// A box needs both an offset and a size
+fn read_box(value: &serde_json::Value, key: &str) -> Option<BoxShape> {
+    let box_value = value.get(key)?;
+    Some(BoxShape {
+        offset: read_vec2(box_value, "offset")?,
+        size: read_vec2(box_value, "size")?,
+    })
+}
+
+fn read_box_overrides(value: &serde_json::Value) -> BoxOverrides {
+    BoxOverrides {
+        pushbox_width: value
+            .get("pushbox_width")
+            .and_then(|v| v.as_f64())
+            .map(|v| v as f32),
+        head: read_box(value, "head"),
+        torso: read_box(value, "torso"),
+        legs: read_box(value, "legs"),
+    }
+}
+
+fn read_poses(value: &serde_json::Value, character: &str) -> HashMap<BoxPose, BoxOverrides> {
+    let mut poses = HashMap::new();
+    for (name, pose_value) in value.as_object().into_iter().flatten() {
+        let Some(pose) = BoxPose::parse(name) else {
+            tracing::warn!("Unknown pose '{}' for {}", name, character);
+            continue;
+        };
+        poses.insert(pose, read_box_overrides(pose_value));
+    }
+    poses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_fall_back_to_built_in_values() {
+        let data = MoveData::from_json(&serde_json::json!({}), AttackType::Heavy);
+        let fallback = MoveData::fallback(AttackType::Heavy);
+        assert_eq!(data.startup_frames, fallback.startup_frames);
+        assert_eq!(data.damage, fallback.damage);
+        assert_eq!(data.height, fallback.height);
+        assert_eq!(data.hitbox_frames.len(), fallback.hitbox_frames.len());
+    }
+
+    #[test]
+    fn fields_override_only_what_they_set() {
+        let data = MoveData::from_json(
+            &serde_json::json!({
+                "startup_frames": 5,
+                "damage": 12,
+                "height": "Low",
+                "hitbox_offset": [20.0, -10.0],
+            }),
+            AttackType::Light,
+        );
+        let fallback = MoveData::fallback(AttackType::Light);
+        assert_eq!(data.startup_frames, 5);
+        assert_eq!(data.damage, 12);
+        assert_eq!(data.height, AttackHeight::Low);
+        assert_eq!(data.hitbox_offset, Vec2::new(20.0, -10.0));
+        assert_eq!(data.active_frames, fallback.active_frames);
+        assert_eq!(data.hitbox_size, fallback.hitbox_size);
+    }
+
+    #[test]
+    fn bad_values_keep_the_fallback() {
+        let data = MoveData::from_json(
+            &serde_json::json!({
+                "startup_frames": "fast",
+                "height": "Overhead",
+                "knockdown": 1,
+            }),
+            AttackType::Kick,
+        );
+        let fallback = MoveData::fallback(AttackType::Kick);
+        assert_eq!(data.startup_frames, fallback.startup_frames);
+        assert_eq!(data.height, fallback.height);
+        assert_eq!(data.knockdown, fallback.knockdown);
+    }
+
+    #[test]
+    fn hitbox_frames_default_to_the_move_hitbox() {
+        let data = MoveData::from_json(
+            &serde_json::json!({
+                "hitbox_offset": [30.0, 0.0],
+                "hitbox_frames": [{ "frame": 2, "size": [10.0, 10.0] }],
+            }),
+            AttackType::Light,
+        );
+        assert_eq!(data.hitbox_frame_index(1), None);
+        assert_eq!(
+            data.hitbox_shape(3),
+            (Vec2::new(30.0, 0.0), Vec2::new(10.0, 10.0))
+        );
+    }
+
+    #[test]
+    fn characters_fall_back_to_the_default_entry() {
+        let move_set = MoveSet::from_json(&serde_json::json!({
+            "Default": { "Light": { "damage": 4 }, "Heavy": { "damage": 9 } },
+            "Zombie": { "Light": { "damage": 6 }, "Punch": {} },
+        }))
+        .expect("move set should parse");
+        let damage =
+            |character, attack_type| move_set.get(character, attack_type).map(|data| data.damage);
+        assert_eq!(damage(CharacterType::Zombie, AttackType::Light), Some(6));
+        assert_eq!(damage(CharacterType::Zombie, AttackType::Heavy), Some(9));
+        assert_eq!(damage(CharacterType::Player, AttackType::Light), Some(4));
+        assert_eq!(damage(CharacterType::Player, AttackType::Kick), None);
+    }
+
+    #[test]
+    fn hurtbox_frames_and_poses_come_from_the_move_data() {
+        let move_set = MoveSet::from_json(&serde_json::json!({
+            "Default": {
+                "Poses": { "Crouching": { "pushbox_width": 30.0 }, "Sitting": {} },
+                "Light": {
+                    "hurtbox_frames": [{
+                        "frame": 2,
+                        "head": { "offset": [1.0, 2.0], "size": [3.0, 4.0] },
+                        "legs": { "offset": [0.0, 0.0] },
+                    }],
+                },
+            },
+            "Zombie": {},
+        }))
+        .expect("move set should parse");
+        let light = move_set
+            .get(CharacterType::Zombie, AttackType::Light)
+            .expect("light falls back to the default entry");
+        assert_eq!(light.hurtbox_frame_index(1), None);
+        let boxes =
+            light.hurtbox_frames[light.hurtbox_frame_index(5).expect("frame 2 applies")].boxes;
+        assert_eq!(boxes.head, Some(shape(1.0, 2.0, 3.0, 4.0)));
+        assert_eq!(boxes.legs, None);
+
+        let pose = |pose| move_set.pose(CharacterType::Zombie, pose);
+        assert_eq!(
+            pose(BoxPose::Crouching).and_then(|boxes| boxes.pushbox_width),
+            Some(30.0)
+        );
+        assert!(pose(BoxPose::Jumping).is_none());
+    }
+}
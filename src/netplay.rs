@@ -0,0 +1,1579 @@
+use bevy::app::FixedMain;
+use bevy::ecs::event::ManualEventReader;
+use bevy::ecs::schedule::{ExecutorKind, ScheduleLabel};
+use bevy::prelude::*;
+use bevy_xpbd_2d::prelude::*;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::net::{SocketAddr, UdpSocket};
+
+use crate::ai::Blackboard;
+use crate::boss::{CurrentPhase, PhaseChangeEvent, PhaseTransition};
+use crate::boxes::{AnimatedHitbox, AppliedBoxLayout, GameLayer};
+use crate::combat::{
+    not_in_hitstop, not_in_super_freeze, Blockstun, Combo, CounterHitEvent, DamageEvent,
+    FightTracker, GrabBox, Hitbox, HitboxDuration, Hitstop, Hitstun, HitstunEndedEvent,
+    HurtboxDisabled, Juggle, Knockdown, LandingRecovery, ParryEvent, Projectile, SpawnHitboxEvent,
+    SuperEvent, SuperFreeze, SuperMeter, Thrown,
+};
+use crate::game_state::{AppState, GameConfig};
+use crate::habits::HabitProfile;
+use crate::input::{record_inputs, FrameInput, InputBuffer};
+use crate::moves::{advance_frame_counter, FrameCounter};
+use crate::player::{
+    AttackCooldown, AttackState, BlockState, ControlType, Dash, FacingDirection, Grounded, Health,
+    Player,
+};
+use crate::replay::player_slot;
+use crate::rng::GameRng;
+use crate::status::StatusEffects;
+use crate::CharacterType;
+
+// Frames each fighter's own buttons are held back, which hides most of the network delay
+const DEFAULT_INPUT_DELAY: u32 = 2;
+// How far the simulation may run ahead of the peer's inputs before it waits for them
+const MAX_PREDICTION_FRAMES: u32 = 8;
+// Extra ticks can slip through while a freeze ends mid-wait, so keep some spare snapshots
+const SNAPSHOT_HISTORY: usize = MAX_PREDICTION_FRAMES as usize * 2;
+// Frames sync test rolls back and replays every rendered frame
+const DEFAULT_SYNCTEST_FRAMES: u32 = 4;
+const MAX_INPUTS_PER_PACKET: u32 = 32;
+// The peers compare checksums of every confirmed frame that's a multiple of this
+const CHECKSUM_INTERVAL_FRAMES: u32 = 60;
+const HELLO_INTERVAL_SECONDS: f32 = 0.5;
+// UDP port the lobby hosts on unless `--host` names another
+pub const DEFAULT_PORT: u16 = 7000;
+// How often the lobby resends each side's picks, and measures the ping
+const LOBBY_INTERVAL_SECONDS: f32 = 0.25;
+const PING_INTERVAL_SECONDS: f32 = 1.0;
+// A peer that stays silent this long, in the lobby or mid-fight, is taken to have gone
+const PEER_TIMEOUT_SECONDS: f32 = 5.0;
+
+pub struct NetplayPlugin;
+
+impl Plugin for NetplayPlugin {
+    fn build(&self, app: &mut App) {
+        // Rollback and replays need every tick to run its systems in the same order, which
+        // the multi-threaded executor doesn't promise for systems left unordered
+        for schedule in [
+            FixedFirst.intern(),
+            FixedPreUpdate.intern(),
+            FixedUpdate.intern(),
+            FixedPostUpdate.intern(),
+            FixedLast.intern(),
+        ] {
+            app.edit_schedule(schedule, |schedule| {
+                schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+            });
+        }
+
+        let args: Vec<String> = std::env::args().collect();
+        app.insert_resource(NetSession::from_args(&args))
+            .init_resource::<DisconnectNotice>()
+            .add_systems(OnEnter(AppState::InGame), start_session)
+            .add_systems(
+                OnEnter(AppState::MainMenu),
+                (end_session, open_from_args).chain(),
+            )
+            .add_systems(
+                PreUpdate,
+                (
+                    receive_packets.run_if(is_online),
+                    roll_back
+                        .run_if(in_state(AppState::InGame))
+                        .run_if(rollback_enabled),
+                    wait_for_peer
+                        .run_if(in_state(AppState::InGame))
+                        .run_if(is_online),
+                )
+                    .chain(),
+            )
+            .add_systems(Update, say_hello.run_if(is_online))
+            .add_systems(
+                Update,
+                (update_lobby, start_when_ready)
+                    .run_if(in_state(AppState::Lobby))
+                    .run_if(is_online),
+            )
+            .add_systems(
+                Update,
+                leave_on_disconnect
+                    .run_if(in_state(AppState::InGame))
+                    .run_if(is_online),
+            )
+            .add_systems(
+                Update,
+                send_inputs
+                    .run_if(in_state(AppState::InGame).or_else(in_state(AppState::GameOver)))
+                    .run_if(is_online),
+            )
+            .add_systems(
+                FixedFirst,
+                save_snapshot
+                    .before(advance_frame_counter)
+                    .run_if(in_state(AppState::InGame))
+                    .run_if(rollback_enabled),
+            )
+            .add_systems(
+                FixedPreUpdate,
+                collect_local_inputs
+                    .before(record_inputs)
+                    .run_if(in_state(AppState::InGame))
+                    .run_if(rollback_enabled),
+            );
+    }
+}
+
+// -- Session --
+
+// How the fight is played, chosen from the main menu's HOST ONLINE and JOIN ONLINE, or on the
+// command line with `--host <port>`, `--join <ip:port>` or `--synctest [frames]`, plus
+// `--input-delay <frames>`
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum NetMode {
+    #[default]
+    Offline,
+    Host,
+    Join,
+    SyncTest {
+        frames: u32,
+    }, // Rolls back this many frames every frame to catch desyncs
+}
+
+// Why the last online fight was cut short, shown once on the main menu
+#[derive(Resource, Default)]
+pub struct DisconnectNotice(pub Option<String>);
+
+// A lobby opened from the command line, once the main menu is up
+enum Launch {
+    Host(u16),
+    Join(SocketAddr),
+}
+
+// What one side has picked in the lobby
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LobbyChoice {
+    pub character: CharacterType,
+    pub ready: bool,
+}
+
+impl Default for LobbyChoice {
+    fn default() -> Self {
+        LobbyChoice {
+            character: CharacterType::Player,
+            ready: false,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct NetSession {
+    mode: NetMode,
+    launch: Option<Launch>,
+    socket: Option<UdpSocket>,
+    peer: Option<SocketAddr>,
+    connected: bool,
+    lobby: LobbyChoice,
+    peer_lobby: Option<LobbyChoice>,
+    last_heard: f32,                         // Real time the peer was last heard from
+    ping: Option<f32>,                       // Round trip to the peer, in seconds
+    start: Option<serde_json::Value>,        // The host's start, resent if the joiner missed it
+    saved_config: Option<serde_json::Value>, // The player's own settings, restored afterwards
+    local_player: u8,                        // 1 for the host, 2 for the joiner
+    input_delay: u32,
+    inputs: [BTreeMap<u32, FrameInput>; 2], // Known buttons per player, by frame
+    confirmed: [u32; 2],                    // Newest frame each player's buttons are known up to
+    predictions: BTreeMap<u32, FrameInput>, // Guesses made for the peer's buttons
+    newest_frame: u32,                      // Newest tick simulated
+    peer_ack: u32,                          // Newest of our frames the peer has received
+    rollback_from: Option<u32>,             // Earliest tick simulated with a wrong guess
+    snapshots: VecDeque<Snapshot>,
+    checksums: BTreeMap<u32, u64>,
+    peer_checksums: BTreeMap<u32, u64>,
+    collisions: ManualEventReader<Collision>,
+    waiting: bool, // Virtual time is paused until the peer catches up
+}
+
+impl NetSession {
+    fn from_args(args: &[String]) -> NetSession {
+        let value = |flag: &str| {
+            args.iter()
+                .position(|arg| arg == flag)
+                .and_then(|index| args.get(index + 1))
+        };
+        let mut session = NetSession {
+            input_delay: DEFAULT_INPUT_DELAY,
+            local_player: 1,
+            ..default()
+        };
+        if let Some(delay) = value("--input-delay").and_then(|delay| delay.parse().ok()) {
+            session.input_delay = delay;
+        }
+
+        if let Some(port) = value("--host") {
+            match port.parse::<u16>() {
+                Ok(port) => session.launch = Some(Launch::Host(port)),
+                Err(_) => tracing::warn!("--host needs a port, e.g. --host 7000"),
+            }
+        } else if let Some(address) = value("--join") {
+            match address.parse::<SocketAddr>() {
+                Ok(peer) => session.launch = Some(Launch::Join(peer)),
+                Err(_) => tracing::warn!("--join needs an address, e.g. --join 127.0.0.1:7000"),
+            }
+        } else if args.iter().any(|arg| arg == "--synctest") {
+            let frames = value("--synctest")
+                .and_then(|frames| frames.parse().ok())
+                .unwrap_or(DEFAULT_SYNCTEST_FRAMES)
+                .clamp(1, MAX_PREDICTION_FRAMES);
+            session.mode = NetMode::SyncTest { frames };
+            session.input_delay = 0;
+            tracing::info!("Sync test: rolling back {} frames every frame", frames);
+        }
+        session
+    }
+
+    // Opens a lobby for a peer to join
+    pub fn host(&mut self, port: u16) -> bool {
+        let Some(socket) = bind(SocketAddr::from(([0, 0, 0, 0], port))) else {
+            return false;
+        };
+        self.socket = Some(socket);
+        self.mode = NetMode::Host;
+        self.local_player = 1;
+        tracing::info!("Hosting on UDP port {}, waiting for a peer", port);
+        true
+    }
+
+    // Knocks on a host's lobby
+    pub fn join(&mut self, peer: SocketAddr) -> bool {
+        let Some(socket) = bind(SocketAddr::from(([0, 0, 0, 0], 0))) else {
+            return false;
+        };
+        self.socket = Some(socket);
+        self.mode = NetMode::Join;
+        self.peer = Some(peer);
+        self.local_player = 2;
+        tracing::info!("Joining {}", peer);
+        true
+    }
+
+    pub fn is_online(&self) -> bool {
+        matches!(self.mode, NetMode::Host | NetMode::Join)
+    }
+
+    pub fn mode(&self) -> NetMode {
+        self.mode
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    pub fn local_player(&self) -> u8 {
+        self.local_player
+    }
+
+    pub fn local_port(&self) -> Option<u16> {
+        let socket = self.socket.as_ref()?;
+        socket.local_addr().ok().map(|address| address.port())
+    }
+
+    pub fn peer_address(&self) -> Option<SocketAddr> {
+        self.peer
+    }
+
+    pub fn local_choice(&self) -> LobbyChoice {
+        self.lobby
+    }
+
+    pub fn peer_choice(&self) -> Option<LobbyChoice> {
+        self.peer_lobby
+    }
+
+    // Picking another character takes back a ready
+    pub fn set_character(&mut self, character: CharacterType) {
+        self.lobby = LobbyChoice {
+            character,
+            ready: false,
+        };
+    }
+
+    pub fn toggle_ready(&mut self) {
+        self.lobby.ready = !self.lobby.ready;
+    }
+
+    pub fn ping_ms(&self) -> Option<f32> {
+        self.ping.map(|ping| ping * 1000.0)
+    }
+
+    // Whether fights keep snapshots and can be rolled back
+    pub fn rolls_back(&self) -> bool {
+        self.is_online() || matches!(self.mode, NetMode::SyncTest { .. })
+    }
+
+    // Which player's keys drive a fighter from this keyboard. Online, the local fighter uses
+    // player 1's keys whichever side it's on, and the peer's fighter isn't read at all.
+    pub fn keyboard_layout(&self, player_id: u8) -> Option<u8> {
+        if self.is_online() {
+            (player_id == self.local_player).then_some(1)
+        } else {
+            Some(player_id)
+        }
+    }
+
+    // A fighter's buttons for a tick; None outside rollback sessions. The peer's buttons are
+    // predicted until they arrive, by assuming they're still holding what they last held.
+    pub fn input(&mut self, frame: u32, player_id: u8) -> Option<FrameInput> {
+        if !self.rolls_back() {
+            return None;
+        }
+        let slot = player_slot(player_id);
+        if let Some(input) = self.inputs[slot].get(&frame) {
+            return Some(*input);
+        }
+        if slot != self.peer_slot() || !self.is_online() {
+            return Some(FrameInput::default());
+        }
+
+        let last_held = self.inputs[slot]
+            .get(&self.confirmed[slot])
+            .map(|input| input.held)
+            .unwrap_or(0);
+        let guess = FrameInput {
+            held: last_held,
+            pressed: 0,
+        };
+        self.predictions.insert(frame, guess);
+        Some(guess)
+    }
+
+    // Forgets a lobby peer that left, so another can join or the joiner can knock again
+    fn drop_peer(&mut self) {
+        self.connected = false;
+        self.peer_lobby = None;
+        self.ping = None;
+        self.lobby.ready = false;
+        if self.mode == NetMode::Host {
+            self.peer = None;
+        }
+    }
+
+    fn peer_slot(&self) -> usize {
+        1 - player_slot(self.local_player)
+    }
+
+    fn peer_confirmed(&self) -> u32 {
+        self.confirmed[self.peer_slot()]
+    }
+
+    fn send(&self, packet: &serde_json::Value) {
+        let (Some(socket), Some(peer)) = (&self.socket, self.peer) else {
+            return;
+        };
+        if let Ok(bytes) = serde_json::to_vec(packet) {
+            // Lost packets are covered by resending, so a failed send is only logged
+            if let Err(error) = socket.send_to(&bytes, peer) {
+                tracing::debug!("Failed to send to {}: {}", peer, error);
+            }
+        }
+    }
+
+    fn receive(&self) -> Vec<(SocketAddr, serde_json::Value)> {
+        let mut packets = Vec::new();
+        let Some(socket) = &self.socket else {
+            return packets;
+        };
+        let mut buffer = [0u8; 2048];
+        while let Ok((length, from)) = socket.recv_from(&mut buffer) {
+            match serde_json::from_slice::<serde_json::Value>(&buffer[..length]) {
+                Ok(packet) => packets.push((from, packet)),
+                Err(_) => tracing::debug!("Ignoring malformed packet from {}", from),
+            }
+        }
+        packets
+    }
+
+    // Stores the peer's buttons, flagging a rollback when one contradicts a guess already
+    // simulated. Buttons must arrive in order; anything after a gap is resent later.
+    fn receive_inputs(&mut self, first: u32, inputs: &[FrameInput]) {
+        let slot = self.peer_slot();
+        for (offset, input) in inputs.iter().enumerate() {
+            let frame = first + offset as u32;
+            if frame != self.confirmed[slot] + 1 {
+                continue;
+            }
+            self.inputs[slot].insert(frame, *input);
+            self.confirmed[slot] = frame;
+            if self
+                .predictions
+                .remove(&frame)
+                .is_some_and(|guess| guess != *input)
+            {
+                self.rollback_from = Some(self.rollback_from.map_or(frame, |from| from.min(frame)));
+            }
+        }
+    }
+
+    fn store_snapshot(&mut self, snapshot: Snapshot) {
+        let frame = snapshot.frame;
+        match self.mode {
+            // Every replay of a frame must land on the same state as the first time through
+            NetMode::SyncTest { .. } => match self.checksums.get(&frame) {
+                Some(&checksum) if checksum != snapshot.checksum => {
+                    tracing::error!(
+                        "Desync at frame {}: checksum {:016x}, first simulated as {:016x}",
+                        frame,
+                        snapshot.checksum,
+                        checksum
+                    );
+                }
+                Some(_) => {}
+                None => {
+                    self.checksums.insert(frame, snapshot.checksum);
+                }
+            },
+            // A frame is final once both fighters' buttons up to it are known
+            _ if frame % CHECKSUM_INTERVAL_FRAMES == 0 && frame <= self.peer_confirmed() => {
+                self.checksums.insert(frame, snapshot.checksum);
+                self.compare_checksums(frame);
+            }
+            _ => {}
+        }
+
+        // A resimulated frame replaces the snapshot taken the first time through
+        self.snapshots.retain(|saved| saved.frame < frame);
+        self.snapshots.push_back(snapshot);
+        while self.snapshots.len() > SNAPSHOT_HISTORY {
+            self.snapshots.pop_front();
+        }
+        self.prune(frame);
+    }
+
+    fn compare_checksums(&self, frame: u32) {
+        if let (Some(ours), Some(theirs)) =
+            (self.checksums.get(&frame), self.peer_checksums.get(&frame))
+        {
+            if ours != theirs {
+                tracing::error!(
+                    "Desync with peer at frame {}: checksum {:016x}, peer has {:016x}",
+                    frame,
+                    ours,
+                    theirs
+                );
+            }
+        }
+    }
+
+    // Drops buttons and checksums too old to be rolled back to or resent
+    fn prune(&mut self, frame: u32) {
+        let oldest = self
+            .snapshots
+            .front()
+            .map(|snapshot| snapshot.frame)
+            .unwrap_or(frame);
+        let local_slot = player_slot(self.local_player);
+        let peer_slot = self.peer_slot();
+        let unacked = self.peer_ack + 1;
+        self.inputs[local_slot].retain(|&input_frame, _| input_frame >= oldest.min(unacked));
+        // The peer's newest known buttons are kept for predictions
+        let newest_peer = self.confirmed[peer_slot];
+        self.inputs[peer_slot]
+            .retain(|&input_frame, _| input_frame >= oldest || input_frame == newest_peer);
+        self.predictions
+            .retain(|&input_frame, _| input_frame > oldest);
+        let history = CHECKSUM_INTERVAL_FRAMES * 4;
+        self.checksums
+            .retain(|&checksum_frame, _| checksum_frame + history > frame);
+        self.peer_checksums
+            .retain(|&checksum_frame, _| checksum_frame + history > frame);
+    }
+}
+
+fn bind(address: SocketAddr) -> Option<UdpSocket> {
+    let socket = match UdpSocket::bind(address) {
+        Ok(socket) => socket,
+        Err(error) => {
+            tracing::warn!("Failed to open UDP socket on {}: {}", address, error);
+            return None;
+        }
+    };
+    if socket.set_nonblocking(true).is_err() {
+        tracing::warn!("Failed to make the UDP socket non-blocking");
+        return None;
+    }
+    Some(socket)
+}
+
+pub fn is_online(session: Res<NetSession>) -> bool {
+    session.is_online()
+}
+
+pub fn rollback_enabled(session: Res<NetSession>) -> bool {
+    session.rolls_back()
+}
+
+// Pausing tears the fight down, which a peer can't follow
+pub fn can_pause(session: Res<NetSession>) -> bool {
+    !session.is_online()
+}
+
+// Whether both fighters' buttons are known for this tick. The outcome of a fight is only
+// decided on confirmed ticks, since a predicted one may yet be rolled back.
+pub fn frame_confirmed(session: Res<NetSession>, frames: Res<FrameCounter>) -> bool {
+    !session.is_online() || frames.0 <= session.peer_confirmed()
+}
+
+// -- Snapshots --
+
+// A fighter's simulation state. Every component is optional so the ones that come and go,
+// like stuns and dashes, are removed again when they weren't there.
+#[derive(Clone)]
+struct FighterSnapshot {
+    entity: Entity,
+    transform: Option<Transform>,
+    velocity: Option<LinearVelocity>,
+    health: Option<Health>,
+    facing: Option<FacingDirection>,
+    cooldown: Option<AttackCooldown>,
+    attack: Option<AttackState>,
+    block: Option<BlockState>,
+    grounded: Option<Grounded>,
+    combo: Option<Combo>,
+    meter: Option<SuperMeter>,
+    status: Option<StatusEffects>,
+    input_buffer: Option<InputBuffer>,
+    dash: Option<Dash>,
+    hitstun: Option<Hitstun>,
+    blockstun: Option<Blockstun>,
+    knockdown: Option<Knockdown>,
+    thrown: Option<Thrown>,
+    juggle: Option<Juggle>,
+    landing: Option<LandingRecovery>,
+    invincible: Option<HurtboxDisabled>,
+    blackboard: Option<Blackboard>, // Boss AI memory and reactions
+    phase: Option<CurrentPhase>,
+    phase_transition: Option<PhaseTransition>,
+}
+
+// An attached hitbox or a projectile
+#[derive(Clone)]
+struct HitboxSnapshot {
+    entity: Entity,
+    parent: Option<Entity>,
+    transform: Transform,
+    velocity: Option<LinearVelocity>,
+    sprite: Option<Sprite>,
+    collider: Option<Collider>,
+    hitbox: Hitbox,
+    duration: Option<HitboxDuration>,
+    projectile: Option<Projectile>,
+    grab: bool,
+    animated: Option<AnimatedHitbox>,
+}
+
+// Everything a tick reads, taken before the tick runs. `frame` is the number of ticks
+// already simulated, so restoring it and stepping once re-runs tick `frame + 1`.
+#[derive(Clone)]
+struct Snapshot {
+    frame: u32,
+    time: Time<Fixed>,
+    rng: GameRng,
+    habits: HabitProfile,
+    tracker: Option<FightTracker>, // Combo and counter hit records for the fight's statistics
+    fighters: Vec<FighterSnapshot>,
+    hitboxes: Vec<HitboxSnapshot>,
+    collisions: Vec<Collision>, // From the last physics step, read by the next tick
+    checksum: u64,
+}
+
+fn get<T: Component + Clone>(world: &World, entity: Entity) -> Option<T> {
+    world.get::<T>(entity).cloned()
+}
+
+fn put<T: Component + Clone>(world: &mut World, entity: Entity, saved: &Option<T>) {
+    match saved {
+        Some(component) => {
+            world.entity_mut(entity).insert(component.clone());
+        }
+        None => {
+            world.entity_mut(entity).remove::<T>();
+        }
+    }
+}
+
+impl FighterSnapshot {
+    fn capture(world: &World, entity: Entity) -> Self {
+        FighterSnapshot {
+            entity,
+            transform: get(world, entity),
+            velocity: get(world, entity),
+            health: get(world, entity),
+            facing: get(world, entity),
+            cooldown: get(world, entity),
+            attack: get(world, entity),
+            block: get(world, entity),
+            grounded: get(world, entity),
+            combo: get(world, entity),
+            meter: get(world, entity),
+            status: get(world, entity),
+            input_buffer: get(world, entity),
+            dash: get(world, entity),
+            hitstun: get(world, entity),
+            blockstun: get(world, entity),
+            knockdown: get(world, entity),
+            thrown: get(world, entity),
+            juggle: get(world, entity),
+            landing: get(world, entity),
+            invincible: get(world, entity),
+            blackboard: get(world, entity),
+            phase: get(world, entity),
+            phase_transition: get(world, entity),
+        }
+    }
+
+    fn restore(&self, world: &mut World) {
+        let entity = self.entity;
+        if world.get_entity(entity).is_none() {
+            return;
+        }
+        put(world, entity, &self.transform);
+        put(world, entity, &self.velocity);
+        put(world, entity, &self.health);
+        put(world, entity, &self.facing);
+        put(world, entity, &self.cooldown);
+        put(world, entity, &self.attack);
+        put(world, entity, &self.block);
+        put(world, entity, &self.grounded);
+        put(world, entity, &self.combo);
+        put(world, entity, &self.meter);
+        put(world, entity, &self.status);
+        put(world, entity, &self.dash);
+        put(world, entity, &self.hitstun);
+        put(world, entity, &self.blockstun);
+        put(world, entity, &self.knockdown);
+        put(world, entity, &self.thrown);
+        put(world, entity, &self.juggle);
+        put(world, entity, &self.landing);
+        put(world, entity, &self.invincible);
+        put(world, entity, &self.blackboard);
+        put(world, entity, &self.phase);
+        put(world, entity, &self.phase_transition);
+        if let (Some(saved), Some(mut buffer)) =
+            (&self.input_buffer, world.get_mut::<InputBuffer>(entity))
+        {
+            buffer.rollback_to(saved);
+        }
+        // Hurtboxes and the pushbox are rebuilt from the restored state next tick
+        world.entity_mut(entity).insert(AppliedBoxLayout::default());
+    }
+
+    fn hash_into(&self, hasher: &mut DefaultHasher) {
+        if let Some(transform) = &self.transform {
+            transform.translation.x.to_bits().hash(hasher);
+            transform.translation.y.to_bits().hash(hasher);
+        }
+        if let Some(velocity) = &self.velocity {
+            velocity.x.to_bits().hash(hasher);
+            velocity.y.to_bits().hash(hasher);
+        }
+        self.health
+            .as_ref()
+            .map(|health| health.current)
+            .hash(hasher);
+        self.facing
+            .as_ref()
+            .map(|facing| matches!(facing, FacingDirection::Right))
+            .hash(hasher);
+        if let Some(attack) = self
+            .attack
+            .as_ref()
+            .and_then(|attack| attack.current.as_ref())
+        {
+            (attack.attack_type as u8).hash(hasher);
+            attack.elapsed.hash(hasher);
+        }
+        if let Some(block) = &self.block {
+            block.is_blocking.hash(hasher);
+            block.guard.to_bits().hash(hasher);
+        }
+        self.grounded
+            .as_ref()
+            .map(|grounded| grounded.0)
+            .hash(hasher);
+        if let Some(combo) = &self.combo {
+            combo.hits.hash(hasher);
+            combo.total_damage.hash(hasher);
+        }
+        self.meter
+            .as_ref()
+            .map(|meter| meter.current.to_bits())
+            .hash(hasher);
+        self.phase.as_ref().map(|phase| phase.0).hash(hasher);
+        if let Some(board) = &self.blackboard {
+            board.move_direction.to_bits().hash(hasher);
+            board.attack.map(|attack| attack as u8).hash(hasher);
+            board.jump.hash(hasher);
+        }
+        if let Some(status) = &self.status {
+            for effect in &status.effects {
+                (effect.kind as u8).hash(hasher);
+                effect.stacks.hash(hasher);
+                effect.timer.elapsed().hash(hasher);
+            }
+        }
+        for timer in [
+            self.dash.as_ref().map(|dash| &dash.timer),
+            self.hitstun.as_ref().map(|stun| &stun.timer),
+            self.blockstun.as_ref().map(|stun| &stun.timer),
+            self.knockdown.as_ref().map(|knockdown| &knockdown.timer),
+            self.thrown.as_ref().map(|thrown| &thrown.timer),
+            self.juggle.as_ref().map(|juggle| &juggle.takeoff),
+            self.landing.as_ref().map(|landing| &landing.timer),
+            self.invincible.as_ref().map(|invincible| &invincible.timer),
+            self.phase_transition
+                .as_ref()
+                .map(|transition| &transition.timer),
+        ] {
+            timer.map(|timer| timer.elapsed()).hash(hasher);
+        }
+    }
+}
+
+impl HitboxSnapshot {
+    fn capture(world: &World, entity: Entity) -> Option<Self> {
+        Some(HitboxSnapshot {
+            entity,
+            parent: world.get::<Parent>(entity).map(|parent| parent.get()),
+            transform: get(world, entity)?,
+            velocity: get(world, entity),
+            sprite: get(world, entity),
+            collider: get(world, entity),
+            hitbox: get(world, entity)?,
+            duration: get(world, entity),
+            projectile: get(world, entity),
+            grab: world.get::<GrabBox>(entity).is_some(),
+            animated: get(world, entity),
+        })
+    }
+
+    // Puts the hitbox back as it was, respawning it if it has since been despawned
+    fn restore(&self, world: &mut World) -> Entity {
+        if world.get::<Hitbox>(self.entity).is_some() {
+            put(world, self.entity, &Some(self.transform));
+            put(world, self.entity, &Some(self.hitbox.clone()));
+            put(world, self.entity, &self.duration);
+            put(world, self.entity, &self.collider);
+            if self.velocity.is_some() {
+                put(world, self.entity, &self.velocity);
+            }
+            if self.animated.is_some() {
+                put(world, self.entity, &self.animated);
+            }
+            return self.entity;
+        }
+
+        // The same bundles `spawn_hitbox` uses
+        let mut hitbox = world.spawn((
+            SpatialBundle::from_transform(self.transform),
+            Sensor,
+            GameLayer::hitbox(),
+            self.hitbox.clone(),
+        ));
+        if let Some(collider) = &self.collider {
+            hitbox.insert(collider.clone());
+        }
+        if let Some(duration) = &self.duration {
+            hitbox.insert(duration.clone());
+        }
+        if let Some(sprite) = &self.sprite {
+            hitbox.insert((sprite.clone(), Handle::<Image>::default()));
+        }
+        if let Some(velocity) = self.velocity {
+            hitbox.insert((RigidBody::Kinematic, velocity));
+        }
+        if let Some(projectile) = &self.projectile {
+            hitbox.insert(projectile.clone());
+        }
+        if self.grab {
+            hitbox.insert(GrabBox);
+        }
+        if let Some(animated) = &self.animated {
+            hitbox.insert(animated.clone());
+        }
+        let entity = hitbox.id();
+        if let Some(parent) = self
+            .parent
+            .filter(|&parent| world.get_entity(parent).is_some())
+        {
+            world.entity_mut(parent).add_child(entity);
+        }
+        entity
+    }
+}
+
+impl Snapshot {
+    fn capture(world: &mut World, frame: u32, collisions: Vec<Collision>) -> Self {
+        let fighter_entities: Vec<Entity> = world
+            .query_filtered::<Entity, With<Player>>()
+            .iter(world)
+            .collect();
+        let hitbox_entities: Vec<Entity> = world
+            .query_filtered::<Entity, With<Hitbox>>()
+            .iter(world)
+            .collect();
+
+        let mut snapshot = Snapshot {
+            frame,
+            time: *world.resource::<Time<Fixed>>(),
+            rng: world.resource::<GameRng>().clone(),
+            habits: world.resource::<HabitProfile>().clone(),
+            tracker: world.get_resource::<FightTracker>().cloned(),
+            fighters: fighter_entities
+                .into_iter()
+                .map(|entity| FighterSnapshot::capture(world, entity))
+                .collect(),
+            hitboxes: hitbox_entities
+                .into_iter()
+                .filter_map(|entity| HitboxSnapshot::capture(world, entity))
+                .collect(),
+            collisions,
+            checksum: 0,
+        };
+        snapshot.checksum = snapshot.checksum();
+        snapshot
+    }
+
+    fn checksum(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.frame.hash(&mut hasher);
+        self.rng.hash(&mut hasher);
+        self.habits.total_attacks().hash(&mut hasher);
+        self.habits.jumps.hash(&mut hasher);
+        self.habits.hit_recoveries.hash(&mut hasher);
+        self.habits.blocks_after_hit.hash(&mut hasher);
+        if let Some(tracker) = &self.tracker {
+            tracker.best_combo.hash(&mut hasher);
+            tracker.counter_hits.hash(&mut hasher);
+        }
+        let mut fighters: Vec<&FighterSnapshot> = self.fighters.iter().collect();
+        fighters.sort_by_key(|fighter| fighter.entity);
+        for fighter in fighters {
+            fighter.hash_into(&mut hasher);
+        }
+        // Respawned hitboxes get new entities, so these are hashed in a stable order instead
+        let mut hitboxes: Vec<(u32, u32, i32, u128)> = self
+            .hitboxes
+            .iter()
+            .map(|saved| {
+                (
+                    saved.transform.translation.x.to_bits(),
+                    saved.transform.translation.y.to_bits(),
+                    saved.hitbox.damage,
+                    saved
+                        .duration
+                        .as_ref()
+                        .map(|duration| duration.timer.elapsed().as_nanos())
+                        .unwrap_or(0),
+                )
+            })
+            .collect();
+        hitboxes.sort();
+        hitboxes.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn restore(&self, world: &mut World) {
+        world.resource_mut::<FrameCounter>().0 = self.frame;
+        *world.resource_mut::<GameRng>() = self.rng.clone();
+        *world.resource_mut::<HabitProfile>() = self.habits.clone();
+        if let Some(tracker) = &self.tracker {
+            // The outcome is only decided on a confirmed tick, so it's never taken back
+            let decided = world
+                .get_resource::<FightTracker>()
+                .is_some_and(|current| current.decided);
+            world.insert_resource(FightTracker {
+                decided,
+                ..tracker.clone()
+            });
+        }
+
+        for fighter in &self.fighters {
+            fighter.restore(world);
+        }
+
+        // Hitboxes spawned since are dropped; ones despawned since come back as new entities
+        let saved: Vec<Entity> = self.hitboxes.iter().map(|saved| saved.entity).collect();
+        let current: Vec<Entity> = world
+            .query_filtered::<Entity, With<Hitbox>>()
+            .iter(world)
+            .collect();
+        for entity in current {
+            if !saved.contains(&entity) {
+                world.entity_mut(entity).despawn_recursive();
+            }
+        }
+        let mut respawned = HashMap::new();
+        for hitbox in &self.hitboxes {
+            let entity = hitbox.restore(world);
+            if entity != hitbox.entity {
+                respawned.insert(hitbox.entity, entity);
+            }
+        }
+
+        // Gameplay events are handled within the tick that sends them, so any left over are
+        // from the abandoned future. Collisions are read a tick late, so those are replayed;
+        // the snapshot reader picks them up again for the next snapshot.
+        clear_tick_events(world);
+        let mut collision_events = world.resource_mut::<Events<Collision>>();
+        collision_events.clear();
+        for collision in &self.collisions {
+            let mut collision = collision.clone();
+            let contacts = &mut collision.0;
+            contacts.entity1 = *respawned
+                .get(&contacts.entity1)
+                .unwrap_or(&contacts.entity1);
+            contacts.entity2 = *respawned
+                .get(&contacts.entity2)
+                .unwrap_or(&contacts.entity2);
+            collision_events.send(collision);
+        }
+    }
+}
+
+// -- Systems --
+
+fn start_session(mut session: ResMut<NetSession>, collision_events: Res<Events<Collision>>) {
+    if !session.rolls_back() {
+        return;
+    }
+
+    session.inputs = Default::default();
+    session.predictions.clear();
+    session.snapshots.clear();
+    session.checksums.clear();
+    session.peer_checksums.clear();
+    session.newest_frame = 0;
+    session.peer_ack = 0;
+    session.rollback_from = None;
+    session.waiting = false;
+    session.collisions.clear(&collision_events);
+
+    // Both sides start with the delay's worth of empty input already known
+    let delay = session.input_delay;
+    for slot in 0..2 {
+        for frame in 1..=delay {
+            session.inputs[slot].insert(frame, FrameInput::default());
+        }
+        session.confirmed[slot] = delay;
+    }
+}
+
+// Online sessions last one fight, or end when the lobby is left; afterwards the player's own
+// settings come back
+fn end_session(mut session: ResMut<NetSession>, mut config: ResMut<GameConfig>) {
+    if !session.is_online() {
+        return;
+    }
+    tracing::info!("Online session ended");
+    if session.connected {
+        session.send(&serde_json::json!({ "type": "bye" }));
+    }
+    if let Some(saved_config) = session.saved_config.take() {
+        *config = GameConfig::from_json(&saved_config);
+    }
+    *session = NetSession {
+        input_delay: session.input_delay,
+        ..default()
+    };
+}
+
+fn open_from_args(mut session: ResMut<NetSession>, mut next_state: ResMut<NextState<AppState>>) {
+    let opened = match session.launch.take() {
+        Some(Launch::Host(port)) => session.host(port),
+        Some(Launch::Join(peer)) => session.join(peer),
+        None => return,
+    };
+    if opened {
+        next_state.set(AppState::Lobby);
+    }
+}
+
+// The joiner knocks until the host answers
+fn say_hello(
+    session: Res<NetSession>,
+    real_time: Res<Time<Real>>,
+    mut since_hello: Local<Option<f32>>,
+) {
+    if session.mode != NetMode::Join || session.connected {
+        return;
+    }
+    let now = real_time.elapsed_seconds();
+    if since_hello.is_some_and(|sent_at| now - sent_at < HELLO_INTERVAL_SECONDS) {
+        return;
+    }
+    *since_hello = Some(now);
+    session.send(&serde_json::json!({ "type": "hello" }));
+}
+
+// Packets are JSON objects tagged by "type": `hello`/`welcome` to join, `lobby` picks,
+// `ping`/`pong`, `start`, `input` and `bye`
+fn receive_packets(
+    mut session: ResMut<NetSession>,
+    mut config: ResMut<GameConfig>,
+    app_state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    real_time: Res<Time<Real>>,
+) {
+    let in_lobby = *app_state.get() == AppState::Lobby;
+    for (from, packet) in session.receive() {
+        match packet.get("type").and_then(|kind| kind.as_str()) {
+            // The host takes the first peer to knock while it's in the lobby
+            Some("hello") if session.mode == NetMode::Host => {
+                if session.peer.is_some_and(|peer| peer != from) {
+                    continue;
+                }
+                if !session.connected {
+                    if !in_lobby {
+                        continue;
+                    }
+                    session.peer = Some(from);
+                    session.connected = true;
+                    tracing::info!("Peer {} joined the lobby", from);
+                }
+                session.send(&serde_json::json!({ "type": "welcome" }));
+            }
+            Some("welcome")
+                if session.mode == NetMode::Join
+                    && !session.connected
+                    && session.peer == Some(from) =>
+            {
+                session.connected = true;
+                tracing::info!("Connected to {}", from);
+            }
+            _ if !session.connected || session.peer != Some(from) => continue,
+            Some("lobby") => {
+                let character = packet
+                    .get("character")
+                    .and_then(|character| character.as_str())
+                    .and_then(CharacterType::from_name);
+                let ready = packet.get("ready").and_then(|ready| ready.as_bool());
+                if let (Some(character), Some(ready)) = (character, ready) {
+                    session.peer_lobby = Some(LobbyChoice { character, ready });
+                }
+                // Still in the lobby, so the joiner missed the start
+                if let Some(start) = &session.start {
+                    session.send(start);
+                }
+            }
+            Some("ping") => {
+                if let Some(time) = packet.get("time") {
+                    session.send(&serde_json::json!({ "type": "pong", "time": time }));
+                }
+            }
+            Some("pong") => {
+                if let Some(time) = packet.get("time").and_then(|time| time.as_f64()) {
+                    session.ping = Some((real_time.elapsed_seconds() - time as f32).max(0.0));
+                }
+            }
+            Some("bye") if in_lobby => {
+                tracing::info!("Peer {} left the lobby", from);
+                session.drop_peer();
+            }
+            // Mid-fight, a peer that leaves ends the fight
+            Some("bye") => {
+                tracing::info!("Peer {} left", from);
+                session.connected = false;
+            }
+            // The host has settled the fight's settings and seed
+            Some("start") if session.mode == NetMode::Join && in_lobby => {
+                let Some(host_config) = packet.get("config") else {
+                    continue;
+                };
+                session.saved_config = Some(config.to_json());
+                *config = GameConfig::from_json(host_config);
+                config.player2_is_human = true;
+                if let Some(delay) = packet.get("input_delay").and_then(|delay| delay.as_u64()) {
+                    session.input_delay = delay as u32;
+                }
+                tracing::info!("Host started the fight");
+                next_state.set(AppState::InGame);
+            }
+            Some("input") => {
+                let first = packet.get("first").and_then(|first| first.as_u64());
+                let inputs = packet.get("inputs").and_then(|inputs| inputs.as_array());
+                let (Some(first), Some(inputs)) = (first, inputs) else {
+                    continue;
+                };
+                let inputs: Vec<FrameInput> = inputs
+                    .iter()
+                    .filter_map(|input| {
+                        let input = input.as_array()?;
+                        Some(FrameInput {
+                            held: input.first()?.as_u64()? as u16,
+                            pressed: input.get(1)?.as_u64()? as u16,
+                        })
+                    })
+                    .collect();
+                session.receive_inputs(first as u32, &inputs);
+
+                if let Some(ack) = packet.get("ack").and_then(|ack| ack.as_u64()) {
+                    session.peer_ack = session.peer_ack.max(ack as u32);
+                }
+                let checksum = packet.get("checksum").and_then(|checksum| {
+                    let checksum = checksum.as_array()?;
+                    Some((checksum.first()?.as_u64()?, checksum.get(1)?.as_u64()?))
+                });
+                if let Some((frame, checksum)) = checksum {
+                    session.peer_checksums.insert(frame as u32, checksum);
+                    session.compare_checksums(frame as u32);
+                }
+            }
+            _ => {}
+        }
+        if session.peer == Some(from) {
+            session.last_heard = real_time.elapsed_seconds();
+        }
+    }
+}
+
+// While in the lobby, both sides keep telling each other their picks and measuring the ping
+fn update_lobby(
+    mut session: ResMut<NetSession>,
+    real_time: Res<Time<Real>>,
+    mut since_update: Local<Option<f32>>,
+    mut since_ping: Local<Option<f32>>,
+) {
+    if !session.connected {
+        return;
+    }
+    let now = real_time.elapsed_seconds();
+    if now - session.last_heard > PEER_TIMEOUT_SECONDS {
+        tracing::warn!("Lost contact with the peer");
+        session.drop_peer();
+        return;
+    }
+
+    if !since_update.is_some_and(|sent_at| now - sent_at < LOBBY_INTERVAL_SECONDS) {
+        *since_update = Some(now);
+        session.send(&serde_json::json!({
+            "type": "lobby",
+            "character": session.lobby.character.name(),
+            "ready": session.lobby.ready,
+        }));
+    }
+    if !since_ping.is_some_and(|sent_at| now - sent_at < PING_INTERVAL_SECONDS) {
+        *since_ping = Some(now);
+        session.send(&serde_json::json!({ "type": "ping", "time": now }));
+    }
+}
+
+// A peer that's gone won't send the buttons the fight waits on, so the fight is called off
+fn leave_on_disconnect(
+    session: Res<NetSession>,
+    real_time: Res<Time<Real>>,
+    mut notice: ResMut<DisconnectNotice>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let silent_for = real_time.elapsed_seconds() - session.last_heard;
+    if session.connected && silent_for <= PEER_TIMEOUT_SECONDS {
+        return;
+    }
+    if notice.0.is_none() {
+        tracing::warn!("Lost the connection to the peer, ending the fight");
+        notice.0 = Some("Connection to the other player was lost".to_string());
+    }
+    next_state.set(AppState::MainMenu);
+}
+
+// Once both sides are ready, the host settles the fight's settings and seed and starts it
+fn start_when_ready(
+    mut session: ResMut<NetSession>,
+    mut config: ResMut<GameConfig>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if session.mode != NetMode::Host || !session.connected || !session.lobby.ready {
+        return;
+    }
+    let Some(peer) = session.peer_lobby.filter(|peer| peer.ready) else {
+        return;
+    };
+
+    session.saved_config = Some(config.to_json());
+    config.player2_is_human = true;
+    config.seed = Some(rand::random::<u64>());
+    config.player1_character = session.lobby.character;
+    config.player2_character = peer.character;
+    let start = serde_json::json!({
+        "type": "start",
+        "config": config.to_json(),
+        "input_delay": session.input_delay,
+    });
+    session.send(&start);
+    session.start = Some(start);
+    tracing::info!("Both sides ready, starting the fight");
+    next_state.set(AppState::InGame);
+}
+
+// Sends every one of our buttons the peer hasn't acknowledged yet, every frame, so lost
+// packets are made up for by the next one
+fn send_inputs(session: Res<NetSession>) {
+    if !session.connected {
+        return;
+    }
+    let slot = player_slot(session.local_player);
+    let first = session.peer_ack + 1;
+    let last = session.confirmed[slot].min(first + MAX_INPUTS_PER_PACKET - 1);
+    let inputs: Vec<serde_json::Value> = (first..=last)
+        .map(|frame| {
+            let input = session.inputs[slot]
+                .get(&frame)
+                .copied()
+                .unwrap_or_default();
+            serde_json::json!([input.held, input.pressed])
+        })
+        .collect();
+    let checksum = session
+        .checksums
+        .iter()
+        .next_back()
+        .map(|(frame, checksum)| serde_json::json!([frame, checksum]));
+
+    session.send(&serde_json::json!({
+        "type": "input",
+        "first": first,
+        "inputs": inputs,
+        "ack": session.peer_confirmed(),
+        "checksum": checksum,
+    }));
+}
+
+// On a new tick, stores the local buttons for the frame they take effect on. Resimulated
+// ticks reuse the buttons already stored for them.
+fn collect_local_inputs(
+    frames: Res<FrameCounter>,
+    mut session: ResMut<NetSession>,
+    mut query: Query<(&Player, &ControlType, &mut InputBuffer)>,
+) {
+    if frames.0 <= session.newest_frame {
+        return;
+    }
+    session.newest_frame = frames.0;
+
+    for (player, control, mut buffer) in query.iter_mut() {
+        if !matches!(control, ControlType::Human) || session.keyboard_layout(player.id).is_none() {
+            continue;
+        }
+        let frame = frames.0 + session.input_delay;
+        let slot = player_slot(player.id);
+        let input = buffer.take_keyboard_input();
+        session.inputs[slot].insert(frame, input);
+        session.confirmed[slot] = frame;
+    }
+}
+
+fn save_snapshot(world: &mut World) {
+    let frame = world.resource::<FrameCounter>().0;
+    world.resource_scope(|world, mut session: Mut<NetSession>| {
+        let collisions: Vec<Collision> = session
+            .collisions
+            .read(world.resource::<Events<Collision>>())
+            .cloned()
+            .collect();
+        let snapshot = Snapshot::capture(world, frame, collisions);
+        session.store_snapshot(snapshot);
+    });
+}
+
+// Rewinds to the last snapshot before a wrong guess and replays every tick since with the
+// buttons now known, all before this frame's regular ticks run. Sync test does this every
+// frame, which replays ticks with the same buttons and so must reach the same checksums.
+fn roll_back(world: &mut World) {
+    let mut session = world.resource_mut::<NetSession>();
+    let newest = session.newest_frame;
+    if let NetMode::SyncTest { frames } = session.mode {
+        if newest > frames {
+            session.rollback_from = Some(newest - frames + 1);
+        }
+    }
+    let Some(from) = session.rollback_from.take().filter(|&from| from <= newest) else {
+        return;
+    };
+    let Some(snapshot) = session
+        .snapshots
+        .iter()
+        .find(|snapshot| snapshot.frame == from - 1)
+        .cloned()
+    else {
+        tracing::warn!(
+            "No snapshot to roll back to frame {}; expect a desync",
+            from
+        );
+        return;
+    };
+
+    snapshot.restore(world);
+
+    // Ticks never run during a freeze, so neither the one under way nor one a replayed tick
+    // starts may switch gameplay systems off for the ticks after it
+    let freeze = std::mem::take(&mut *world.resource_mut::<SuperFreeze>());
+    let hitstop = std::mem::take(&mut *world.resource_mut::<Hitstop>());
+    let paused = world.resource::<Time<Virtual>>().is_paused();
+
+    // Same as the regular fixed loop, but on the restored clock
+    let mut time = snapshot.time;
+    for tick in 0..newest - snapshot.frame {
+        if tick > 0 {
+            time.advance_by(time.timestep());
+        }
+        *world.resource_mut::<Time>() = time.as_generic();
+        world.run_schedule(FixedMain);
+        *world.resource_mut::<SuperFreeze>() = SuperFreeze::default();
+        *world.resource_mut::<Hitstop>() = Hitstop::default();
+    }
+    // The replayed ticks were already shown once; `clear` leaves `Update` readers' cursors
+    // alone, so without this their sounds, particles, shakes and popups would play again
+    clear_tick_events(world);
+
+    *world.resource_mut::<SuperFreeze>() = freeze;
+    *world.resource_mut::<Hitstop>() = hitstop;
+    if !paused {
+        world.resource_mut::<Time<Virtual>>().unpause();
+    }
+    *world.resource_mut::<Time>() = world.resource::<Time<Virtual>>().as_generic();
+}
+
+// Drops the events sent by gameplay ticks, apart from collisions
+fn clear_tick_events(world: &mut World) {
+    world.resource_mut::<Events<SpawnHitboxEvent>>().clear();
+    world.resource_mut::<Events<DamageEvent>>().clear();
+    world.resource_mut::<Events<ParryEvent>>().clear();
+    world.resource_mut::<Events<SuperEvent>>().clear();
+    world.resource_mut::<Events<CounterHitEvent>>().clear();
+    world.resource_mut::<Events<HitstunEndedEvent>>().clear();
+    world.resource_mut::<Events<PhaseChangeEvent>>().clear();
+}
+
+// Stops the clock while too far ahead of the peer's buttons, so rollbacks stay short
+fn wait_for_peer(
+    mut session: ResMut<NetSession>,
+    freeze: Res<SuperFreeze>,
+    hitstop: Res<Hitstop>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+) {
+    let ahead = session
+        .newest_frame
+        .saturating_sub(session.peer_confirmed());
+    if ahead >= MAX_PREDICTION_FRAMES {
+        if !session.waiting {
+            tracing::info!("Waiting for the peer ({} frames ahead)", ahead);
+            session.waiting = true;
+            virtual_time.pause();
+        }
+    } else if session.waiting {
+        session.waiting = false;
+        // A freeze that started meanwhile keeps the clock stopped
+        if not_in_super_freeze(freeze) && not_in_hitstop(hitstop) {
+            virtual_time.unpause();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::asset::LoadState;
+    use bevy::state::app::StatesPlugin;
+    use bevy::time::TimeUpdateStrategy;
+    use std::time::Duration;
+
+    use crate::ai::AiPlugin;
+    use crate::boss::BossPlugin;
+    use crate::boxes::BoxesPlugin;
+    use crate::combat::CombatPlugin;
+    use crate::game_state::{PlayerProgress, Winner};
+    use crate::habits::HabitsPlugin;
+    use crate::input::{InputButton, InputPlugin};
+    use crate::moves::{MovesPlugin, FRAMES_PER_SECOND};
+    use crate::player::PlayerPlugin;
+    use crate::replay::{Replay, ReplayPlugin, Replays, REPLAY_VERSION};
+    use crate::rng::RngPlugin;
+    use crate::status::StatusPlugin;
+    use crate::GameAssets;
+
+    const ROLLBACK_FRAMES: &str = "8";
+    const FIGHT_TICKS: u32 = 300;
+
+    // The fight's systems without a window, audio, UI or menus, running one tick per update
+    fn headless_fight() -> App {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            StatesPlugin,
+            AssetPlugin::default(),
+            TransformPlugin,
+            HierarchyPlugin,
+            PhysicsPlugins::new(FixedPostUpdate),
+        ))
+        .add_plugins((
+            RngPlugin,
+            MovesPlugin,
+            InputPlugin,
+            ReplayPlugin,
+            AiPlugin,
+            HabitsPlugin,
+            BoxesPlugin,
+            StatusPlugin,
+            PlayerPlugin,
+            CombatPlugin,
+            BossPlugin,
+        ))
+        .add_plugins(NetplayPlugin)
+        // Sprites and sounds are loaded but never drawn or played
+        .init_asset::<Image>()
+        .init_asset::<AudioSource>()
+        .init_resource::<ButtonInput<KeyCode>>()
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            1.0 / FRAMES_PER_SECOND as f64,
+        )))
+        .insert_resource(Time::new_with(Physics::fixed_once_hz(
+            FRAMES_PER_SECOND as f64,
+        )))
+        .init_state::<AppState>()
+        .insert_resource(Winner::default())
+        .insert_resource(GameConfig::default())
+        .insert_resource(PlayerProgress::default())
+        .add_systems(Startup, crate::setup_assets)
+        .add_systems(
+            OnEnter(AppState::InGame),
+            (crate::cleanup_old_arenas, crate::setup),
+        );
+        app
+    }
+
+    // Player 1 walks in, attacks with every button and jumps now and then
+    fn scripted_inputs() -> Vec<[FrameInput; 2]> {
+        let buttons = [
+            (12, InputButton::Light),
+            (30, InputButton::Heavy),
+            (45, InputButton::Kick),
+            (70, InputButton::Up),
+            (90, InputButton::Down),
+        ];
+        (0..FIGHT_TICKS)
+            .map(|tick| {
+                let mut input = FrameInput {
+                    held: InputButton::Right.bit(),
+                    pressed: 0,
+                };
+                for (every, button) in buttons {
+                    if tick % every == every - 1 {
+                        input.held |= button.bit();
+                        input.pressed |= button.bit();
+                    }
+                }
+                [input, FrameInput::default()]
+            })
+            .collect()
+    }
+
+    // Move data and boss trees must not finish loading mid-fight, or replayed ticks would
+    // see different data from the first time through
+    fn wait_for_fight_data(app: &mut App) {
+        for _ in 0..500 {
+            app.update();
+            let assets = app.world().resource::<GameAssets>();
+            let server = app.world().resource::<AssetServer>();
+            let settled = |state| matches!(state, Some(LoadState::Loaded | LoadState::Failed(_)));
+            if settled(server.get_load_state(&assets.move_data))
+                && settled(server.get_load_state(&assets.boss_ai))
+            {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("fight data never finished loading");
+    }
+
+    // Starts the scripted fight, rolling back every frame when `args` asks for a synctest
+    fn start_scripted_fight(args: &[String]) -> App {
+        let mut app = headless_fight();
+        app.insert_resource(NetSession::from_args(args));
+        wait_for_fight_data(&mut app);
+
+        let replay = Replay {
+            version: REPLAY_VERSION.to_string(),
+            config: GameConfig::default().to_json(),
+            seed: 0x5EED,
+            frames: scripted_inputs(),
+        };
+        app.world_mut()
+            .resource_scope(|world, mut replays: Mut<Replays>| {
+                replays.start_playback(replay, &mut world.resource_mut::<GameConfig>());
+            });
+        app.world_mut()
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::InGame);
+        app
+    }
+
+    fn synctest_args() -> [String; 2] {
+        ["--synctest".to_string(), ROLLBACK_FRAMES.to_string()]
+    }
+
+    #[test]
+    fn rollbacks_replay_ticks_exactly() {
+        let mut app = start_scripted_fight(&synctest_args());
+        let mut compared = 0;
+        for _ in 0..FIGHT_TICKS {
+            app.update();
+            // Each frame's latest snapshot is from its most recent replay; it must match the
+            // checksum from the first time through
+            let session = app.world().resource::<NetSession>();
+            for snapshot in &session.snapshots {
+                if let Some(&first) = session.checksums.get(&snapshot.frame) {
+                    assert_eq!(
+                        snapshot.checksum, first,
+                        "desync at frame {} after rolling back",
+                        snapshot.frame
+                    );
+                    compared += 1;
+                }
+            }
+        }
+
+        let session = app.world().resource::<NetSession>();
+        assert!(
+            session.newest_frame > FIGHT_TICKS / 2,
+            "the fight barely ran"
+        );
+        assert!(compared > 0, "no rolled back frames were checked");
+    }
+
+    // Attacks as heard by the attack sound in `Update`
+    #[derive(Resource, Default)]
+    struct AttacksShown(u32);
+
+    fn count_attacks_shown(
+        mut spawn_reader: EventReader<SpawnHitboxEvent>,
+        mut shown: ResMut<AttacksShown>,
+    ) {
+        shown.0 += spawn_reader.read().count() as u32;
+    }
+
+    #[test]
+    fn rollbacks_dont_repeat_feedback() {
+        let attacks_shown = |args: &[String]| {
+            let mut app = start_scripted_fight(args);
+            app.init_resource::<AttacksShown>()
+                .add_systems(Update, count_attacks_shown);
+            for _ in 0..FIGHT_TICKS {
+                app.update();
+            }
+            app.world().resource::<AttacksShown>().0
+        };
+        let offline = attacks_shown(&[]);
+        assert!(offline > 0, "the scripted fight threw no attacks");
+        assert_eq!(attacks_shown(&synctest_args()), offline);
+    }
+}
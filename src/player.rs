@@ -2,30 +2,44 @@ use bevy::prelude::*;
 use bevy_xpbd_2d::prelude::*;
 use std::time::Duration;
 
-use crate::combat;
-use crate::combat::SpawnHitboxEvent;
+use crate::ai::{run_behavior_trees, Blackboard};
+use crate::boss::CurrentPhase;
+use crate::boxes::{spawn_hurtboxes, GameLayer};
+use crate::combat::{
+    not_in_hitstop, not_in_super_freeze, Blockstun, Hitstun, HurtboxDisabled, NotStunned,
+    SpawnHitboxEvent, SuperEvent, SuperMeter,
+};
 use crate::game_state::{AppState, BossType, Difficulty, GameConfig};
-use crate::GameAssets;
+use crate::input::{DashDirection, InputBuffer, InputButton};
+use crate::moves::{frames_to_duration, MoveData, MoveLibrary, FRAMES_PER_SECOND};
+use crate::{AnimationState, GameAssets};
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
-            Update,
+            FixedUpdate,
             (
-                update_ai_state,
-                player_movement.after(update_ai_state),
-                player_jump.after(player_movement),
+                player_movement.after(run_behavior_trees),
+                update_dashes.after(player_movement),
+                player_jump.after(player_movement).after(run_behavior_trees),
                 play_jump_sound.after(player_jump),
                 update_grounded.after(play_jump_sound),
                 update_attack_cooldowns,
-                player_attack.after(update_attack_cooldowns),
-                player_block.after(update_attack_cooldowns),
+                update_attack_state.after(update_attack_cooldowns),
+                player_attack
+                    .after(update_attack_state)
+                    .after(run_behavior_trees),
+                player_block
+                    .after(update_attack_state)
+                    .after(run_behavior_trees),
                 update_block_state.after(player_block),
                 update_player_facing_direction,
             )
-                .run_if(in_state(AppState::InGame)),
+                .run_if(in_state(AppState::InGame))
+                .run_if(not_in_super_freeze)
+                .run_if(not_in_hitstop),
         )
         .add_systems(OnExit(AppState::InGame), cleanup_game_entities);
     }
@@ -48,6 +62,42 @@ impl Difficulty {
         }
     }
 
+    // Chance that an AI fighter tech-rolls out of a knockdown
+    pub fn tech_chance(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.2,
+            Difficulty::Normal => 0.4,
+            Difficulty::Hard => 0.7,
+        }
+    }
+
+    // Frames before an AI fighter reacts to an incoming attack or a whiff
+    pub fn reaction_frames(&self) -> u32 {
+        match self {
+            Difficulty::Easy => 12,
+            Difficulty::Normal => 6,
+            Difficulty::Hard => 3,
+        }
+    }
+
+    // Scales how often bosses act on their block, parry and punish tendencies
+    pub fn defense_skill(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.5,
+            Difficulty::Normal => 0.8,
+            Difficulty::Hard => 1.0,
+        }
+    }
+
+    // Chance that an AI fighter blocks at the right height
+    pub fn stance_accuracy(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.6,
+            Difficulty::Normal => 0.85,
+            Difficulty::Hard => 1.0,
+        }
+    }
+
     #[allow(dead_code)]
     pub fn health_multiplier(&self) -> f32 {
         match self {
@@ -71,14 +121,14 @@ pub enum ControlType {
     AI(BossType),
 }
 
-#[derive(Component, Default)]
+#[derive(Component, Clone, Default)]
 pub enum FacingDirection {
     #[default]
     Right,
     Left,
 }
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct Health {
     pub current: i32,
     pub max: i32,
@@ -87,182 +137,270 @@ pub struct Health {
 #[derive(Component)]
 pub struct MoveSpeed(pub f32);
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct AttackCooldown {
     pub timer: Timer,
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum AttackType {
     Light,
     Heavy,
     Kick,
+    Projectile, // Travels across the arena instead of staying attached
+    Super,      // Spends a full super meter
+    Throw,      // Close-range grab that ignores blocking
+    AirLight,   // Aerial variants, used when attacking while airborne
+    AirHeavy,
+    AirKick,
 }
 
-#[derive(Component)]
-pub struct BlockState {
-    pub is_blocking: bool,
-    pub block_timer: Timer,
-    pub cooldown_timer: Timer,
+impl AttackType {
+    // The aerial variant used when this attack is started in the air
+    pub fn aerial(self) -> Self {
+        match self {
+            AttackType::Light => AttackType::AirLight,
+            AttackType::Heavy => AttackType::AirHeavy,
+            AttackType::Kick => AttackType::AirKick,
+            other => other,
+        }
+    }
+
+    pub fn is_aerial(self) -> bool {
+        matches!(
+            self,
+            AttackType::AirLight | AttackType::AirHeavy | AttackType::AirKick
+        )
+    }
+
+    // The ground attack an aerial shares its animation with
+    pub fn ground_variant(self) -> Self {
+        match self {
+            AttackType::AirLight => AttackType::Light,
+            AttackType::AirHeavy => AttackType::Heavy,
+            AttackType::AirKick => AttackType::Kick,
+            other => other,
+        }
+    }
 }
 
-#[derive(Component)]
-pub struct Grounded(pub bool);
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AttackPhase {
+    Startup,
+    Active,
+    Recovery,
+}
 
+// Attack state machine - the hitbox only exists during the active window
 #[derive(Component, Clone, Default)]
-pub enum AIState {
-    #[default]
-    Aggressive,
-    Defensive,
-    Erratic,
+pub struct AttackState {
+    pub current: Option<AttackInProgress>,
 }
 
-// -- Systems --
+#[derive(Clone)]
+pub struct AttackInProgress {
+    pub attack_type: AttackType,
+    pub phase: AttackPhase,
+    pub elapsed: Duration,
+    pub startup_frames: u32,
+    pub active_frames: u32,
+    pub recovery_frames: u32,
+}
+
+impl AttackState {
+    pub fn start(&mut self, attack_type: AttackType, move_data: &MoveData) {
+        self.current = Some(AttackInProgress {
+            attack_type,
+            phase: AttackPhase::Startup,
+            elapsed: Duration::ZERO,
+            startup_frames: move_data.startup_frames,
+            active_frames: move_data.active_frames,
+            recovery_frames: move_data.recovery_frames,
+        });
+    }
+
+    pub fn is_attacking(&self) -> bool {
+        self.current.is_some()
+    }
 
-fn update_ai_state(time: Res<Time>, mut query: Query<(&Health, &mut AIState), With<ControlType>>) {
-    for (health, mut state) in query.iter_mut() {
-        let health_ratio = health.current as f32 / health.max as f32;
+    pub fn current_attack(&self) -> Option<AttackType> {
+        self.current.as_ref().map(|attack| attack.attack_type)
+    }
+}
 
-        // Change state based on health and time
-        if health_ratio < 0.3 {
-            *state = AIState::Defensive; // Low health: be defensive
-        } else if (time.elapsed_seconds() as i32 % 10) < 3 {
-            *state = AIState::Erratic; // Every 10 seconds, 3 seconds of erratic
+impl AttackInProgress {
+    pub fn frame(&self) -> u32 {
+        (self.elapsed.as_secs_f32() * FRAMES_PER_SECOND) as u32
+    }
+
+    // Phase for the current frame, or None once recovery has finished
+    fn phase_for_current_frame(&self) -> Option<AttackPhase> {
+        let frame = self.frame();
+        if frame < self.startup_frames {
+            Some(AttackPhase::Startup)
+        } else if frame < self.startup_frames + self.active_frames {
+            Some(AttackPhase::Active)
+        } else if frame < self.startup_frames + self.active_frames + self.recovery_frames {
+            Some(AttackPhase::Recovery)
         } else {
-            *state = AIState::Aggressive; // Default aggressive
+            None
         }
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AttackHeight {
+    High, // Blocked standing
+    Low,  // Blocked crouching
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BlockStance {
+    High,
+    Low,
+}
+
+pub const MAX_GUARD: f32 = 100.0;
+const GUARD_REGEN_PER_SECOND: f32 = 20.0;
+pub const GUARD_BREAK_STUN_FRAMES: u32 = 60;
+pub const PARRY_WINDOW_FRAMES: u32 = 6;
+pub const PARRY_STUN_FRAMES: u32 = 30;
+
+#[derive(Component, Clone)]
+pub struct BlockState {
+    pub is_blocking: bool,
+    pub stance: BlockStance,
+    pub block_timer: Timer, // Runs for the parry window after a block starts
+    pub guard: f32,
+}
+
+impl BlockState {
+    // Highs must be blocked standing and lows crouching
+    pub fn blocks(&self, height: AttackHeight) -> bool {
+        self.is_blocking
+            && matches!(
+                (self.stance, height),
+                (BlockStance::High, AttackHeight::High) | (BlockStance::Low, AttackHeight::Low)
+            )
+    }
+
+    // A correctly guarded hit in the first frames of a block is a parry
+    pub fn parries(&self, height: AttackHeight) -> bool {
+        self.blocks(height) && !self.block_timer.finished()
+    }
+}
+
+#[derive(Component, Clone)]
+pub struct Grounded(pub bool);
+
+const DASH_SPEED: f32 = 700.0;
+const DASH_FRAMES: u32 = 12;
+
+// A short burst of movement triggered by double-tapping a direction
+#[derive(Component, Clone)]
+pub struct Dash {
+    pub timer: Timer,
+    pub direction: f32, // -1.0 for left, 1.0 for right
+}
+
+// -- Systems --
+
 #[allow(clippy::type_complexity)]
 fn player_movement(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut commands: Commands,
     time: Res<Time>,
     config: Res<GameConfig>,
-    mut query: Query<(
-        &mut LinearVelocity,
-        &Player,
-        &MoveSpeed,
-        &ControlType,
-        &Transform,
-        Option<&AIState>,
-    )>,
-    player_transforms: Query<(&Transform, &Player, &ControlType)>,
+    mut query: Query<
+        (
+            Entity,
+            &mut LinearVelocity,
+            &MoveSpeed,
+            &ControlType,
+            &AttackState,
+            &BlockState,
+            &FacingDirection,
+            &mut InputBuffer,
+            Option<&Dash>,
+            Option<&Blackboard>,
+            Option<&CurrentPhase>,
+        ),
+        NotStunned,
+    >,
 ) {
-    let mut human_position = None;
-    for (transform, _player, control) in player_transforms.iter() {
-        if matches!(control, ControlType::Human) {
-            human_position = Some(transform.translation.x);
-            break;
+    for (
+        entity,
+        mut velocity,
+        move_speed,
+        control,
+        attack_state,
+        block_state,
+        facing,
+        mut input_buffer,
+        dash,
+        board,
+        phase,
+    ) in query.iter_mut()
+    {
+        // Aerial attacks keep the jump's momentum until landing
+        if attack_state
+            .current_attack()
+            .is_some_and(|attack_type| attack_type.is_aerial())
+        {
+            continue;
+        }
+
+        // Fighters are committed to their attack until recovery ends, and hold still while blocking
+        if attack_state.is_attacking() || block_state.is_blocking {
+            velocity.x = 0.0;
+            continue;
+        }
+
+        // Dashes override normal walking until they finish
+        if let Some(dash) = dash {
+            velocity.x = dash.direction * DASH_SPEED;
+            continue;
+        }
+        if let Some(dash_direction) = input_buffer.take_dash(time.elapsed_seconds()) {
+            let forward = match facing {
+                FacingDirection::Right => 1.0,
+                FacingDirection::Left => -1.0,
+            };
+            let direction = match dash_direction {
+                DashDirection::Forward => forward,
+                DashDirection::Back => -forward,
+            };
+            commands.entity(entity).insert(Dash {
+                timer: Timer::new(frames_to_duration(DASH_FRAMES), TimerMode::Once),
+                direction,
+            });
+            velocity.x = direction * DASH_SPEED;
+            continue;
         }
-    }
 
-    for (mut velocity, _player, move_speed, control, transform, ai_state) in query.iter_mut() {
         let mut direction = 0.0;
         match control {
             ControlType::Human => {
-                // Human player controls - different keys per player
-                match _player.id {
-                    1 => {
-                        if keyboard_input.pressed(KeyCode::KeyA) {
-                            direction -= 1.0;
-                        }
-                        if keyboard_input.pressed(KeyCode::KeyD) {
-                            direction += 1.0;
-                        }
-                    }
-                    2 => {
-                        if keyboard_input.pressed(KeyCode::ArrowLeft) {
-                            direction -= 1.0;
-                        }
-                        if keyboard_input.pressed(KeyCode::ArrowRight) {
-                            direction += 1.0;
-                        }
-                    }
-                    _ => {}
+                if input_buffer.current.held(InputButton::Left) {
+                    direction -= 1.0;
+                }
+                if input_buffer.current.held(InputButton::Right) {
+                    direction += 1.0;
                 }
             }
-            ControlType::AI(boss_type) => {
-                // AI movement logic
-                if let Some(human_x) = human_position {
-                    let distance = human_x - transform.translation.x;
-                    let abs_distance = distance.abs();
-
-                    let mut base_direction = match boss_type {
-                        BossType::NullPointer => {
-                            // Vanishes occasionally - erratic movement
-                            let random_time = time.elapsed_seconds() % 3.0;
-                            if random_time > 2.5 {
-                                rand::random::<f32>() * 4.0 - 2.0 // Random direction
-                            } else {
-                                // Normal movement towards player
-                                if distance > 0.0 {
-                                    1.0
-                                } else {
-                                    -1.0
-                                }
-                            }
-                        }
-                        BossType::UndefinedBehavior => {
-                            // Unpredictable erratic movement
-                            time.elapsed_seconds().sin() * 2.0 // Sinusoidal erratic movement
-                        }
-                        BossType::DataRace => {
-                            // Aggressive approach and retreat
-                            let time_phase = (time.elapsed_seconds() * 2.0).sin();
-                            let mut dir = if time_phase > 0.0 { 1.0 } else { -1.0 };
-                            if abs_distance < 100.0 {
-                                dir *= -1.0; // Retreat when close
-                            } else {
-                                // Move towards player when far
-                                dir = if distance > 0.0 { 1.0 } else { -1.0 };
-                            }
-                            dir
-                        }
-                        BossType::UseAfterFree => {
-                            // Steady aggressive approach
-                            if distance > 0.0 {
-                                1.0
-                            } else {
-                                -1.0
-                            }
-                        }
-                        BossType::BufferOverflow => {
-                            // Slow but steady towards player
-                            if distance > 0.0 {
-                                0.5
-                            } else {
-                                -0.5
-                            }
-                        }
-                    };
-
-                    // Apply AI state modifiers
-                    if let Some(ai_state) = ai_state {
-                        match ai_state {
-                            AIState::Aggressive => {
-                                // Boost towards player
-                                base_direction *= 1.5;
-                            }
-                            AIState::Defensive => {
-                                // Move away from player
-                                base_direction = if distance > 0.0 { -1.0 } else { 1.0 };
-                            }
-                            AIState::Erratic => {
-                                // Add randomness
-                                base_direction += rand::random::<f32>() * 2.0 - 1.0;
-                            }
-                        }
-                    }
-
-                    direction = base_direction;
+            ControlType::AI(_) => {
+                // Movement intent from the boss's behaviour tree
+                if let Some(board) = board {
+                    direction = board.move_direction;
                 }
             }
         }
-        let speed_mult = if matches!(control, ControlType::AI(_)) {
-            config.difficulty.speed_multiplier()
-        } else {
-            1.0
+        let speed_mult = match (control, phase) {
+            // Later boss phases move faster
+            (ControlType::AI(boss_type), Some(phase)) => {
+                config.difficulty.speed_multiplier() * phase.phase(*boss_type).speed_multiplier
+            }
+            (ControlType::AI(_), None) => config.difficulty.speed_multiplier(),
+            (ControlType::Human, _) => 1.0,
         };
         velocity.x = direction * move_speed.0 * speed_mult;
     }
@@ -274,191 +412,243 @@ fn update_attack_cooldowns(time: Res<Time>, mut query: Query<&mut AttackCooldown
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn player_attack(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
     time: Res<Time>,
-    config: Res<GameConfig>,
-    mut query: Query<(
-        Entity,
-        &Player,
-        &ControlType,
-        &Transform,
-        &mut AttackCooldown,
-    )>,
-    player_transforms: Query<(&Transform, &ControlType)>,
-    mut spawn_hitbox_writer: EventWriter<SpawnHitboxEvent>,
+    move_library: MoveLibrary,
+    mut query: Query<
+        (
+            Entity,
+            &ControlType,
+            &AnimationState,
+            &BlockState,
+            &mut AttackCooldown,
+            &mut AttackState,
+            &mut InputBuffer,
+            &mut SuperMeter,
+            &Grounded,
+            Option<&Blackboard>,
+        ),
+        NotStunned,
+    >,
+    mut super_writer: EventWriter<SuperEvent>,
 ) {
-    let mut human_position = None;
-    for (transform, control) in player_transforms.iter() {
-        if matches!(control, ControlType::Human) {
-            human_position = Some(transform.translation);
-            break;
-        }
-    }
-
-    for (entity, player, control, transform, mut cooldown) in query.iter_mut() {
-        // Only allow attacks if cooldown is finished
-        if !cooldown.timer.finished() {
+    for (
+        entity,
+        control,
+        animation_state,
+        block_state,
+        mut cooldown,
+        mut attack_state,
+        mut input_buffer,
+        mut super_meter,
+        grounded,
+        board,
+    ) in query.iter_mut()
+    {
+        // Only allow attacks if cooldown is finished, no attack is in progress and not blocking
+        if !cooldown.timer.finished() || attack_state.is_attacking() || block_state.is_blocking {
             continue;
         }
 
         // Determine attack type for human players
         let attack_type = match control {
             ControlType::Human => {
-                let (light_key, heavy_key, kick_key) = if player.id == 1 {
-                    (KeyCode::KeyF, KeyCode::KeyR, KeyCode::KeyT) // P1: F=Light, R=Heavy, T=Kick
-                } else {
-                    (KeyCode::KeyL, KeyCode::KeyO, KeyCode::KeyP) // P2: L=Light, O=Heavy, P=Kick
-                };
-
-                if keyboard_input.just_pressed(light_key) {
-                    Some(AttackType::Light)
-                } else if keyboard_input.just_pressed(heavy_key) {
-                    Some(AttackType::Heavy)
-                } else if keyboard_input.just_pressed(kick_key) {
-                    Some(AttackType::Kick)
-                } else {
-                    None
-                }
-            }
-            ControlType::AI(boss_type) => {
-                // AI attack logic - determine attack type based on boss behavior
-                if let Some(human_pos) = human_position {
-                    let distance = (human_pos - transform.translation).length();
-
-                    let should_attack = match boss_type {
-                        BossType::NullPointer => {
-                            // Sporadic attacks - prefers light attacks
-                            let phase = time.elapsed_seconds() % 4.0;
-                            let threshold = 3.5 * config.difficulty.attack_frequency_multiplier();
-                            distance < 150.0 && phase > threshold
-                        }
-                        BossType::UndefinedBehavior => {
-                            // Random attacks - mixed attack types
-                            distance < 200.0 && rand::random::<f32>() < 0.05
-                        }
-                        BossType::DataRace => {
-                            // Rapid attacks when close - prefers light attacks
-                            distance < 120.0 && (time.elapsed_seconds() * 3.0).fract() < 0.3
-                        }
-                        BossType::UseAfterFree => {
-                            // Steady attack intervals - prefers heavy attacks
-                            distance < 180.0 && (time.elapsed_seconds() % 2.0) < 0.2
-                        }
-                        BossType::BufferOverflow => {
-                            // Slow but powerful attacks - always heavy
-                            distance < 160.0 && (time.elapsed_seconds() % 5.0) < 0.5
-                        }
-                    };
-
-                    if should_attack {
-                        // Choose attack type based on boss personality
-                        Some(match boss_type {
-                            BossType::NullPointer => AttackType::Light, // Fast erratic attacks
-                            BossType::UndefinedBehavior => {
-                                // Random attack type
-                                match rand::random::<u32>() % 3 {
-                                    0 => AttackType::Light,
-                                    1 => AttackType::Heavy,
-                                    _ => AttackType::Kick,
-                                }
-                            }
-                            BossType::DataRace => AttackType::Light, // Fast rapid attacks
-                            BossType::UseAfterFree => AttackType::Heavy, // Powerful steady attacks
-                            BossType::BufferOverflow => AttackType::Heavy, // Slow powerful attacks
-                        })
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
+                // Presses (and completed motions) are recorded by the input buffer, so an
+                // attack pressed during recovery comes out on the first legal frame
+                input_buffer.take_attack(time.elapsed_seconds())
             }
+            // Attack intent from the boss's behaviour tree
+            ControlType::AI(_) => board.and_then(|board| board.attack),
         };
 
+        // Attacks started in the air use their aerial variants
+        let attack_type = attack_type.map(|attack_type| {
+            if grounded.0 {
+                attack_type
+            } else {
+                attack_type.aerial()
+            }
+        });
+
         if let Some(attack_type) = attack_type {
-            // Start cooldown timer (longer for heavy attacks)
-            let cooldown_duration = match attack_type {
-                AttackType::Light => Duration::from_millis(250), // Quick recovery
-                AttackType::Heavy => Duration::from_millis(400), // Longer recovery
-                AttackType::Kick => Duration::from_millis(300),  // Medium recovery
-            };
-            cooldown.timer.set_duration(cooldown_duration);
+            // Supers need a full meter, and spending it triggers the cinematic freeze
+            if attack_type == AttackType::Super {
+                if !super_meter.is_full() {
+                    continue;
+                }
+                super_meter.current = 0.0;
+                super_writer.send(SuperEvent { attacker: entity });
+            }
+
+            // Start cooldown timer from the move data (longer for heavy attacks)
+            let move_data = move_library.get(animation_state.character_type, attack_type);
+            cooldown.timer.set_duration(move_data.cooldown());
             cooldown.timer.reset();
 
-            // Send attack event with type
-            spawn_hitbox_writer.send(SpawnHitboxEvent {
-                attacker: entity,
-                attack_type,
-            });
+            // The hitbox is spawned by update_attack_state once startup ends
+            attack_state.start(attack_type, &move_data);
+        }
+    }
+}
+
+fn update_dashes(mut commands: Commands, time: Res<Time>, mut query: Query<(Entity, &mut Dash)>) {
+    for (entity, mut dash) in query.iter_mut() {
+        dash.timer.tick(time.delta());
+        if dash.timer.finished() {
+            commands.entity(entity).remove::<Dash>();
+        }
+    }
+}
+
+pub fn update_attack_state(
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut AttackState)>,
+    mut spawn_hitbox_writer: EventWriter<SpawnHitboxEvent>,
+) {
+    for (entity, mut attack_state) in query.iter_mut() {
+        let Some(attack) = attack_state.current.as_mut() else {
+            continue;
+        };
+
+        attack.elapsed += time.delta();
+        match attack.phase_for_current_frame() {
+            Some(phase) => {
+                // Spawn the hitbox when startup ends, even if a long frame skipped the active window
+                if attack.phase == AttackPhase::Startup && phase != AttackPhase::Startup {
+                    spawn_hitbox_writer.send(SpawnHitboxEvent {
+                        attacker: entity,
+                        attack_type: attack.attack_type,
+                    });
+                }
+                attack.phase = phase;
+            }
+            None => {
+                // Recovery finished - fighter can act again
+                attack_state.current = None;
+            }
         }
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn player_block(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut query: Query<(&Player, &ControlType, &mut BlockState)>,
+    mut query: Query<
+        (
+            &Player,
+            &ControlType,
+            &AttackState,
+            &mut BlockState,
+            &InputBuffer,
+            Option<&Blackboard>,
+        ),
+        NotStunned,
+    >,
 ) {
-    for (player, control, mut block_state) in query.iter_mut() {
-        if block_state.cooldown_timer.finished() {
-            let should_block = match control {
-                ControlType::Human => keyboard_input.just_pressed(match player.id {
-                    1 => KeyCode::KeyS,
-                    2 => KeyCode::ArrowDown,
-                    _ => KeyCode::KeyS,
-                }),
-                ControlType::AI(_) => {
-                    // AI blocks occasionally when health is low
-                    false // For now, no AI blocking
+    for (player, control, attack_state, mut block_state, input_buffer, board) in query.iter_mut() {
+        // Hold to block: high block key or low block key
+        let stance = match control {
+            ControlType::Human => {
+                if input_buffer.current.held(InputButton::BlockLow) {
+                    Some(BlockStance::Low)
+                } else if input_buffer.current.held(InputButton::BlockHigh) {
+                    Some(BlockStance::High)
+                } else {
+                    None
                 }
-            };
+            }
+            // Block intent from the boss's behaviour tree
+            ControlType::AI(_) => board.and_then(|board| board.block),
+        };
 
-            if should_block {
-                block_state.is_blocking = true;
-                block_state.block_timer.reset();
-                block_state.cooldown_timer.reset();
-                tracing::info!("Player {} started blocking", player.id);
+        match stance {
+            Some(stance) if !attack_state.is_attacking() && block_state.guard > 0.0 => {
+                if !block_state.is_blocking {
+                    block_state.is_blocking = true;
+                    block_state.block_timer.reset();
+                    tracing::info!("Player {} started blocking", player.id);
+                }
+                block_state.stance = stance;
+            }
+            _ => {
+                block_state.is_blocking = false;
             }
         }
     }
 }
 
-fn update_block_state(time: Res<Time>, mut query: Query<(&mut BlockState, &mut Sprite)>) {
-    for (mut block_state, mut sprite) in query.iter_mut() {
+fn update_block_state(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(
+        Entity,
+        &Player,
+        &mut BlockState,
+        &mut Sprite,
+        Has<HurtboxDisabled>,
+    )>,
+) {
+    for (entity, player, mut block_state, mut sprite, invincible) in query.iter_mut() {
         block_state.block_timer.tick(time.delta());
-        block_state.cooldown_timer.tick(time.delta());
 
-        if block_state.is_blocking && block_state.block_timer.finished() {
+        // Only blocked hits drain the guard meter; letting go of block refills it
+        if !block_state.is_blocking {
+            block_state.guard =
+                (block_state.guard + GUARD_REGEN_PER_SECOND * time.delta_seconds()).min(MAX_GUARD);
+        }
+
+        // Guard break: an empty meter drops the block and stuns the defender
+        if block_state.is_blocking && block_state.guard <= 0.0 {
             block_state.is_blocking = false;
-            tracing::info!("Block ended");
+            commands
+                .entity(entity)
+                .remove::<Blockstun>()
+                .insert(Hitstun {
+                    timer: Timer::new(frames_to_duration(GUARD_BREAK_STUN_FRAMES), TimerMode::Once),
+                });
+            tracing::info!("Player {} guard broken!", player.id);
         }
 
         // Visual feedback: change color when blocking
-        if block_state.is_blocking {
-            sprite.color = Color::srgb(0.5, 0.5, 1.0); // Blue tint when blocking
+        sprite.color = if !block_state.is_blocking {
+            Color::WHITE // No tint
+        } else if block_state.stance == BlockStance::Low {
+            Color::srgb(0.5, 0.8, 1.0) // Light blue tint when blocking low
         } else {
-            // Reset to original color (this is simplistic - in reality you'd store original color)
-            // For now, assume red for P1, blue for P2, but this will be overridden by boss colors
+            Color::srgb(0.5, 0.5, 1.0) // Blue tint when blocking high
+        };
+
+        // Invincible fighters are drawn semi-transparent
+        if invincible {
+            sprite.color.set_alpha(0.5);
         }
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn player_jump(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut query: Query<(&Player, &ControlType, &Grounded, &mut LinearVelocity)>,
+    time: Res<Time>,
+    mut query: Query<
+        (
+            &Player,
+            &ControlType,
+            &Grounded,
+            &AttackState,
+            &mut LinearVelocity,
+            &mut InputBuffer,
+            Option<&Blackboard>,
+        ),
+        NotStunned,
+    >,
 ) {
-    for (player, control, grounded, mut velocity) in query.iter_mut() {
-        if grounded.0 {
+    for (player, control, grounded, attack_state, mut velocity, mut input_buffer, board) in
+        query.iter_mut()
+    {
+        if grounded.0 && !attack_state.is_attacking() {
             let should_jump = match control {
-                ControlType::Human => keyboard_input.just_pressed(match player.id {
-                    1 => KeyCode::KeyW,
-                    2 => KeyCode::ArrowUp,
-                    _ => KeyCode::KeyW,
-                }),
-                ControlType::AI(_) => {
-                    // AI jumps occasionally for variety
-                    rand::random::<f32>() < 0.02 // 2% chance per frame when grounded
-                }
+                ControlType::Human => input_buffer.take_jump(time.elapsed_seconds()),
+                // Jump intent from the boss's behaviour tree
+                ControlType::AI(_) => board.is_some_and(|board| board.jump),
             };
 
             if should_jump {
@@ -471,6 +661,7 @@ fn player_jump(
 
 fn update_grounded(
     mut collision_events: EventReader<Collision>,
+    layers_query: Query<&CollisionLayers>,
     mut query: Query<(Entity, &mut Grounded)>,
 ) {
     // First, set all players to not grounded
@@ -478,14 +669,21 @@ fn update_grounded(
         grounded.0 = false;
     }
 
-    // Then, set grounded to true if colliding with ground
+    // Then, set grounded to true if colliding with the arena (not the other fighter)
     for collision in collision_events.read() {
         let contacts = &collision.0;
         for (entity, mut grounded) in query.iter_mut() {
-            // Check if this entity is involved in the collision
-            if contacts.entity1 == entity || contacts.entity2 == entity {
-                // For simplicity, assume any collision means grounded
-                // In a more complex system, you'd check if it's with the ground
+            let other = if contacts.entity1 == entity {
+                contacts.entity2
+            } else if contacts.entity2 == entity {
+                contacts.entity1
+            } else {
+                continue;
+            };
+            if layers_query
+                .get(other)
+                .is_ok_and(|layers| GameLayer::Arena.contains(layers))
+            {
                 grounded.0 = true;
                 break;
             }
@@ -542,55 +740,60 @@ pub fn setup_game_entities(mut commands: Commands) {
         },
         RigidBody::Static,
         Collider::rectangle(1200.0, 50.0),
+        GameLayer::arena(),
     ));
 
     // -- Player 1 --
-    commands.spawn((
-        SpriteBundle {
-            sprite: Sprite {
-                color: Color::srgb(1.0, 0.0, 0.0),
-                custom_size: Some(Vec2::new(50.0, 100.0)),
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::srgb(1.0, 0.0, 0.0),
+                    custom_size: Some(Vec2::new(50.0, 100.0)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(-200.0, 0.0, 0.0),
                 ..default()
             },
-            transform: Transform::from_xyz(-200.0, 0.0, 0.0),
-            ..default()
-        },
-        RigidBody::Dynamic,
-        LockedAxes::ROTATION_LOCKED,
-        Collider::rectangle(50.0, 100.0),
-        combat::Hurtbox,
-        Player { id: 1 },
-        Health {
-            current: 100,
-            max: 100,
-        },
-        MoveSpeed(300.0),
-        FacingDirection::Right,
-    ));
+            RigidBody::Dynamic,
+            LockedAxes::ROTATION_LOCKED,
+            Collider::rectangle(50.0, 100.0),
+            GameLayer::pushbox(),
+            Player { id: 1 },
+            Health {
+                current: 100,
+                max: 100,
+            },
+            MoveSpeed(300.0),
+            FacingDirection::Right,
+        ))
+        .with_children(spawn_hurtboxes);
 
     // -- Player 2 --
-    commands.spawn((
-        SpriteBundle {
-            sprite: Sprite {
-                color: Color::srgb(0.0, 0.0, 1.0),
-                custom_size: Some(Vec2::new(50.0, 100.0)),
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::srgb(0.0, 0.0, 1.0),
+                    custom_size: Some(Vec2::new(50.0, 100.0)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(200.0, 0.0, 0.0),
                 ..default()
             },
-            transform: Transform::from_xyz(200.0, 0.0, 0.0),
-            ..default()
-        },
-        RigidBody::Dynamic,
-        LockedAxes::ROTATION_LOCKED,
-        Collider::rectangle(50.0, 100.0),
-        combat::Hurtbox,
-        Player { id: 2 },
-        Health {
-            current: 100,
-            max: 100,
-        },
-        MoveSpeed(300.0),
-        FacingDirection::Left,
-    ));
+            RigidBody::Dynamic,
+            LockedAxes::ROTATION_LOCKED,
+            Collider::rectangle(50.0, 100.0),
+            GameLayer::pushbox(),
+            Player { id: 2 },
+            Health {
+                current: 100,
+                max: 100,
+            },
+            MoveSpeed(300.0),
+            FacingDirection::Left,
+        ))
+        .with_children(spawn_hurtboxes);
 }
 
 type GameEntityQuery<'w, 's> = Query<'w, 's, Entity, Or<(With<Player>, With<RigidBody>)>>;
@@ -616,3 +819,42 @@ fn play_jump_sound(
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(stance: BlockStance) -> BlockState {
+        BlockState {
+            is_blocking: true,
+            stance,
+            block_timer: Timer::new(frames_to_duration(PARRY_WINDOW_FRAMES), TimerMode::Once),
+            guard: MAX_GUARD,
+        }
+    }
+
+    #[test]
+    fn blocks_need_the_matching_stance() {
+        assert!(block(BlockStance::High).blocks(AttackHeight::High));
+        assert!(!block(BlockStance::High).blocks(AttackHeight::Low));
+        assert!(block(BlockStance::Low).blocks(AttackHeight::Low));
+        assert!(!block(BlockStance::Low).blocks(AttackHeight::High));
+
+        let mut not_blocking = block(BlockStance::High);
+        not_blocking.is_blocking = false;
+        assert!(!not_blocking.blocks(AttackHeight::High));
+    }
+
+    #[test]
+    fn parries_only_in_the_parry_window() {
+        let mut block_state = block(BlockStance::Low);
+        assert!(block_state.parries(AttackHeight::Low));
+        assert!(!block_state.parries(AttackHeight::High));
+
+        block_state
+            .block_timer
+            .tick(frames_to_duration(PARRY_WINDOW_FRAMES));
+        assert!(block_state.blocks(AttackHeight::Low));
+        assert!(!block_state.parries(AttackHeight::Low));
+    }
+}
@@ -0,0 +1,322 @@
+use bevy::prelude::*;
+
+use crate::combat::FightTracker;
+use crate::game_state::{AppState, GameConfig};
+use crate::input::FrameInput;
+
+// Stamped into every replay; replays from other versions may desync
+pub const REPLAY_VERSION: &str = env!("CARGO_PKG_VERSION");
+const REPLAY_DIR: &str = "replays";
+// An hour of ticks; longer files are rejected so a bad run count can't exhaust memory
+const MAX_REPLAY_FRAMES: usize = 60 * 60 * 60;
+// Name of the replay that always holds the most recent fight
+pub const LAST_FIGHT_REPLAY: &str = "last";
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Replays>()
+            .add_systems(OnEnter(AppState::InGame), start_recording)
+            .add_systems(OnEnter(AppState::GameOver), save_replay)
+            .add_systems(OnEnter(AppState::Credits), save_replay)
+            .add_systems(OnEnter(AppState::MainMenu), end_playback);
+    }
+}
+
+// -- Replay File --
+
+// Everything needed to play a fight back: the settings it was played with, its RNG seed
+// and every tick of both players' buttons
+pub struct Replay {
+    pub version: String,
+    pub config: serde_json::Value, // GameConfig, as saved in game_config.json
+    pub seed: u64,
+    pub frames: Vec<[FrameInput; 2]>, // Player 1 and 2 buttons for each tick from the first
+}
+
+impl Replay {
+    pub fn path(name: &str) -> String {
+        format!("{}/{}.replay.json", REPLAY_DIR, name)
+    }
+
+    pub fn exists(name: &str) -> bool {
+        std::path::Path::new(&Replay::path(name)).exists()
+    }
+
+    // Ticks are run-length encoded as [count, p1 held, p1 pressed, p2 held, p2 pressed], which
+    // keeps long stretches of held or idle buttons down to a single entry
+    fn to_json(&self) -> serde_json::Value {
+        let mut runs: Vec<(u32, [FrameInput; 2])> = Vec::new();
+        for frame in &self.frames {
+            match runs.last_mut() {
+                Some((count, last)) if last == frame => *count += 1,
+                _ => runs.push((1, *frame)),
+            }
+        }
+        let frames: Vec<serde_json::Value> = runs
+            .iter()
+            .map(|(count, [p1, p2])| {
+                serde_json::json!([count, p1.held, p1.pressed, p2.held, p2.pressed])
+            })
+            .collect();
+
+        serde_json::json!({
+            "version": self.version,
+            "config": self.config,
+            "seed": self.seed,
+            "frames": frames,
+        })
+    }
+
+    fn from_json(json: &serde_json::Value) -> Option<Replay> {
+        let mut frames = Vec::new();
+        for run in json.get("frames")?.as_array()? {
+            let values: Vec<u64> = run
+                .as_array()?
+                .iter()
+                .filter_map(|value| value.as_u64())
+                .collect();
+            let [count, p1_held, p1_pressed, p2_held, p2_pressed] = values[..] else {
+                return None;
+            };
+            let frame = [
+                FrameInput {
+                    held: p1_held as u16,
+                    pressed: p1_pressed as u16,
+                },
+                FrameInput {
+                    held: p2_held as u16,
+                    pressed: p2_pressed as u16,
+                },
+            ];
+            let count = usize::try_from(count).ok()?;
+            if count > MAX_REPLAY_FRAMES - frames.len() {
+                return None;
+            }
+            frames.extend(std::iter::repeat(frame).take(count));
+        }
+
+        Some(Replay {
+            version: json.get("version")?.as_str()?.to_string(),
+            config: json.get("config")?.clone(),
+            seed: json.get("seed")?.as_u64()?,
+            frames,
+        })
+    }
+
+    pub fn save(&self, name: &str) {
+        use std::fs;
+
+        if fs::create_dir_all(REPLAY_DIR).is_err() {
+            tracing::warn!("Failed to create replay directory");
+            return;
+        }
+        if let Ok(json_string) = serde_json::to_string(&self.to_json()) {
+            if fs::write(Replay::path(name), json_string).is_ok() {
+                tracing::info!("Replay saved: {}", Replay::path(name));
+            } else {
+                tracing::warn!("Failed to write replay {}", Replay::path(name));
+            }
+        }
+    }
+
+    pub fn load(name: &str) -> Option<Replay> {
+        let json_string = std::fs::read_to_string(Replay::path(name)).ok()?;
+        let json = serde_json::from_str::<serde_json::Value>(&json_string).ok()?;
+        let replay = Replay::from_json(&json);
+        if replay.is_none() {
+            tracing::warn!("Replay {} is malformed", Replay::path(name));
+        }
+        replay
+    }
+}
+
+// -- Recording & Playback --
+
+struct Playback {
+    replay: Replay,
+    saved_config: serde_json::Value, // The player's own settings, restored afterwards
+}
+
+// Records the current fight's inputs, or plays a replay's inputs back in their place
+#[derive(Resource, Default)]
+pub struct Replays {
+    recording: Vec<[FrameInput; 2]>,
+    playback: Option<Playback>,
+}
+
+impl Replays {
+    pub fn is_playing(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    // Buttons a replay pressed on this tick; None when not playing back. Ticks past the end
+    // of the replay press nothing.
+    pub fn playback_input(&self, frame: u32, player_id: u8) -> Option<FrameInput> {
+        let playback = self.playback.as_ref()?;
+        Some(
+            playback
+                .replay
+                .frames
+                .get(frame.saturating_sub(1) as usize)
+                .map(|inputs| inputs[player_slot(player_id)])
+                .unwrap_or_default(),
+        )
+    }
+
+    pub fn record_input(&mut self, frame: u32, player_id: u8, input: FrameInput) {
+        if self.is_playing() {
+            return;
+        }
+        let index = frame.saturating_sub(1) as usize;
+        // Anything past the cap couldn't be loaded again
+        if index >= MAX_REPLAY_FRAMES {
+            return;
+        }
+        if self.recording.len() <= index {
+            self.recording.resize(index + 1, [FrameInput::default(); 2]);
+        }
+        self.recording[index][player_slot(player_id)] = input;
+    }
+
+    // Swaps in the replay's settings, including its seed, for the fight about to start
+    pub fn start_playback(&mut self, replay: Replay, config: &mut GameConfig) {
+        if replay.version != REPLAY_VERSION {
+            tracing::warn!(
+                "Replay was recorded with version {} (running {}); it may desync",
+                replay.version,
+                REPLAY_VERSION
+            );
+        }
+        let saved_config = config.to_json();
+        *config = GameConfig::from_json(&replay.config);
+        config.seed = Some(replay.seed);
+        self.playback = Some(Playback {
+            replay,
+            saved_config,
+        });
+    }
+}
+
+// Index of a player's buttons in a pair of inputs
+pub fn player_slot(player_id: u8) -> usize {
+    if player_id == 2 {
+        1
+    } else {
+        0
+    }
+}
+
+// -- Systems --
+
+fn start_recording(mut replays: ResMut<Replays>) {
+    replays.recording.clear();
+}
+
+// Saves a finished fight as the latest replay, and vs-AI fights as that boss's replay too
+fn save_replay(
+    mut replays: ResMut<Replays>,
+    config: Res<GameConfig>,
+    fight_tracker: Option<Res<FightTracker>>,
+) {
+    if replays.is_playing() || replays.recording.is_empty() {
+        return;
+    }
+    let Some(fight_tracker) = fight_tracker else {
+        return;
+    };
+
+    let replay = Replay {
+        version: REPLAY_VERSION.to_string(),
+        config: config.to_json(),
+        seed: fight_tracker.seed,
+        frames: std::mem::take(&mut replays.recording),
+    };
+    replay.save(LAST_FIGHT_REPLAY);
+    if !config.player2_is_human {
+        replay.save(&format!("{:?}", config.boss));
+    }
+}
+
+fn end_playback(mut replays: ResMut<Replays>, mut config: ResMut<GameConfig>) {
+    // A fight abandoned from the pause menu is never saved
+    replays.recording.clear();
+    if let Some(playback) = replays.playback.take() {
+        *config = GameConfig::from_json(&playback.saved_config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(held: u16, pressed: u16) -> FrameInput {
+        FrameInput { held, pressed }
+    }
+
+    #[test]
+    fn run_length_encoding_round_trips() {
+        let idle = [FrameInput::default(); 2];
+        let walking = [input(0b10, 0), FrameInput::default()];
+        let attack = [input(0b10, 0b1000), input(0b1, 0b1)];
+        let mut frames = vec![idle; 30];
+        frames.extend([walking; 12]);
+        frames.push(attack);
+        frames.extend([idle; 5]);
+        let replay = Replay {
+            version: REPLAY_VERSION.to_string(),
+            config: serde_json::json!({ "difficulty": "Hard" }),
+            seed: 1234,
+            frames,
+        };
+
+        let json = replay.to_json();
+        // Each stretch of identical ticks is a single entry
+        assert_eq!(json["frames"].as_array().map(Vec::len), Some(4));
+
+        let loaded = Replay::from_json(&json).expect("replay should load");
+        assert_eq!(loaded.version, replay.version);
+        assert_eq!(loaded.config, replay.config);
+        assert_eq!(loaded.seed, replay.seed);
+        assert_eq!(loaded.frames, replay.frames);
+    }
+
+    #[test]
+    fn malformed_runs_are_rejected() {
+        let json = serde_json::json!({
+            "version": REPLAY_VERSION,
+            "config": {},
+            "seed": 1,
+            "frames": [[3, 0, 0, 0]],
+        });
+        assert!(Replay::from_json(&json).is_none());
+    }
+
+    #[test]
+    fn oversized_replays_are_rejected() {
+        let replay_with = |runs: serde_json::Value| {
+            Replay::from_json(&serde_json::json!({
+                "version": REPLAY_VERSION,
+                "config": {},
+                "seed": 1,
+                "frames": runs,
+            }))
+        };
+        assert!(replay_with(serde_json::json!([[u64::MAX, 0, 0, 0, 0]])).is_none());
+        // Runs that each fit but add up to more than the cap
+        let half = MAX_REPLAY_FRAMES / 2 + 1;
+        assert!(replay_with(serde_json::json!([[half, 0, 0, 0, 0], [half, 1, 0, 0, 0]])).is_none());
+        assert!(replay_with(serde_json::json!([[MAX_REPLAY_FRAMES, 0, 0, 0, 0]])).is_some());
+    }
+
+    #[test]
+    fn recording_stops_at_the_cap() {
+        let mut replays = Replays::default();
+        replays.record_input(MAX_REPLAY_FRAMES as u32 + 1, 1, input(1, 1));
+        assert!(replays.recording.is_empty());
+        replays.record_input(3, 2, input(1, 1));
+        assert_eq!(replays.recording.len(), 3);
+        assert_eq!(replays.recording[2][1], input(1, 1));
+    }
+}
@@ -0,0 +1,188 @@
+use bevy::prelude::*;
+
+use crate::game_state::{AppState, GameConfig};
+
+pub struct RngPlugin;
+
+impl Plugin for RngPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(GameRng::new(0))
+            .insert_resource(EffectsRng::from_entropy())
+            .add_systems(OnEnter(AppState::InGame), seed_fight_rng);
+    }
+}
+
+// -- Streams --
+
+// Each subsystem draws from its own stream, so extra draws in one (say, another AI roll)
+// don't shift the numbers another one sees
+#[derive(Clone, Copy, Debug)]
+pub enum RngStream {
+    Ai,     // Behaviour tree rolls, reactions and move picks
+    Combat, // Tech chances
+    Status, // Status effect procs and picks
+}
+
+const STREAM_COUNT: usize = 3;
+
+// Deterministic random numbers for everything that affects a fight, seeded once per fight
+#[derive(Resource, Clone, Hash)]
+pub struct GameRng {
+    seed: u64,
+    streams: [u64; STREAM_COUNT],
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = GameRng {
+            seed,
+            streams: [0; STREAM_COUNT],
+        };
+        rng.reseed(seed);
+        rng
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        for (index, state) in self.streams.iter_mut().enumerate() {
+            // Spread the streams apart so they don't start on neighbouring values
+            let mut stream_seed = seed ^ (index as u64 + 1).wrapping_mul(0xA24B_AED4_963E_E407);
+            *state = splitmix64(&mut stream_seed);
+        }
+    }
+
+    pub fn next_u64(&mut self, stream: RngStream) -> u64 {
+        splitmix64(&mut self.streams[stream as usize])
+    }
+
+    // Uniform in [0, 1)
+    pub fn f32(&mut self, stream: RngStream) -> f32 {
+        (self.next_u64(stream) >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    // Uniform in [-1, 1)
+    pub fn signed(&mut self, stream: RngStream) -> f32 {
+        self.f32(stream) * 2.0 - 1.0
+    }
+
+    // Uniform index into a collection of `len` items; `len` must be non-zero
+    pub fn index(&mut self, stream: RngStream, len: usize) -> usize {
+        (self.next_u64(stream) % len as u64) as usize
+    }
+
+    pub fn chance(&mut self, stream: RngStream, probability: f32) -> bool {
+        self.f32(stream) < probability
+    }
+}
+
+// Cosmetic randomness for particles and camera shake. These are drawn at the display's frame
+// rate and aren't replayed by rollbacks, so they stay out of the fight's seeded, rolled-back
+// `GameRng` and are seeded from entropy instead.
+#[derive(Resource)]
+pub struct EffectsRng {
+    state: u64,
+}
+
+impl EffectsRng {
+    pub fn from_entropy() -> Self {
+        EffectsRng {
+            state: rand::random::<u64>(),
+        }
+    }
+
+    // Uniform in [0, 1)
+    pub fn f32(&mut self) -> f32 {
+        (splitmix64(&mut self.state) >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    // Uniform in [-1, 1)
+    pub fn signed(&mut self) -> f32 {
+        self.f32() * 2.0 - 1.0
+    }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// -- Systems --
+
+// A fixed seed in the config makes every fight reproducible; otherwise each fight gets a
+// fresh one. Either way it's logged and kept on the fight tracker.
+pub fn seed_fight_rng(config: Res<GameConfig>, mut rng: ResMut<GameRng>) {
+    let seed = config.seed.unwrap_or_else(rand::random::<u64>);
+    rng.reseed(seed);
+    tracing::info!("Fight seed: {}", seed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draws(rng: &mut GameRng, stream: RngStream) -> Vec<u64> {
+        (0..8).map(|_| rng.next_u64(stream)).collect()
+    }
+
+    #[test]
+    fn same_seed_gives_same_numbers() {
+        let mut a = GameRng::new(42);
+        let mut b = GameRng::new(42);
+        assert_eq!(draws(&mut a, RngStream::Ai), draws(&mut b, RngStream::Ai));
+        assert_ne!(
+            draws(&mut GameRng::new(42), RngStream::Ai),
+            draws(&mut GameRng::new(43), RngStream::Ai)
+        );
+    }
+
+    #[test]
+    fn reseeding_starts_over() {
+        let mut rng = GameRng::new(7);
+        draws(&mut rng, RngStream::Combat);
+        rng.reseed(7);
+        assert_eq!(rng.seed(), 7);
+        assert_eq!(
+            draws(&mut rng, RngStream::Combat),
+            draws(&mut GameRng::new(7), RngStream::Combat)
+        );
+    }
+
+    #[test]
+    fn streams_are_independent() {
+        // Extra AI rolls don't shift the combat stream
+        let mut busy = GameRng::new(99);
+        draws(&mut busy, RngStream::Ai);
+        busy.f32(RngStream::Status);
+        let mut quiet = GameRng::new(99);
+        assert_eq!(
+            draws(&mut busy, RngStream::Combat),
+            draws(&mut quiet, RngStream::Combat)
+        );
+        // ...and the streams don't start on the same numbers
+        assert_ne!(
+            draws(&mut GameRng::new(99), RngStream::Ai),
+            draws(&mut GameRng::new(99), RngStream::Combat)
+        );
+    }
+
+    #[test]
+    fn draws_stay_in_range() {
+        let mut rng = GameRng::new(3);
+        for _ in 0..1000 {
+            let value = rng.f32(RngStream::Ai);
+            assert!((0.0..1.0).contains(&value));
+            let signed = rng.signed(RngStream::Ai);
+            assert!((-1.0..1.0).contains(&signed));
+            assert!(rng.index(RngStream::Ai, 3) < 3);
+        }
+        assert!(!rng.chance(RngStream::Ai, 0.0));
+        assert!(rng.chance(RngStream::Ai, 1.0));
+    }
+}
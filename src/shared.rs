@@ -1,3 +1,4 @@
+use bevy::ecs::query::QueryData;
 use bevy::prelude::*;
 use bevy_xpbd_2d::prelude::*;
 use std::time::Duration;
@@ -15,8 +16,11 @@ pub struct GameAssets {
     pub hit_sfx: Handle<AudioSource>,
     pub jump_sfx: Handle<AudioSource>,
     pub block_sfx: Handle<AudioSource>,
+    pub parry_sfx: Handle<AudioSource>,
     pub victory_music: Handle<AudioSource>,
     pub defeat_music: Handle<AudioSource>,
+    pub move_data: Handle<crate::moves::MoveSet>,
+    pub boss_ai: Handle<crate::ai::BossTrees>,
 }
 
 #[derive(Resource)]
@@ -42,6 +46,10 @@ pub struct CharacterAnimations {
     pub light_attack: Vec<Handle<Image>>,
     pub heavy_attack: Vec<Handle<Image>>,
     pub kick_attack: Vec<Handle<Image>>,
+    pub throw: Vec<Handle<Image>>,
+    pub thrown: Vec<Handle<Image>>,
+    pub landing: Vec<Handle<Image>>,
+    pub phase_shift: Vec<Handle<Image>>,
 }
 
 #[derive(Resource)]
@@ -93,9 +101,13 @@ pub enum AnimationType {
     LightAttack,
     HeavyAttack,
     KickAttack,
+    Throw,
+    Thrown,
+    Landing,
+    PhaseShift, // Boss powering up into a new phase
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum CharacterType {
     Player,
     Zombie,
@@ -114,16 +126,48 @@ impl CharacterType {
             crate::game_state::BossType::BufferOverflow => CharacterType::Player, // Use player as alternate boss
         }
     }
+
+    // The name used in moves.json, saved configs and lobby packets
+    pub fn name(self) -> &'static str {
+        match self {
+            CharacterType::Player => "Player",
+            CharacterType::Zombie => "Zombie",
+            CharacterType::Adventurer => "Adventurer",
+            CharacterType::Female => "Female",
+            CharacterType::Soldier => "Soldier",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Player" => Some(CharacterType::Player),
+            "Zombie" => Some(CharacterType::Zombie),
+            "Adventurer" => Some(CharacterType::Adventurer),
+            "Female" => Some(CharacterType::Female),
+            "Soldier" => Some(CharacterType::Soldier),
+            _ => None,
+        }
+    }
 }
 
 // Import our modules
-use crate::combat::CombatPlugin;
+use crate::ai::{AiPlugin, Blackboard};
+use crate::boss::{BossPlugin, CurrentPhase};
+use crate::boxes::{spawn_hurtboxes, AppliedBoxLayout, BoxesPlugin, GameLayer};
+use crate::combat::{CombatPlugin, Combo, SuperMeter};
 use crate::game_state::{AppState, GameConfig, PlayerProgress, Winner};
+use crate::habits::HabitsPlugin;
+use crate::input::{InputBuffer, InputButton, InputPlugin};
 use crate::menu::MenuPlugin;
+use crate::moves::{frames_to_duration, MovesPlugin};
+use crate::netplay::{NetSession, NetplayPlugin};
 use crate::player::{
-    AIState, AttackCooldown, BlockState, ControlType, FacingDirection, Grounded, Health, MoveSpeed,
-    Player, PlayerPlugin,
+    AttackCooldown, AttackState, AttackType, BlockStance, BlockState, ControlType, FacingDirection,
+    Grounded, Health, MoveSpeed, Player, PlayerPlugin, MAX_GUARD, PARRY_WINDOW_FRAMES,
 };
+use crate::replay::{ReplayPlugin, Replays};
+use crate::rng::RngPlugin;
+use crate::status::{StatusEffects, StatusPlugin};
 use crate::ui::UiPlugin;
 
 pub fn game_main() {
@@ -138,13 +182,27 @@ pub fn game_main() {
                 }),
                 ..default()
             }),
-            PhysicsPlugins::default(),
+            // Physics steps once per gameplay tick so jumps and knockback don't depend on frame rate
+            PhysicsPlugins::new(FixedPostUpdate),
             // PhysicsDebugPlugin::default(),
+            RngPlugin,
+            MovesPlugin,
+            InputPlugin,
+            ReplayPlugin,
+            AiPlugin,
+            HabitsPlugin,
+            BoxesPlugin,
+            StatusPlugin,
             PlayerPlugin,
             CombatPlugin,
+            BossPlugin,
             UiPlugin,
             MenuPlugin,
         ))
+        .add_plugins(NetplayPlugin)
+        .insert_resource(Time::new_with(Physics::fixed_once_hz(
+            crate::moves::FRAMES_PER_SECOND as f64,
+        )))
         .init_state::<AppState>()
         .insert_resource(Winner::default())
         .insert_resource(GameConfig::load_config())
@@ -168,31 +226,27 @@ pub fn game_main() {
 #[derive(Component)]
 struct ArenaBackground;
 
-fn setup(mut commands: Commands, game_config: Res<GameConfig>, assets: Res<GameAssets>) {
-    // Clean up old arena backgrounds first
-    commands.spawn_empty().insert(CleanupArenaBackground);
-
-    // Get the correct arena background based on selected arena
-    let arena_texture = match game_config.arena {
-        crate::game_state::ArenaType::Default => {
-            tracing::info!("SELECTED ARENA: Default - Loading null_pointer texture");
-            assets.arena_backgrounds.null_pointer.clone()
-        }
-        crate::game_state::ArenaType::DataRace => {
-            tracing::info!("SELECTED ARENA: Data Race - Loading data_race texture");
-            assets.arena_backgrounds.data_race.clone()
-        }
+// Background image for an arena; also used when a boss phase changes the arena
+fn arena_texture(assets: &GameAssets, arena: crate::game_state::ArenaType) -> Handle<Image> {
+    match arena {
+        crate::game_state::ArenaType::Default => assets.arena_backgrounds.null_pointer.clone(),
+        crate::game_state::ArenaType::DataRace => assets.arena_backgrounds.data_race.clone(),
         crate::game_state::ArenaType::UndefinedBehavior => {
-            tracing::info!(
-                "SELECTED ARENA: Undefined Behavior - Loading undefined_behavior texture"
-            );
             assets.arena_backgrounds.undefined_behavior.clone()
         }
         crate::game_state::ArenaType::BufferOverflow => {
-            tracing::info!("SELECTED ARENA: Buffer Overflow - Loading buffer_overflow texture");
             assets.arena_backgrounds.buffer_overflow.clone()
         }
-    };
+    }
+}
+
+fn setup(mut commands: Commands, game_config: Res<GameConfig>, assets: Res<GameAssets>) {
+    // Clean up old arena backgrounds first
+    commands.spawn_empty().insert(CleanupArenaBackground);
+
+    // Get the correct arena background based on selected arena
+    tracing::info!("SELECTED ARENA: {:?}", game_config.arena);
+    let arena_texture = arena_texture(&assets, game_config.arena);
 
     // Arena background - positioned behind everything, zoomed out by 50%
     commands.spawn((
@@ -213,6 +267,7 @@ fn setup(mut commands: Commands, game_config: Res<GameConfig>, assets: Res<GameA
         RigidBody::Static,
         // Corrected: Use .rectangle() instead of .cuboid()
         Collider::rectangle(1200.0, 50.0),
+        GameLayer::arena(),
         Transform::from_xyz(0.0, -200.0, 0.0),
     ));
 
@@ -221,6 +276,7 @@ fn setup(mut commands: Commands, game_config: Res<GameConfig>, assets: Res<GameA
         TransformBundle::from_transform(Transform::from_xyz(-650.0, 0.0, 0.0)),
         RigidBody::Static,
         Collider::rectangle(50.0, 800.0), // Thin wall, tall enough for screen
+        GameLayer::arena(),
     ));
 
     // Right boundary wall
@@ -228,6 +284,7 @@ fn setup(mut commands: Commands, game_config: Res<GameConfig>, assets: Res<GameA
         TransformBundle::from_transform(Transform::from_xyz(650.0, 0.0, 0.0)),
         RigidBody::Static,
         Collider::rectangle(50.0, 800.0), // Thin wall, tall enough for screen
+        GameLayer::arena(),
     ));
 
     // Upper boundary wall (ceiling)
@@ -235,76 +292,63 @@ fn setup(mut commands: Commands, game_config: Res<GameConfig>, assets: Res<GameA
         TransformBundle::from_transform(Transform::from_xyz(0.0, 350.0, 0.0)),
         RigidBody::Static,
         Collider::rectangle(1400.0, 50.0), // Wide ceiling, thin enough
+        GameLayer::arena(),
     ));
 
     // -- Player 1 (Human) --
-    commands.spawn((
-        SpriteBundle {
-            texture: assets.player_animations.idle[0].clone(),
-            sprite: Sprite {
-                custom_size: Some(Vec2::new(50.0, 100.0)),
+    commands
+        .spawn((
+            SpriteBundle {
+                texture: idle_texture(&assets, game_config.player1_character),
+                sprite: Sprite {
+                    custom_size: Some(Vec2::new(50.0, 100.0)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(-200.0, 0.0, 0.0),
                 ..default()
             },
-            transform: Transform::from_xyz(-200.0, 0.0, 0.0),
-            ..default()
-        },
-        AnimationState {
-            current_animation: AnimationType::Idle,
-            current_frame: 0,
-            timer: Timer::new(Duration::from_secs_f32(0.15), TimerMode::Repeating),
-            frame_duration: 0.15,
-            character_type: CharacterType::Player, // Human player always uses Player character
-        },
-        RigidBody::Dynamic,
-        LockedAxes::ROTATION_LOCKED,
-        // Corrected: Use .rectangle() instead of .cuboid()
-        Collider::rectangle(50.0, 100.0),
-        crate::combat::Hurtbox,
-        Player { id: 1 },
-        ControlType::Human,
-        Health {
-            current: 100,
-            max: 100,
-        },
-        MoveSpeed(300.0),
-        FacingDirection::Right,
-        AttackCooldown {
-            timer: Timer::new(Duration::from_millis(300), TimerMode::Once),
-        },
-        BlockState {
-            is_blocking: false,
-            block_timer: Timer::new(Duration::from_millis(500), TimerMode::Once),
-            cooldown_timer: Timer::new(Duration::from_secs(2), TimerMode::Once),
-        },
-        Grounded(true), // Start grounded
-    ));
+            AnimationState {
+                current_animation: AnimationType::Idle,
+                current_frame: 0,
+                timer: Timer::new(Duration::from_secs_f32(0.15), TimerMode::Repeating),
+                frame_duration: 0.15,
+                character_type: game_config.player1_character,
+            },
+            RigidBody::Dynamic,
+            LockedAxes::ROTATION_LOCKED,
+            // Pushbox; the hurtboxes are separate child colliders
+            Collider::rectangle(50.0, 100.0),
+            GameLayer::pushbox(),
+            Player { id: 1 },
+            ControlType::Human,
+            Health {
+                current: 100,
+                max: 100,
+            },
+            MoveSpeed(300.0),
+            FacingDirection::Right,
+            fighter_combat_bundle(),
+            Grounded(true), // Start grounded
+        ))
+        .with_children(spawn_hurtboxes);
 
     // Determine Player 2 sprite and control type
     let (player2_initial_texture, player2_control, player2_health, player2_character_type) =
         if game_config.player2_is_human {
             (
-                assets.player_animations.idle[0].clone(), // Use player sprite for human P2
+                idle_texture(&assets, game_config.player2_character),
                 ControlType::Human,
                 Health {
                     current: 100,
                     max: 100,
                 },
-                CharacterType::Player, // Human P2 uses Player character
+                game_config.player2_character,
             )
         } else {
             let health_mult = game_config.difficulty.health_multiplier();
             let character_type = CharacterType::from_boss_type(game_config.boss);
-            let initial_texture = match character_type {
-                CharacterType::Player => assets.all_character_animations.player.idle[0].clone(),
-                CharacterType::Zombie => assets.all_character_animations.zombie.idle[0].clone(),
-                CharacterType::Adventurer => {
-                    assets.all_character_animations.adventurer.idle[0].clone()
-                }
-                CharacterType::Female => assets.all_character_animations.female.idle[0].clone(),
-                CharacterType::Soldier => assets.all_character_animations.soldier.idle[0].clone(),
-            };
             (
-                initial_texture, // Use character-specific sprite for AI
+                idle_texture(&assets, character_type), // Use character-specific sprite for AI
                 ControlType::AI(game_config.boss),
                 Health {
                     current: (100.0 * health_mult) as i32,
@@ -334,29 +378,54 @@ fn setup(mut commands: Commands, game_config: Res<GameConfig>, assets: Res<GameA
         },
         RigidBody::Dynamic,
         LockedAxes::ROTATION_LOCKED,
-        // Corrected: Use .rectangle() instead of .cuboid()
+        // Pushbox; the hurtboxes are separate child colliders
         Collider::rectangle(50.0, 100.0),
-        crate::combat::Hurtbox,
+        GameLayer::pushbox(),
         Player { id: 2 },
         player2_control.clone(),
         player2_health,
         MoveSpeed(300.0),
         FacingDirection::Left,
+        fighter_combat_bundle(),
+        Grounded(true), // Start grounded
+    ));
+    player2_entity.with_children(spawn_hurtboxes);
+
+    // Add the AI blackboard and boss phases if it's AI
+    if matches!(player2_control, ControlType::AI(_)) {
+        player2_entity.insert((Blackboard::default(), CurrentPhase::default()));
+    }
+}
+
+fn idle_texture(assets: &GameAssets, character: CharacterType) -> Handle<Image> {
+    match character {
+        CharacterType::Player => assets.all_character_animations.player.idle[0].clone(),
+        CharacterType::Zombie => assets.all_character_animations.zombie.idle[0].clone(),
+        CharacterType::Adventurer => assets.all_character_animations.adventurer.idle[0].clone(),
+        CharacterType::Female => assets.all_character_animations.female.idle[0].clone(),
+        CharacterType::Soldier => assets.all_character_animations.soldier.idle[0].clone(),
+    }
+}
+
+// Combat state shared by every fighter
+fn fighter_combat_bundle() -> impl Bundle {
+    (
         AttackCooldown {
             timer: Timer::new(Duration::from_millis(300), TimerMode::Once),
         },
+        AttackState::default(),
+        Combo::default(),
+        InputBuffer::default(),
+        SuperMeter::default(),
         BlockState {
             is_blocking: false,
-            block_timer: Timer::new(Duration::from_millis(500), TimerMode::Once),
-            cooldown_timer: Timer::new(Duration::from_secs(2), TimerMode::Once),
+            stance: BlockStance::High,
+            block_timer: Timer::new(frames_to_duration(PARRY_WINDOW_FRAMES), TimerMode::Once),
+            guard: MAX_GUARD,
         },
-        Grounded(true), // Start grounded
-    ));
-
-    // Add AI state if it's AI
-    if matches!(player2_control, ControlType::AI(_)) {
-        player2_entity.insert(AIState::default());
-    }
+        AppliedBoxLayout::default(),
+        StatusEffects::default(),
+    )
 }
 
 #[derive(Component)]
@@ -478,6 +547,19 @@ fn setup_assets(mut commands: Commands, asset_server: Res<AssetServer>) {
                 asset_server.load("sprites/kenney_platformer-characters/PNG/Player/Poses/player_action2.png"),
             ],
             kick_attack: vec![asset_server.load("sprites/kenney_platformer-characters/PNG/Player/Poses/player_kick.png")],
+            throw: vec![
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Player/Poses/player_hold1.png"),
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Player/Poses/player_hold2.png"),
+            ],
+            thrown: vec![
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Player/Poses/player_hurt.png"),
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Player/Poses/player_fall.png"),
+            ],
+            landing: vec![asset_server.load("sprites/kenney_platformer-characters/PNG/Player/Poses/player_duck.png")],
+            phase_shift: vec![
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Player/Poses/player_back.png"),
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Player/Poses/player_cheer1.png"),
+            ],
         },
         zombie: CharacterAnimations {
             idle: vec![asset_server.load("sprites/kenney_platformer-characters/PNG/Zombie/Poses/zombie_idle.png")],
@@ -505,6 +587,19 @@ fn setup_assets(mut commands: Commands, asset_server: Res<AssetServer>) {
                 asset_server.load("sprites/kenney_platformer-characters/PNG/Zombie/Poses/zombie_action2.png"),
             ],
             kick_attack: vec![asset_server.load("sprites/kenney_platformer-characters/PNG/Zombie/Poses/zombie_kick.png")],
+            throw: vec![
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Zombie/Poses/zombie_hold1.png"),
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Zombie/Poses/zombie_hold2.png"),
+            ],
+            thrown: vec![
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Zombie/Poses/zombie_hurt.png"),
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Zombie/Poses/zombie_fall.png"),
+            ],
+            landing: vec![asset_server.load("sprites/kenney_platformer-characters/PNG/Zombie/Poses/zombie_duck.png")],
+            phase_shift: vec![
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Zombie/Poses/zombie_back.png"),
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Zombie/Poses/zombie_cheer1.png"),
+            ],
         },
         adventurer: CharacterAnimations {
             idle: vec![asset_server.load("sprites/kenney_platformer-characters/PNG/Adventurer/Poses/adventurer_idle.png")],
@@ -532,6 +627,19 @@ fn setup_assets(mut commands: Commands, asset_server: Res<AssetServer>) {
                 asset_server.load("sprites/kenney_platformer-characters/PNG/Adventurer/Poses/adventurer_action2.png"),
             ],
             kick_attack: vec![asset_server.load("sprites/kenney_platformer-characters/PNG/Adventurer/Poses/adventurer_kick.png")],
+            throw: vec![
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Adventurer/Poses/adventurer_hold1.png"),
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Adventurer/Poses/adventurer_hold2.png"),
+            ],
+            thrown: vec![
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Adventurer/Poses/adventurer_hurt.png"),
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Adventurer/Poses/adventurer_fall.png"),
+            ],
+            landing: vec![asset_server.load("sprites/kenney_platformer-characters/PNG/Adventurer/Poses/adventurer_duck.png")],
+            phase_shift: vec![
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Adventurer/Poses/adventurer_back.png"),
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Adventurer/Poses/adventurer_cheer1.png"),
+            ],
         },
         female: CharacterAnimations {
             idle: vec![asset_server.load("sprites/kenney_platformer-characters/PNG/Female/Poses/female_idle.png")],
@@ -559,6 +667,19 @@ fn setup_assets(mut commands: Commands, asset_server: Res<AssetServer>) {
                 asset_server.load("sprites/kenney_platformer-characters/PNG/Female/Poses/female_action2.png"),
             ],
             kick_attack: vec![asset_server.load("sprites/kenney_platformer-characters/PNG/Female/Poses/female_kick.png")],
+            throw: vec![
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Female/Poses/female_hold1.png"),
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Female/Poses/female_hold2.png"),
+            ],
+            thrown: vec![
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Female/Poses/female_hurt.png"),
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Female/Poses/female_fall.png"),
+            ],
+            landing: vec![asset_server.load("sprites/kenney_platformer-characters/PNG/Female/Poses/female_duck.png")],
+            phase_shift: vec![
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Female/Poses/female_back.png"),
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Female/Poses/female_cheer1.png"),
+            ],
         },
         soldier: CharacterAnimations {
             idle: vec![asset_server.load("sprites/kenney_platformer-characters/PNG/Soldier/Poses/soldier_idle.png")],
@@ -586,6 +707,19 @@ fn setup_assets(mut commands: Commands, asset_server: Res<AssetServer>) {
                 asset_server.load("sprites/kenney_platformer-characters/PNG/Soldier/Poses/soldier_action2.png"),
             ],
             kick_attack: vec![asset_server.load("sprites/kenney_platformer-characters/PNG/Soldier/Poses/soldier_kick.png")],
+            throw: vec![
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Soldier/Poses/soldier_hold1.png"),
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Soldier/Poses/soldier_hold2.png"),
+            ],
+            thrown: vec![
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Soldier/Poses/soldier_hurt.png"),
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Soldier/Poses/soldier_fall.png"),
+            ],
+            landing: vec![asset_server.load("sprites/kenney_platformer-characters/PNG/Soldier/Poses/soldier_duck.png")],
+            phase_shift: vec![
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Soldier/Poses/soldier_back.png"),
+                asset_server.load("sprites/kenney_platformer-characters/PNG/Soldier/Poses/soldier_cheer1.png"),
+            ],
         },
     };
 
@@ -609,15 +743,22 @@ fn setup_assets(mut commands: Commands, asset_server: Res<AssetServer>) {
         hit_sfx: asset_server.load("audio/hit.ogg"),
         jump_sfx: asset_server.load("audio/jump.ogg"),
         block_sfx: asset_server.load("audio/hit.ogg"), // Reuse hit sound for blocks
+        parry_sfx: asset_server.load("audio/jump.ogg"), // Reuse jump sound for parries
         victory_music: asset_server.load("audio/victory_sting.ogg"),
         defeat_music: asset_server.load("audio/game_over.ogg"),
+        // Attack frame data - edit the JSON to rebalance without recompiling
+        move_data: asset_server.load("data/fighters.moves.json"),
+        boss_ai: asset_server.load("data/bosses.ai.json"),
     };
 
     commands.insert_resource(assets);
 }
 
 fn setup_camera(mut commands: Commands) {
-    commands.spawn(Camera2dBundle::default());
+    commands.spawn((
+        Camera2dBundle::default(),
+        crate::combat::CameraShake::default(),
+    ));
 }
 
 #[derive(Component)]
@@ -626,114 +767,113 @@ pub struct AttackInput {
 }
 
 // Animation Systems
-#[allow(clippy::type_complexity)]
-fn update_animation_state(
-    time: Res<Time>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    winner: Res<Winner>,
-    mut query: Query<(
-        &mut AnimationState,
-        &LinearVelocity,
-        &Grounded,
-        &AttackCooldown,
-        &Health,
-        &Player,
-        &ControlType,
-        Option<&BlockState>,
-    )>,
-) {
-    for (
-        mut animation_state,
-        velocity,
-        grounded,
-        attack_cooldown,
-        health,
-        player,
-        control_type,
-        block_state,
-    ) in query.iter_mut()
-    {
-        // Check immediate input states for responsive animations
+
+// The simulated state a fighter's animation is picked from
+#[derive(QueryData)]
+pub struct FighterPose {
+    velocity: &'static LinearVelocity,
+    grounded: &'static Grounded,
+    attack_state: &'static AttackState,
+    health: &'static Health,
+    input_buffer: &'static InputBuffer,
+    control_type: &'static ControlType,
+    block_state: Option<&'static BlockState>,
+    in_hitstun: Has<crate::combat::Hitstun>,
+    in_blockstun: Has<crate::combat::Blockstun>,
+    is_dashing: Has<crate::player::Dash>,
+    is_knocked_down: Has<crate::combat::Knockdown>,
+    is_thrown: Has<crate::combat::Thrown>,
+    is_juggled: Has<crate::combat::Juggle>,
+    is_landing: Has<crate::combat::LandingRecovery>,
+    is_changing_phase: Has<crate::boss::PhaseTransition>,
+}
+
+impl FighterPoseItem<'_> {
+    pub fn animation(&self, winner: &Winner) -> AnimationType {
+        // Attack animations follow the attack state machine, plus held keys for responsiveness
+        // Aerial attacks share the animation of their ground version
+        let current_attack = self
+            .attack_state
+            .current_attack()
+            .map(AttackType::ground_variant);
         let (light_attack_pressed, heavy_attack_pressed, kick_attack_pressed, jump_pressed) =
-            match control_type {
+            match self.control_type {
                 ControlType::Human => {
-                    let (light_key, heavy_key, kick_key) = if player.id == 1 {
-                        (KeyCode::KeyF, KeyCode::KeyR, KeyCode::KeyT) // P1: F=Light, R=Heavy, T=Kick
-                    } else {
-                        (KeyCode::KeyL, KeyCode::KeyO, KeyCode::KeyP) // P2: L=Light, O=Heavy, P=Kick
-                    };
-                    let jump_key = if player.id == 1 {
-                        KeyCode::KeyW
-                    } else {
-                        KeyCode::ArrowUp
-                    };
+                    // Buttons as the simulation saw them, so replays animate the same way
+                    let input = self.input_buffer.current;
                     (
-                        keyboard_input.pressed(light_key)
-                            || (!attack_cooldown.timer.finished()
-                                && matches!(
-                                    animation_state.current_animation,
-                                    AnimationType::LightAttack
-                                )),
-                        keyboard_input.pressed(heavy_key)
-                            || (!attack_cooldown.timer.finished()
-                                && matches!(
-                                    animation_state.current_animation,
-                                    AnimationType::HeavyAttack
-                                )),
-                        keyboard_input.pressed(kick_key)
-                            || (!attack_cooldown.timer.finished()
-                                && matches!(
-                                    animation_state.current_animation,
-                                    AnimationType::KickAttack
-                                )),
-                        keyboard_input.pressed(jump_key),
+                        input.held(InputButton::Light) || current_attack == Some(AttackType::Light),
+                        input.held(InputButton::Heavy) || current_attack == Some(AttackType::Heavy),
+                        input.held(InputButton::Kick) || current_attack == Some(AttackType::Kick),
+                        input.held(InputButton::Up),
                     )
                 }
                 ControlType::AI(_) => (
-                    !attack_cooldown.timer.finished()
-                        && matches!(
-                            animation_state.current_animation,
-                            AnimationType::LightAttack
-                        ),
-                    !attack_cooldown.timer.finished()
-                        && matches!(
-                            animation_state.current_animation,
-                            AnimationType::HeavyAttack
-                        ),
-                    !attack_cooldown.timer.finished()
-                        && matches!(animation_state.current_animation, AnimationType::KickAttack),
+                    current_attack == Some(AttackType::Light),
+                    current_attack == Some(AttackType::Heavy),
+                    current_attack == Some(AttackType::Kick),
                     false, // AI jumping is random, not input-based
                 ),
             };
 
         // Check for blocking
-        let is_blocking = block_state.map(|bs| bs.is_blocking).unwrap_or(false);
+        let is_blocking =
+            self.block_state.map(|bs| bs.is_blocking).unwrap_or(false) || self.in_blockstun;
 
         // Check for victory (human winner and this is a human player)
-        let is_victorious =
-            winner.is_human_winner.unwrap_or(false) && matches!(control_type, ControlType::Human);
+        let is_victorious = winner.is_human_winner.unwrap_or(false)
+            && matches!(self.control_type, ControlType::Human);
 
         // Determine new animation based on state with higher priority for immediate actions
-        let new_animation = if is_victorious {
+        if is_victorious {
             AnimationType::Victory
+        } else if self.is_changing_phase {
+            AnimationType::PhaseShift
+        } else if self.is_thrown {
+            AnimationType::Thrown
+        } else if self.is_knocked_down {
+            AnimationType::Falling
+        } else if self.in_hitstun || self.is_juggled {
+            AnimationType::Hurt
+        } else if self.is_landing {
+            AnimationType::Landing
         } else if is_blocking {
             AnimationType::Blocking
+        } else if current_attack == Some(AttackType::Throw) {
+            AnimationType::Throw
+        } else if matches!(
+            current_attack,
+            Some(AttackType::Projectile) | Some(AttackType::Super)
+        ) || self.is_dashing
+        {
+            // Specials, supers and dashes
+            AnimationType::SpecialAttack
         } else if light_attack_pressed {
             AnimationType::LightAttack
         } else if heavy_attack_pressed {
             AnimationType::HeavyAttack
         } else if kick_attack_pressed {
             AnimationType::KickAttack
-        } else if jump_pressed || !grounded.0 {
+        } else if jump_pressed || !self.grounded.0 {
             AnimationType::Jumping
-        } else if health.current < health.max / 3 {
+        } else if self.health.current < self.health.max / 3 {
             AnimationType::Hurt
-        } else if velocity.x.abs() > 1.0 {
+        } else if self.velocity.x.abs() > 1.0 {
             // Much lower threshold for immediate walking response
             AnimationType::Walking
         } else {
             AnimationType::Idle
-        };
+        }
+    }
+}
+
+fn update_animation_state(
+    time: Res<Time>,
+    winner: Res<Winner>,
+    mut query: Query<(&mut AnimationState, FighterPose)>,
+) {
+    for (mut animation_state, pose) in query.iter_mut() {
+        let new_animation = pose.animation(&winner);
 
         // Change animation if different (immediate response)
         if animation_state.current_animation != new_animation {
@@ -776,6 +916,12 @@ fn animate_sprite(
                     AnimationType::Blocking => &assets.all_character_animations.player.block,
                     AnimationType::Victory => &assets.all_character_animations.player.victory,
                     AnimationType::Falling => &assets.all_character_animations.player.fall,
+                    AnimationType::Throw => &assets.all_character_animations.player.throw,
+                    AnimationType::Thrown => &assets.all_character_animations.player.thrown,
+                    AnimationType::Landing => &assets.all_character_animations.player.landing,
+                    AnimationType::PhaseShift => {
+                        &assets.all_character_animations.player.phase_shift
+                    }
                     AnimationType::SpecialAttack => &assets.all_character_animations.player.special,
                 },
                 CharacterType::Zombie => match animation_state.current_animation {
@@ -795,6 +941,12 @@ fn animate_sprite(
                     AnimationType::Blocking => &assets.all_character_animations.zombie.block,
                     AnimationType::Victory => &assets.all_character_animations.zombie.victory,
                     AnimationType::Falling => &assets.all_character_animations.zombie.fall,
+                    AnimationType::Throw => &assets.all_character_animations.zombie.throw,
+                    AnimationType::Thrown => &assets.all_character_animations.zombie.thrown,
+                    AnimationType::Landing => &assets.all_character_animations.zombie.landing,
+                    AnimationType::PhaseShift => {
+                        &assets.all_character_animations.zombie.phase_shift
+                    }
                     AnimationType::SpecialAttack => &assets.all_character_animations.zombie.special,
                 },
                 CharacterType::Adventurer => match animation_state.current_animation {
@@ -814,6 +966,12 @@ fn animate_sprite(
                     AnimationType::Blocking => &assets.all_character_animations.adventurer.block,
                     AnimationType::Victory => &assets.all_character_animations.adventurer.victory,
                     AnimationType::Falling => &assets.all_character_animations.adventurer.fall,
+                    AnimationType::Throw => &assets.all_character_animations.adventurer.throw,
+                    AnimationType::Thrown => &assets.all_character_animations.adventurer.thrown,
+                    AnimationType::Landing => &assets.all_character_animations.adventurer.landing,
+                    AnimationType::PhaseShift => {
+                        &assets.all_character_animations.adventurer.phase_shift
+                    }
                     AnimationType::SpecialAttack => {
                         &assets.all_character_animations.adventurer.special
                     }
@@ -835,6 +993,12 @@ fn animate_sprite(
                     AnimationType::Blocking => &assets.all_character_animations.female.block,
                     AnimationType::Victory => &assets.all_character_animations.female.victory,
                     AnimationType::Falling => &assets.all_character_animations.female.fall,
+                    AnimationType::Throw => &assets.all_character_animations.female.throw,
+                    AnimationType::Thrown => &assets.all_character_animations.female.thrown,
+                    AnimationType::Landing => &assets.all_character_animations.female.landing,
+                    AnimationType::PhaseShift => {
+                        &assets.all_character_animations.female.phase_shift
+                    }
                     AnimationType::SpecialAttack => &assets.all_character_animations.female.special,
                 },
                 CharacterType::Soldier => match animation_state.current_animation {
@@ -854,6 +1018,12 @@ fn animate_sprite(
                     AnimationType::Blocking => &assets.all_character_animations.soldier.block,
                     AnimationType::Victory => &assets.all_character_animations.soldier.victory,
                     AnimationType::Falling => &assets.all_character_animations.soldier.fall,
+                    AnimationType::Throw => &assets.all_character_animations.soldier.throw,
+                    AnimationType::Thrown => &assets.all_character_animations.soldier.thrown,
+                    AnimationType::Landing => &assets.all_character_animations.soldier.landing,
+                    AnimationType::PhaseShift => {
+                        &assets.all_character_animations.soldier.phase_shift
+                    }
                     AnimationType::SpecialAttack => {
                         &assets.all_character_animations.soldier.special
                     }
@@ -925,9 +1095,10 @@ fn stop_victory_defeat_music(
     }
 }
 
-fn save_config_on_change(config: Res<GameConfig>) {
-    // Only save if the config has changed
-    if config.is_changed() {
+fn save_config_on_change(config: Res<GameConfig>, replays: Res<Replays>, session: Res<NetSession>) {
+    // Only save if the config has changed, and never save a replay's or an online host's
+    // settings as the player's
+    if config.is_changed() && !replays.is_playing() && !session.is_online() {
         GameConfig::save_config(config.as_ref());
     }
 }
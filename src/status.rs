@@ -0,0 +1,270 @@
+use bevy::prelude::*;
+
+use crate::combat::{apply_damage, DamageEvent};
+use crate::game_state::{AppState, BossType};
+use crate::player::{AttackType, ControlType};
+use crate::rng::{GameRng, RngStream};
+
+// Chance for a boss hit to apply its signature effect
+const STATUS_APPLY_CHANCE: f32 = 0.35;
+const DANGLING_DRAIN_PER_SECOND: f32 = 1.5; // HP per second, per stack
+const RACE_DELAY_FRAMES: usize = 6;
+const OVERFLOW_GROWTH_PER_STACK: f32 = 0.15;
+
+pub struct StatusPlugin;
+
+impl Plugin for StatusPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (
+                apply_boss_status_effects,
+                tick_status_effects.before(apply_damage),
+            )
+                .run_if(in_state(AppState::InGame)),
+        );
+    }
+}
+
+// -- Data --
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StatusKind {
+    Dereference, // Disables one attack button
+    Race,        // Delays every input
+    Dangling,    // Drains HP over time
+    Overflow,    // Grows the hurtboxes
+}
+
+// How re-applying an effect that is already active combines with it
+#[derive(Clone, Copy)]
+pub enum StackRule {
+    Refresh,            // Restart the timer
+    Stack { max: u32 }, // Add a stack, up to max, and restart the timer
+}
+
+impl StatusKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            StatusKind::Dereference => "DEREFERENCE",
+            StatusKind::Race => "RACE",
+            StatusKind::Dangling => "DANGLING",
+            StatusKind::Overflow => "OVERFLOW",
+        }
+    }
+
+    fn duration_seconds(self) -> f32 {
+        match self {
+            StatusKind::Dereference => 4.0,
+            StatusKind::Race => 5.0,
+            StatusKind::Dangling => 6.0,
+            StatusKind::Overflow => 6.0,
+        }
+    }
+
+    fn stack_rule(self) -> StackRule {
+        match self {
+            StatusKind::Dereference | StatusKind::Race => StackRule::Refresh,
+            StatusKind::Dangling | StatusKind::Overflow => StackRule::Stack { max: 3 },
+        }
+    }
+
+    // Signature effect each boss applies with its hits
+    pub fn for_boss(boss: BossType, rng: &mut GameRng) -> Self {
+        match boss {
+            BossType::NullPointer => StatusKind::Dereference,
+            BossType::DataRace => StatusKind::Race,
+            BossType::UseAfterFree => StatusKind::Dangling,
+            BossType::BufferOverflow => StatusKind::Overflow,
+            // Anything can happen
+            BossType::UndefinedBehavior => match rng.index(RngStream::Status, 4) {
+                0 => StatusKind::Dereference,
+                1 => StatusKind::Race,
+                2 => StatusKind::Dangling,
+                _ => StatusKind::Overflow,
+            },
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub timer: Timer,
+    pub stacks: u32,
+    pub disabled_attack: Option<AttackType>, // The button a dereference disables
+}
+
+// Timed effects active on a fighter
+#[derive(Component, Clone, Default)]
+pub struct StatusEffects {
+    pub effects: Vec<StatusEffect>,
+    drained: f32, // Fractional HP drained but not yet applied
+}
+
+impl StatusEffects {
+    pub fn apply(&mut self, kind: StatusKind, rng: &mut GameRng) {
+        let duration = std::time::Duration::from_secs_f32(kind.duration_seconds());
+        if let Some(effect) = self.effects.iter_mut().find(|effect| effect.kind == kind) {
+            if let StackRule::Stack { max } = kind.stack_rule() {
+                effect.stacks = (effect.stacks + 1).min(max);
+            }
+            effect.timer = Timer::new(duration, TimerMode::Once);
+            return;
+        }
+
+        let disabled_attack =
+            (kind == StatusKind::Dereference).then(|| match rng.index(RngStream::Status, 3) {
+                0 => AttackType::Light,
+                1 => AttackType::Heavy,
+                _ => AttackType::Kick,
+            });
+        self.effects.push(StatusEffect {
+            kind,
+            timer: Timer::new(duration, TimerMode::Once),
+            stacks: 1,
+            disabled_attack,
+        });
+    }
+
+    pub fn stacks(&self, kind: StatusKind) -> u32 {
+        self.effects
+            .iter()
+            .find(|effect| effect.kind == kind)
+            .map(|effect| effect.stacks)
+            .unwrap_or(0)
+    }
+
+    pub fn disabled_attack(&self) -> Option<AttackType> {
+        self.effects
+            .iter()
+            .find_map(|effect| effect.disabled_attack)
+    }
+
+    // Ticks that every input arrives late
+    pub fn input_delay_frames(&self) -> usize {
+        if self.stacks(StatusKind::Race) > 0 {
+            RACE_DELAY_FRAMES
+        } else {
+            0
+        }
+    }
+
+    pub fn hurtbox_scale(&self) -> f32 {
+        1.0 + OVERFLOW_GROWTH_PER_STACK * self.stacks(StatusKind::Overflow) as f32
+    }
+}
+
+// -- Systems --
+
+fn apply_boss_status_effects(
+    mut damage_reader: EventReader<DamageEvent>,
+    attacker_query: Query<&ControlType>,
+    mut target_query: Query<&mut StatusEffects>,
+    mut rng: ResMut<GameRng>,
+) {
+    for event in damage_reader.read().filter(|event| !event.drain) {
+        let Ok(ControlType::AI(boss)) = attacker_query.get(event.attacker) else {
+            continue;
+        };
+        if !rng.chance(RngStream::Status, STATUS_APPLY_CHANCE) {
+            continue;
+        }
+
+        if let Ok(mut status) = target_query.get_mut(event.target) {
+            let kind = StatusKind::for_boss(*boss, &mut rng);
+            tracing::info!("Status effect applied: {:?}", kind);
+            status.apply(kind, &mut rng);
+        }
+    }
+}
+
+// Drained HP goes through `apply_damage`, so a lethal drain is a KO on the same tick
+fn tick_status_effects(
+    time: Res<Time>,
+    mut damage_writer: EventWriter<DamageEvent>,
+    mut query: Query<(Entity, &mut StatusEffects)>,
+) {
+    for (entity, mut status) in query.iter_mut() {
+        for effect in status.effects.iter_mut() {
+            effect.timer.tick(time.delta());
+        }
+        status.effects.retain(|effect| !effect.timer.finished());
+
+        // Dangling drains HP continuously, applied a whole point at a time
+        let stacks = status.stacks(StatusKind::Dangling);
+        if stacks == 0 {
+            status.drained = 0.0;
+            continue;
+        }
+        status.drained += DANGLING_DRAIN_PER_SECOND * stacks as f32 * time.delta_seconds();
+        let whole = status.drained.floor();
+        if whole >= 1.0 {
+            status.drained -= whole;
+            damage_writer.send(DamageEvent {
+                target: entity,
+                attacker: entity,
+                damage: whole as i32,
+                drain: true,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn stacking_effects_add_up_to_their_max() {
+        let mut rng = GameRng::new(1);
+        let mut status = StatusEffects::default();
+        for expected in [1, 2, 3, 3] {
+            status.apply(StatusKind::Dangling, &mut rng);
+            assert_eq!(status.stacks(StatusKind::Dangling), expected);
+        }
+        assert_eq!(status.effects.len(), 1);
+    }
+
+    #[test]
+    fn refreshing_effects_restart_without_stacking() {
+        let mut rng = GameRng::new(1);
+        let mut status = StatusEffects::default();
+        status.apply(StatusKind::Race, &mut rng);
+        status.effects[0].timer.tick(Duration::from_secs(2));
+        status.apply(StatusKind::Race, &mut rng);
+        assert_eq!(status.stacks(StatusKind::Race), 1);
+        assert_eq!(status.effects[0].timer.elapsed(), Duration::ZERO);
+        assert!(status.input_delay_frames() > 0);
+    }
+
+    #[test]
+    fn reapplying_keeps_the_disabled_button() {
+        let mut rng = GameRng::new(5);
+        let mut status = StatusEffects::default();
+        status.apply(StatusKind::Dereference, &mut rng);
+        let disabled = status.disabled_attack();
+        assert!(disabled.is_some());
+        for _ in 0..10 {
+            status.apply(StatusKind::Dereference, &mut rng);
+            assert_eq!(status.disabled_attack(), disabled);
+        }
+    }
+
+    #[test]
+    fn different_effects_are_tracked_separately() {
+        let mut rng = GameRng::new(1);
+        let mut status = StatusEffects::default();
+        status.apply(StatusKind::Overflow, &mut rng);
+        status.apply(StatusKind::Overflow, &mut rng);
+        status.apply(StatusKind::Dangling, &mut rng);
+        assert_eq!(status.stacks(StatusKind::Overflow), 2);
+        assert_eq!(status.stacks(StatusKind::Dangling), 1);
+        assert_eq!(status.stacks(StatusKind::Race), 0);
+        assert_eq!(
+            status.hurtbox_scale(),
+            1.0 + OVERFLOW_GROWTH_PER_STACK * 2.0
+        );
+    }
+}
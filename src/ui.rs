@@ -1,9 +1,16 @@
 use bevy::prelude::*;
 use std::time::Duration;
 
+use crate::ai::Blackboard;
+use crate::boss::{boss_phases, PhaseChangeEvent};
+use crate::combat::{Combo, CounterHitEvent, ParryEvent, SuperEvent, SuperMeter, MAX_SUPER_METER};
 use crate::game_state::{AppState, BossType, GameConfig, Winner};
+use crate::habits::HabitProfile;
 use crate::menu::BossDisplay;
-use crate::player::{ControlType, Health, Player};
+use crate::netplay::can_pause;
+use crate::player::{AttackType, BlockState, ControlType, Health, Player, MAX_GUARD};
+use crate::replay::Replays;
+use crate::status::StatusEffects;
 use crate::{GameAssets, VictoryDefeatMusic};
 
 pub struct UiPlugin;
@@ -15,11 +22,24 @@ impl Plugin for UiPlugin {
                 Update,
                 (
                     update_health_bars.run_if(in_state(AppState::InGame)),
-                    handle_pause_button.run_if(in_state(AppState::InGame)),
-                    handle_p_key_pause.run_if(in_state(AppState::InGame)),
+                    update_guard_bars.run_if(in_state(AppState::InGame)),
+                    update_super_bars.run_if(in_state(AppState::InGame)),
+                    update_status_text.run_if(in_state(AppState::InGame)),
+                    handle_pause_button
+                        .run_if(in_state(AppState::InGame))
+                        .run_if(can_pause),
+                    handle_p_key_pause
+                        .run_if(in_state(AppState::InGame))
+                        .run_if(can_pause),
                     update_combo_counter.run_if(in_state(AppState::InGame)),
                     update_damage_numbers.run_if(in_state(AppState::InGame)),
                     spawn_damage_number.run_if(in_state(AppState::InGame)),
+                    spawn_parry_popup.run_if(in_state(AppState::InGame)),
+                    spawn_super_popup.run_if(in_state(AppState::InGame)),
+                    spawn_counter_popup.run_if(in_state(AppState::InGame)),
+                    spawn_phase_popup.run_if(in_state(AppState::InGame)),
+                    toggle_habit_overlay.run_if(in_state(AppState::InGame)),
+                    update_habit_overlay.run_if(in_state(AppState::InGame)),
                 ),
             )
             .add_systems(OnEnter(AppState::Paused), setup_pause_screen)
@@ -49,6 +69,19 @@ impl Plugin for UiPlugin {
 #[derive(Component)]
 struct HealthBar(u8); // Holds the player ID (1 or 2)
 
+#[derive(Component)]
+struct GuardBar(u8); // Holds the player ID (1 or 2)
+
+#[derive(Component)]
+struct SuperBar(u8); // Holds the player ID (1 or 2)
+
+#[derive(Component)]
+struct StatusText(u8); // Holds the player ID (1 or 2)
+
+// Debug readout of the player's learned habits, toggled with F3
+#[derive(Component)]
+struct HabitOverlay;
+
 #[derive(Component)]
 struct HealthBarContainer; // For cleanup of health bar UI containers
 
@@ -69,7 +102,7 @@ struct ExitButton;
 
 // Combo and Damage Display
 #[derive(Component)]
-struct ComboCounter;
+struct ComboCounter(u8); // Holds the attacking player's ID
 
 #[derive(Component)]
 struct DamageNumber {
@@ -98,7 +131,11 @@ fn boss_name(b: BossType) -> &'static str {
 // -- Systems --
 
 #[allow(clippy::type_complexity)]
-fn setup_ui(mut commands: Commands, player_query: Query<(&Player, &ControlType)>) {
+fn setup_ui(
+    mut commands: Commands,
+    player_query: Query<(&Player, &ControlType)>,
+    replays: Res<Replays>,
+) {
     // Player 1 Health Container
     commands
         .spawn((
@@ -106,7 +143,7 @@ fn setup_ui(mut commands: Commands, player_query: Query<(&Player, &ControlType)>
                 style: Style {
                     position_type: PositionType::Absolute,
                     width: Val::Percent(40.0),
-                    height: Val::Px(60.0),
+                    height: Val::Px(106.0),
                     left: Val::Percent(5.0),
                     top: Val::Percent(2.0),
                     flex_direction: FlexDirection::Column,
@@ -164,6 +201,71 @@ fn setup_ui(mut commands: Commands, player_query: Query<(&Player, &ControlType)>
                         HealthBar(1),
                     ));
                 });
+
+            // Guard meter
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.0),
+                        height: Val::Px(8.0),
+                        ..default()
+                    },
+                    background_color: Color::srgb(0.2, 0.2, 0.2).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(100.0),
+                                height: Val::Percent(100.0),
+                                ..default()
+                            },
+                            background_color: Color::srgb(0.3, 0.6, 1.0).into(),
+                            ..default()
+                        },
+                        GuardBar(1),
+                    ));
+                });
+
+            // Super meter
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.0),
+                        height: Val::Px(8.0),
+                        ..default()
+                    },
+                    background_color: Color::srgb(0.2, 0.2, 0.2).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(0.0),
+                                height: Val::Percent(100.0),
+                                ..default()
+                            },
+                            background_color: Color::srgb(0.6, 0.5, 0.1).into(),
+                            ..default()
+                        },
+                        SuperBar(1),
+                    ));
+                });
+
+            // Active status effects
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 14.0,
+                        color: Color::srgb(0.8, 0.5, 1.0),
+                        ..default()
+                    },
+                ),
+                StatusText(1),
+            ));
         });
 
     // Player 2 Health Container
@@ -173,7 +275,7 @@ fn setup_ui(mut commands: Commands, player_query: Query<(&Player, &ControlType)>
                 style: Style {
                     position_type: PositionType::Absolute,
                     width: Val::Percent(40.0),
-                    height: Val::Px(60.0),
+                    height: Val::Px(106.0),
                     right: Val::Percent(5.0),
                     top: Val::Percent(2.0),
                     flex_direction: FlexDirection::Column,
@@ -231,6 +333,71 @@ fn setup_ui(mut commands: Commands, player_query: Query<(&Player, &ControlType)>
                         HealthBar(2),
                     ));
                 });
+
+            // Guard meter
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.0),
+                        height: Val::Px(8.0),
+                        ..default()
+                    },
+                    background_color: Color::srgb(0.2, 0.2, 0.2).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(100.0),
+                                height: Val::Percent(100.0),
+                                ..default()
+                            },
+                            background_color: Color::srgb(0.3, 0.6, 1.0).into(),
+                            ..default()
+                        },
+                        GuardBar(2),
+                    ));
+                });
+
+            // Super meter
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.0),
+                        height: Val::Px(8.0),
+                        ..default()
+                    },
+                    background_color: Color::srgb(0.2, 0.2, 0.2).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(0.0),
+                                height: Val::Percent(100.0),
+                                ..default()
+                            },
+                            background_color: Color::srgb(0.6, 0.5, 0.1).into(),
+                            ..default()
+                        },
+                        SuperBar(2),
+                    ));
+                });
+
+            // Active status effects
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 14.0,
+                        color: Color::srgb(0.8, 0.5, 1.0),
+                        ..default()
+                    },
+                ),
+                StatusText(2),
+            ));
         });
 
     // Central Boss Display (only if vs AI)
@@ -261,6 +428,11 @@ fn setup_ui(mut commands: Commands, player_query: Query<(&Player, &ControlType)>
                         _ => None,
                     })
                     .unwrap_or("VS BOSS".to_string());
+                let boss_label = if replays.is_playing() {
+                    format!("{} (REPLAY)", boss_label)
+                } else {
+                    boss_label
+                };
 
                 parent.spawn((
                     TextBundle::from_section(
@@ -276,6 +448,26 @@ fn setup_ui(mut commands: Commands, player_query: Query<(&Player, &ControlType)>
             });
     }
 
+    // Habit Overlay (hidden until toggled)
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 14.0,
+                color: Color::srgb(0.6, 1.0, 0.6),
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Percent(5.0),
+            bottom: Val::Percent(5.0),
+            ..default()
+        }),
+        Visibility::Hidden,
+        HabitOverlay,
+    ));
+
     // Pause Button - More Centrally Positioned
     commands
         .spawn((
@@ -322,6 +514,72 @@ fn update_health_bars(
     }
 }
 
+fn update_guard_bars(
+    player_query: Query<(&BlockState, &Player)>,
+    mut guard_bar_query: Query<(&mut Style, &GuardBar)>,
+) {
+    for (mut style, guard_bar) in guard_bar_query.iter_mut() {
+        for (block_state, player) in player_query.iter() {
+            if player.id == guard_bar.0 {
+                style.width = Val::Percent((block_state.guard / MAX_GUARD) * 100.0);
+            }
+        }
+    }
+}
+
+fn update_super_bars(
+    player_query: Query<(&SuperMeter, &Player)>,
+    mut super_bar_query: Query<(&mut Style, &mut BackgroundColor, &SuperBar)>,
+) {
+    for (mut style, mut color, super_bar) in super_bar_query.iter_mut() {
+        for (meter, player) in player_query.iter() {
+            if player.id == super_bar.0 {
+                style.width = Val::Percent((meter.current / MAX_SUPER_METER) * 100.0);
+                // Bright gold once a super is available
+                *color = if meter.is_full() {
+                    Color::srgb(1.0, 0.85, 0.1).into()
+                } else {
+                    Color::srgb(0.6, 0.5, 0.1).into()
+                };
+            }
+        }
+    }
+}
+
+// Lists each active effect with its stacks and remaining time, e.g. "DANGLING x2 3.1s"
+fn update_status_text(
+    player_query: Query<(&StatusEffects, &Player)>,
+    mut text_query: Query<(&mut Text, &StatusText)>,
+) {
+    for (mut text, status_text) in text_query.iter_mut() {
+        for (status, player) in player_query.iter() {
+            if player.id != status_text.0 {
+                continue;
+            }
+            let entries: Vec<String> = status
+                .effects
+                .iter()
+                .map(|effect| {
+                    let mut entry = effect.kind.label().to_string();
+                    // Name the button a dereference took away
+                    match effect.disabled_attack {
+                        Some(AttackType::Light) => entry.push_str(" (LIGHT)"),
+                        Some(AttackType::Heavy) => entry.push_str(" (HEAVY)"),
+                        Some(AttackType::Kick) => entry.push_str(" (KICK)"),
+                        _ => {}
+                    }
+                    if effect.stacks > 1 {
+                        entry.push_str(&format!(" x{}", effect.stacks));
+                    }
+                    entry.push_str(&format!(" {:.1}s", effect.timer.remaining_secs()));
+                    entry
+                })
+                .collect();
+            text.sections[0].value = entries.join("   ");
+        }
+    }
+}
+
 fn setup_game_over_screen(
     mut commands: Commands,
     winner: Res<Winner>,
@@ -586,6 +844,69 @@ fn handle_p_key_pause(
     }
 }
 
+fn toggle_habit_overlay(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut overlay_query: Query<&mut Visibility, With<HabitOverlay>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F3) {
+        return;
+    }
+    for mut visibility in overlay_query.iter_mut() {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Visible,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+// Shows what the boss has learned about the player this fight and how it is countering it
+fn update_habit_overlay(
+    time: Res<Time<Fixed>>,
+    profile: Res<HabitProfile>,
+    board_query: Query<&Blackboard>,
+    mut overlay_query: Query<(&mut Text, &Visibility), With<HabitOverlay>>,
+) {
+    let now = time.elapsed_seconds();
+    for (mut text, visibility) in overlay_query.iter_mut() {
+        if *visibility == Visibility::Hidden {
+            continue;
+        }
+        let favourite = profile
+            .favourite_attack()
+            .map(|(attack_type, share)| format!("{:?} ({:.0}%)", attack_type, share * 100.0))
+            .unwrap_or("-".to_string());
+        let mut lines = vec![
+            "HABITS (F3)".to_string(),
+            format!(
+                "Attacks: {}   Favourite: {}",
+                profile.total_attacks(),
+                favourite
+            ),
+            format!(
+                "Jumps: {} ({:.1}/min)",
+                profile.jumps,
+                profile.jumps_per_minute(now)
+            ),
+            format!(
+                "Blocks after hit: {}/{}   Avg delay: {:.0}f",
+                profile.blocks_after_hit,
+                profile.hit_recoveries,
+                profile.block_delay_frames()
+            ),
+        ];
+        for board in board_query.iter() {
+            lines.push(format!(
+                "Boss bias - anti-air: {:.2}   throw blockers: {:.2}   read {:?} x{:.2}",
+                board.habits.anti_air,
+                board.habits.throw_blockers,
+                board.habits.favourite,
+                board.habits.read_factor
+            ));
+        }
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
 fn reset_winner_on_menu(mut winner: ResMut<Winner>) {
     winner.player_id = None;
     winner.is_human_winner = None;
@@ -600,51 +921,50 @@ fn cleanup_pause_button(mut commands: Commands, query: Query<Entity, With<PauseB
 
 fn update_combo_counter(
     mut commands: Commands,
-    combo_query: Query<Entity, With<ComboCounter>>,
-    mut text_query: Query<&mut Text, With<ComboCounter>>,
-    mut damage_events: EventReader<crate::combat::DamageEvent>,
+    combo_query: Query<(&Combo, &Player)>,
+    mut counter_query: Query<(Entity, &ComboCounter, &mut Text)>,
 ) {
-    let mut combo_count = 0;
-
-    // Count recent damage events (simplified combo tracking)
-    for _ in damage_events.read() {
-        combo_count += 1;
-    }
+    for (combo, player) in combo_query.iter() {
+        let counter = counter_query
+            .iter_mut()
+            .find(|(_, counter, _)| counter.0 == player.id);
+
+        // Only strings of two or more hits count as a combo
+        if combo.hits < 2 {
+            if let Some((entity, _, _)) = counter {
+                commands.entity(entity).despawn_recursive();
+            }
+            continue;
+        }
 
-    if combo_count > 0 {
-        // Update or create combo counter
-        if let Ok(mut text) = text_query.get_single_mut() {
-            text.sections[0].value = format!("COMBO: {combo_count}x");
+        let combo_text = format!("COMBO: {}x\n{} DMG", combo.hits, combo.total_damage);
+        if let Some((_, _, mut text)) = counter {
+            text.sections[0].value = combo_text;
         } else {
-            // Create combo counter if it doesn't exist
-            commands
-                .spawn(NodeBundle {
-                    style: Style {
-                        position_type: PositionType::Absolute,
-                        top: Val::Percent(10.0),
-                        left: Val::Percent(45.0),
+            // Show the counter on the attacker's side of the screen
+            let mut style = Style {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(12.0),
+                ..default()
+            };
+            if player.id == 1 {
+                style.left = Val::Percent(10.0);
+            } else {
+                style.right = Val::Percent(10.0);
+            }
+
+            commands.spawn((
+                TextBundle::from_section(
+                    combo_text,
+                    TextStyle {
+                        font_size: 32.0,
+                        color: Color::srgb(1.0, 1.0, 0.0), // Yellow
                         ..default()
                     },
-                    ..default()
-                })
-                .with_children(|parent| {
-                    parent.spawn((
-                        TextBundle::from_section(
-                            format!("COMBO: {combo_count}x"),
-                            TextStyle {
-                                font_size: 32.0,
-                                color: Color::srgb(1.0, 1.0, 0.0), // Yellow
-                                ..default()
-                            },
-                        ),
-                        ComboCounter,
-                    ));
-                });
-        }
-    } else {
-        // Remove combo counter if combo ended
-        for entity in combo_query.iter() {
-            commands.entity(entity).despawn_recursive();
+                )
+                .with_style(style),
+                ComboCounter(player.id),
+            ));
         }
     }
 }
@@ -654,7 +974,7 @@ fn spawn_damage_number(
     mut damage_events: EventReader<crate::combat::DamageEvent>,
     player_query: Query<(&Transform, &Player)>,
 ) {
-    for event in damage_events.read() {
+    for event in damage_events.read().filter(|event| !event.drain) {
         // Find the player that was damaged by checking if the target entity has a Player component
         if let Ok((transform, _)) = player_query.get(event.target) {
             // Spawn floating damage number as a sprite in world space
@@ -684,6 +1004,144 @@ fn spawn_damage_number(
     }
 }
 
+fn spawn_parry_popup(
+    mut commands: Commands,
+    mut parry_events: EventReader<ParryEvent>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    for event in parry_events.read() {
+        if let Ok(transform) = player_query.get(event.defender) {
+            // Reuses the floating damage number behaviour
+            commands.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        "PARRY!",
+                        TextStyle {
+                            font_size: 28.0,
+                            color: Color::srgb(0.3, 0.9, 1.0), // Cyan
+                            ..default()
+                        },
+                    ),
+                    transform: Transform::from_xyz(
+                        transform.translation.x,
+                        transform.translation.y + 70.0,
+                        10.0, // Above other sprites
+                    ),
+                    ..default()
+                },
+                DamageNumber {
+                    timer: Timer::new(Duration::from_millis(800), TimerMode::Once),
+                    velocity: Vec2::new(0.0, 40.0), // Float upward
+                },
+            ));
+        }
+    }
+}
+
+fn spawn_counter_popup(
+    mut commands: Commands,
+    mut counter_events: EventReader<CounterHitEvent>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    for event in counter_events.read() {
+        if let Ok(transform) = player_query.get(event.defender) {
+            // Reuses the floating damage number behaviour, above the damage number
+            commands.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        "COUNTER",
+                        TextStyle {
+                            font_size: 30.0,
+                            color: Color::srgb(1.0, 0.4, 0.1), // Orange
+                            ..default()
+                        },
+                    ),
+                    transform: Transform::from_xyz(
+                        transform.translation.x,
+                        transform.translation.y + 90.0,
+                        10.0, // Above other sprites
+                    ),
+                    ..default()
+                },
+                DamageNumber {
+                    timer: Timer::new(Duration::from_millis(800), TimerMode::Once),
+                    velocity: Vec2::new(0.0, 40.0), // Float upward
+                },
+            ));
+        }
+    }
+}
+
+fn spawn_phase_popup(
+    mut commands: Commands,
+    mut phase_events: EventReader<PhaseChangeEvent>,
+    boss_query: Query<(&Transform, &ControlType), With<Player>>,
+) {
+    for event in phase_events.read() {
+        let Ok((transform, ControlType::AI(boss))) = boss_query.get(event.boss) else {
+            continue;
+        };
+        let phase = &boss_phases(*boss)[event.phase];
+        // Lingers over the boss for most of the transition
+        commands.spawn((
+            Text2dBundle {
+                text: Text::from_section(
+                    format!("PHASE {}: {}", event.phase + 1, phase.name.to_uppercase()),
+                    TextStyle {
+                        font_size: 32.0,
+                        color: Color::srgb(1.0, 0.2, 0.2), // Red
+                        ..default()
+                    },
+                ),
+                transform: Transform::from_xyz(
+                    transform.translation.x,
+                    transform.translation.y + 100.0,
+                    10.0, // Above other sprites
+                ),
+                ..default()
+            },
+            DamageNumber {
+                timer: Timer::new(Duration::from_millis(1500), TimerMode::Once),
+                velocity: Vec2::new(0.0, 20.0), // Float upward slowly
+            },
+        ));
+    }
+}
+
+fn spawn_super_popup(
+    mut commands: Commands,
+    mut super_events: EventReader<SuperEvent>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    for event in super_events.read() {
+        if let Ok(transform) = player_query.get(event.attacker) {
+            // Hangs over the attacker during the freeze, then floats away
+            commands.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        "SUPER!",
+                        TextStyle {
+                            font_size: 36.0,
+                            color: Color::srgb(1.0, 0.85, 0.1), // Gold
+                            ..default()
+                        },
+                    ),
+                    transform: Transform::from_xyz(
+                        transform.translation.x,
+                        transform.translation.y + 80.0,
+                        10.0, // Above other sprites
+                    ),
+                    ..default()
+                },
+                DamageNumber {
+                    timer: Timer::new(Duration::from_millis(800), TimerMode::Once),
+                    velocity: Vec2::new(0.0, 40.0), // Float upward
+                },
+            ));
+        }
+    }
+}
+
 fn update_damage_numbers(
     mut commands: Commands,
     time: Res<Time>,
@@ -722,6 +1180,7 @@ fn cleanup_game_ui(
             With<BossDisplay>,
             With<ComboCounter>,
             With<DamageNumber>,
+            With<HabitOverlay>,
         )>,
     >,
 ) {